@@ -0,0 +1,44 @@
+// Copyright 2021 The Druid Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Emits one cfg per backend, so that `platform/mod.rs` and the public API
+//! that only some backends support are gated on the same condition.
+//!
+//! - `skia_backend`: `winit_x11` or `winit_wayland` on Linux or macOS.
+//! - `dri_backend`: `direct_render` on Linux.
+//! - `headless_backend`: `headless` on Linux or macOS.
+
+use std::env;
+
+fn main() {
+    let feature = |name: &str| env::var_os(format!("CARGO_FEATURE_{}", name)).is_some();
+    let target_os = env::var("CARGO_CFG_TARGET_OS").unwrap_or_default();
+    let linux = target_os == "linux";
+    let linux_or_macos = linux || target_os == "macos";
+
+    let backends = [
+        (
+            "skia_backend",
+            (feature("WINIT_X11") || feature("WINIT_WAYLAND")) && linux_or_macos,
+        ),
+        ("dri_backend", feature("DIRECT_RENDER") && linux),
+        ("headless_backend", feature("HEADLESS") && linux_or_macos),
+    ];
+    for (name, enabled) in backends.iter() {
+        println!("cargo:rustc-check-cfg=cfg({})", name);
+        if *enabled {
+            println!("cargo:rustc-cfg={}", name);
+        }
+    }
+}
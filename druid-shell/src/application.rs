@@ -14,14 +14,27 @@
 
 //! The top-level application type.
 
+#[cfg(skia_backend)]
+use std::any::Any;
 use std::cell::RefCell;
 use std::rc::Rc;
 use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(skia_backend)]
+use std::sync::mpsc;
+#[cfg(skia_backend)]
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
 
 use crate::clipboard::Clipboard;
 use crate::error::Error;
+#[cfg(any(skia_backend, dri_backend, target_arch = "wasm32"))]
+use crate::hotkey::HotKey;
 use crate::platform::application as platform;
+#[cfg(skia_backend)]
+use crate::screen::Monitor;
 use crate::util;
+#[cfg(any(skia_backend, dri_backend, target_arch = "wasm32"))]
+use crate::window::IdleToken;
 
 /// A top-level handler that is not associated with any window.
 ///
@@ -42,6 +55,48 @@ pub trait AppHandler {
     fn command(&mut self, id: u32) {}
 }
 
+/// The light or dark appearance chosen in the system settings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Theme {
+    Light,
+    Dark,
+}
+
+/// Rendering statistics, from [`Application::frame_stats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FrameStats {
+    /// The frames drawn per second, over the last whole second.
+    pub fps: f64,
+    /// How long the last frame took to render and present.
+    pub last_frame_time: Duration,
+    /// The number of frames that took longer than the target frame rate allows, and so
+    /// missed their slot. This counts from the start of the application.
+    pub dropped: u64,
+}
+
+/// Sends values from any thread to the handler of the focused window, see
+/// [`Application::user_event_sender`].
+#[cfg(skia_backend)]
+#[derive(Clone)]
+pub struct UserEventSender(platform::UserEventSender);
+
+#[cfg(skia_backend)]
+impl UserEventSender {
+    /// Send `event` to the [`WinHandler::user_event`] method of the focused window.
+    ///
+    /// The event loop is woken up to deliver it. Events sent before the application runs
+    /// are delivered once it does.
+    ///
+    /// # Errors
+    ///
+    /// Errors if the application has stopped running.
+    ///
+    /// [`WinHandler::user_event`]: crate::WinHandler::user_event
+    pub fn send(&self, event: impl Any + Send) -> Result<(), Error> {
+        Ok(self.0.send(Box::new(event))?)
+    }
+}
+
 /// The top level application object.
 ///
 /// This can be thought of as a reference and it can be safely cloned.
@@ -160,6 +215,57 @@ impl Application {
         util::release_main_thread();
     }
 
+    /// Create an `Application` on a new thread, and run it there.
+    ///
+    /// This is for hosts that keep the main thread for themselves. `setup` is called on the
+    /// new thread with the new `Application` to create the windows, and the handler it
+    /// returns is passed to [`run`]. The returned sender delivers values to the window
+    /// handlers, and the thread finishes once the application quits.
+    ///
+    /// Whether the event loop can run off the main thread depends on the platform:
+    ///
+    /// | Platform                           | Supported |
+    /// |------------------------------------|-----------|
+    /// | Linux and the BSDs, X11 or Wayland | yes       |
+    /// | macOS                              | no        |
+    ///
+    /// # Errors
+    ///
+    /// Errors if the platform needs the event loop on the main thread, if an `Application`
+    /// has already been created, or if the thread can't be spawned.
+    ///
+    /// [`run`]: Application::run
+    #[cfg(skia_backend)]
+    pub fn run_on_thread<F>(setup: F) -> Result<(UserEventSender, JoinHandle<()>), Error>
+    where
+        F: FnOnce(&Application) -> Option<Box<dyn AppHandler>> + Send + 'static,
+    {
+        if !platform::Application::supports_any_thread() {
+            return Err(anyhow::anyhow!("the event loop must run on the main thread").into());
+        }
+        let (sender, receiver) = mpsc::channel();
+        let thread = thread::Builder::new()
+            .name("druid-shell".into())
+            .spawn(move || {
+                let app = match Application::new() {
+                    Ok(app) => app,
+                    Err(err) => {
+                        let _ = sender.send(Err(err));
+                        return;
+                    }
+                };
+                app.platform_app.allow_any_thread();
+                let _ = sender.send(Ok(app.user_event_sender()));
+                let handler = setup(&app);
+                app.run(handler);
+            })
+            .map_err(anyhow::Error::from)?;
+        match receiver.recv() {
+            Ok(events) => Ok((events?, thread)),
+            Err(_) => Err(anyhow::anyhow!("the application thread exited early").into()),
+        }
+    }
+
     /// Quit the `Application`.
     ///
     /// This will cause [`run`] to return control back to the calling function.
@@ -169,6 +275,143 @@ impl Application {
         self.platform_app.quit()
     }
 
+    /// Process the pending input, timers and idle callbacks once, without blocking.
+    ///
+    /// This is an alternative to [`run`] for hosts that own the main loop: they call this
+    /// and [`render_now`] at their own cadence. Windows are only rendered by
+    /// [`render_now`] in this mode. Returns `false` once all the windows are closed.
+    ///
+    /// [`run`]: Application::run
+    /// [`render_now`]: Application::render_now
+    #[cfg(skia_backend)]
+    pub fn pump_events(&self) -> bool {
+        self.platform_app.pump_events()
+    }
+
+    /// Render and present the windows that have damage or requested an animation frame.
+    ///
+    /// This does nothing until [`pump_events`] has been called.
+    ///
+    /// [`pump_events`]: Application::pump_events
+    #[cfg(skia_backend)]
+    pub fn render_now(&self) {
+        self.platform_app.render_now()
+    }
+
+    /// Set the frame rate that window redraws are paced to.
+    ///
+    /// By default redraws follow the refresh rate of the monitor the window is on, or 60
+    /// frames per second if it isn't known. 0 lets windows redraw as often as they ask to.
+    #[cfg(skia_backend)]
+    pub fn set_target_fps(&self, fps: u32) {
+        self.platform_app.set_target_fps(fps)
+    }
+
+    /// Set whether consecutive mouse moves are merged.
+    ///
+    /// Fast mice can report many moves between two frames. By default only the last
+    /// position is delivered to [`mouse_move`], once per loop iteration and always before
+    /// any other event of the window, which saves redundant hit testing and layout.
+    /// While a mouse button is held every move is delivered, so drag paths stay accurate.
+    /// Pass `false` to deliver every move as it arrives.
+    ///
+    /// [`mouse_move`]: crate::WinHandler::mouse_move
+    #[cfg(skia_backend)]
+    pub fn set_coalesce_mouse_moves(&self, coalesce: bool) {
+        self.platform_app.set_coalesce_mouse_moves(coalesce)
+    }
+
+    /// Set the OpenGL and OpenGL ES versions to request for the windows' GL contexts.
+    ///
+    /// OpenGL is tried first, then OpenGL ES. The defaults are OpenGL 4.6 and OpenGL ES 3.1;
+    /// older GPUs, embedded boards and virtual machines may need lower versions, such as
+    /// OpenGL ES 2.0. This applies to windows created after the call. If no context can be
+    /// created, the window falls back to software rendering and the error is logged.
+    #[cfg(skia_backend)]
+    pub fn set_gl_versions(&self, opengl: (u8, u8), opengles: (u8, u8)) {
+        self.platform_app.set_gl_versions(opengl, opengles)
+    }
+
+    /// Register `hotkey` as a global hotkey, which works even when the application isn't
+    /// focused.
+    ///
+    /// When it is pressed, `token` is delivered to the [`idle`] method of one of the
+    /// application's windows. Registering another hotkey with the same token replaces
+    /// this one. This fails if the hotkey is taken by another application, or has a key
+    /// that can't be grabbed. On the DRI and web backends this does nothing.
+    ///
+    /// [`idle`]: crate::WinHandler::idle
+    #[cfg(any(skia_backend, dri_backend, target_arch = "wasm32"))]
+    pub fn register_global_hotkey(&self, hotkey: HotKey, token: IdleToken) -> Result<(), Error> {
+        Ok(self.platform_app.register_global_hotkey(hotkey, token)?)
+    }
+
+    /// Unregister the global hotkey that delivers `token`, if there is one.
+    #[cfg(any(skia_backend, dri_backend, target_arch = "wasm32"))]
+    pub fn unregister_global_hotkey(&self, token: IdleToken) -> Result<(), Error> {
+        Ok(self.platform_app.unregister_global_hotkey(token)?)
+    }
+
+    /// Returns whether the system is in light or dark mode.
+    ///
+    /// This is [`Theme::Light`] if the system has no preference. The DRI backend reads
+    /// it from the `DRUID_SHELL_THEME` environment variable, set to `light` or `dark`.
+    /// Changes are reported by [`WinHandler::theme_changed`].
+    ///
+    /// [`WinHandler::theme_changed`]: crate::WinHandler::theme_changed
+    #[cfg(any(skia_backend, dri_backend, headless_backend, target_arch = "wasm32"))]
+    pub fn system_theme(&self) -> Theme {
+        self.platform_app.system_theme()
+    }
+
+    /// Returns the monitors of the system.
+    ///
+    /// On the skia backend these are known once the application runs, and refreshed when
+    /// a window's scale changes; the list is empty before [`run`] is called.
+    ///
+    /// [`run`]: Application::run
+    #[cfg(skia_backend)]
+    pub fn monitors(&self) -> Vec<Monitor> {
+        self.platform_app.monitors()
+    }
+
+    /// Returns the video modes of the display the application renders to.
+    ///
+    /// The mode in use is the one the display driver picked when the application started.
+    /// It can't be changed, and it is kept when a display is unplugged and plugged back in,
+    /// so the window size stays the same too.
+    #[cfg(dri_backend)]
+    pub fn display_modes(&self) -> Vec<crate::DisplayMode> {
+        self.platform_app.display_modes()
+    }
+
+    /// Returns the primary monitor, if it is known, see [`monitors`].
+    ///
+    /// [`monitors`]: Application::monitors
+    #[cfg(skia_backend)]
+    pub fn primary_monitor(&self) -> Option<Monitor> {
+        self.platform_app.primary_monitor()
+    }
+
+    /// Returns a sender for posting values to the UI thread from other threads.
+    ///
+    /// Unlike idle callbacks, the values are delivered to the handler of the focused
+    /// window (or any window, if none has had focus) through [`WinHandler::user_event`].
+    ///
+    /// [`WinHandler::user_event`]: crate::WinHandler::user_event
+    #[cfg(skia_backend)]
+    pub fn user_event_sender(&self) -> UserEventSender {
+        UserEventSender(self.platform_app.user_event_sender())
+    }
+
+    /// Returns the frame rate and frame timing of the application's windows.
+    ///
+    /// The frame rate is updated once a second.
+    #[cfg(skia_backend)]
+    pub fn frame_stats(&self) -> FrameStats {
+        self.platform_app.frame_stats()
+    }
+
     // TODO: do these two go in some kind of PlatformExt trait?
     /// Hide the application this window belongs to. (cmd+H)
     pub fn hide(&self) {
@@ -144,6 +144,42 @@ impl Clipboard {
         self.0.get_string()
     }
 
+    /// Read a string from the system clipboard, and call `callback` with it once it is
+    /// available.
+    ///
+    /// Browsers only give access to the clipboard asynchronously, so [`get_string`] can
+    /// only return the text that was last put on or read from the clipboard. `callback`
+    /// gets `None` if there is no text on the clipboard or the user denied access to it.
+    ///
+    /// [`get_string`]: Clipboard::get_string
+    #[cfg(target_arch = "wasm32")]
+    pub fn request_string(&self, callback: impl FnOnce(Option<String>) + 'static) {
+        self.0.request_string(callback)
+    }
+
+    /// Put a string into the X11 primary selection.
+    ///
+    /// This is the text that other applications paste on a middle click, it should be
+    /// set whenever the user selects text. Wayland compositors only support it if they
+    /// implement version 2 of the data control protocol.
+    ///
+    /// Pasting it on a middle click is left to the application, the middle button is
+    /// reported as a normal [`MouseButton::Middle`] press.
+    ///
+    /// [`MouseButton::Middle`]: crate::MouseButton::Middle
+    #[cfg(all(target_os = "linux", skia_backend))]
+    pub fn set_primary_selection(&mut self, s: impl AsRef<str>) {
+        self.0.set_primary_selection(s)
+    }
+
+    /// Get the text of the X11 primary selection, if there is any.
+    ///
+    /// See [`Clipboard::set_primary_selection`] for where this is supported.
+    #[cfg(all(target_os = "linux", skia_backend))]
+    pub fn get_primary_selection(&self) -> Option<String> {
+        self.0.get_primary_selection()
+    }
+
     /// Given a list of supported clipboard types, returns the supported type which has
     /// highest priority on the system clipboard, or `None` if no types are supported.
     pub fn preferred_format(&self, formats: &[FormatId]) -> Option<FormatId> {
@@ -131,6 +131,12 @@ impl ClickCounter {
         self.max_distance.set(distance)
     }
 
+    /// Forget any previous clicks, so that the next click has a count of one.
+    pub fn reset(&self) {
+        self.click_count.set(0);
+        self.last_pos.set(Point::new(f64::MAX, 0.0));
+    }
+
     /// Return the click count for a click occuring now, at the provided position.
     pub fn count_for_click(&self, click_pos: Point) -> u8 {
         let click_time = Instant::now();
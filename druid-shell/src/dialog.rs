@@ -207,7 +207,10 @@ impl FileDialogOptions {
 
     /// Set multiple items to be selectable.
     ///
-    /// This is only relevant for open dialogs.
+    /// This is only relevant for open dialogs. The chosen files are reported by
+    /// [`WinHandler::open_files`]. The skia backend can only select a single directory.
+    ///
+    /// [`WinHandler::open_files`]: crate::WinHandler::open_files
     pub fn multi_selection(mut self) -> Self {
         self.multi_selection = true;
         self
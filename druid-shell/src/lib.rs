@@ -43,6 +43,10 @@ pub use image;
 pub use kurbo;
 pub use piet_common as piet;
 
+// Reexport the version of `raw-window-handle` that `WindowHandle` implements.
+#[cfg(skia_backend)]
+pub use raw_window_handle;
+
 #[macro_use]
 mod util;
 
@@ -61,7 +65,9 @@ mod scale;
 mod screen;
 mod window;
 
-pub use application::{AppHandler, Application};
+#[cfg(skia_backend)]
+pub use application::UserEventSender;
+pub use application::{AppHandler, Application, FrameStats, Theme};
 pub use clipboard::{Clipboard, ClipboardFormat, FormatId};
 pub use common_util::Counter;
 pub use dialog::{FileDialogOptions, FileInfo, FileSpec};
@@ -74,8 +80,13 @@ pub use region::Region;
 pub use scale::{Scalable, Scale, ScaledArea};
 pub use screen::{Monitor, Screen};
 pub use window::{
-    FileDialogToken, IdleHandle, IdleToken, TimerToken, WinHandler, WindowBuilder, WindowHandle,
-    WindowLevel, WindowState,
+    FileDialogToken, IdleHandle, IdleToken, TimerToken, WinHandler, WindowBuilder, WindowGeometry,
+    WindowHandle, WindowLevel, WindowState,
 };
 
+#[cfg(dri_backend)]
+pub use platform::screen::DisplayMode;
+#[cfg(dri_backend)]
+pub use platform::window::Rotation;
+
 pub use keyboard_types;
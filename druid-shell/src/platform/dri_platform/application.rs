@@ -17,11 +17,17 @@ use std::convert::TryInto;
 use std::rc::Rc;
 use std::time::{Duration, Instant};
 
-use crate::application::AppHandler;
+use crate::application::{AppHandler, Theme};
+use crate::hotkey::HotKey;
+use crate::platform::shared;
 use crate::scale::Scale;
+use crate::window::IdleToken;
 
+use super::card::{Card, CursorPlane, Hotplug};
 use super::clipboard::Clipboard;
-use super::window::Window;
+use super::input::Input;
+use super::screen::{self, DisplayMode};
+use super::window::{rotate_canvas, Rotation, Window};
 
 use skia_safe::{
     gpu::{gl::FramebufferInfo, BackendRenderTarget, SurfaceOrigin},
@@ -42,22 +48,16 @@ pub(crate) struct Application {
 /// The mutable `Application` state.
 struct State {
     /// Whether `Application::quit` has already been called.
-    _quitting: bool,
+    quitting: bool,
     /// A collection of all the `Application` windows.
     window: Option<Rc<Window>>, // we only want to support one window for now
 }
 
 impl Application {
     pub fn new() -> Result<Application, Error> {
-        #[cfg(not(target_os = "macos"))]
-        {
-            // using functions from druid here to supress warnings without changing druid's code (and hence being upstream)
-            use super::super::shared::hardware_keycode_to_code;
-            hardware_keycode_to_code(0);
-        }
         //use super::super::strip_access_key;
         let state = Rc::new(RefCell::new(State {
-            _quitting: false,
+            quitting: false,
             window: None,
         }));
         Ok(Application { state })
@@ -88,20 +88,19 @@ impl Application {
         let mut gr_context =
             skia_safe::gpu::Context::new_gl(None, None).expect("failed to create skia gl context");
 
-        let fb_info = {
-            let mut fboid: GLint = 0;
-            unsafe { glGetIntegerv(GL_FRAMEBUFFER_BINDING, &mut fboid) };
-
-            FramebufferInfo {
-                fboid: fboid.try_into().unwrap(),
-                format: skia_safe::gpu::gl::Format::RGBA8.into(),
-            }
-        };
+        /// Create a surface for the framebuffer that is bound now.
+        ///
+        /// The binding can change when the display is set up again, so it isn't cached.
+        fn create_surface(gr_context: &mut skia_safe::gpu::Context) -> skia_safe::Surface {
+            let fb_info = {
+                let mut fboid: GLint = 0;
+                unsafe { glGetIntegerv(GL_FRAMEBUFFER_BINDING, &mut fboid) };
 
-        fn create_surface(
-            fb_info: FramebufferInfo,
-            gr_context: &mut skia_safe::gpu::Context,
-        ) -> skia_safe::Surface {
+                FramebufferInfo {
+                    fboid: fboid.try_into().unwrap(),
+                    format: skia_safe::gpu::gl::Format::RGBA8.into(),
+                }
+            };
             let backend_render_target = BackendRenderTarget::new_gl(
                 (unsafe { drm_screen_width() }, unsafe {
                     drm_screen_height()
@@ -123,9 +122,9 @@ impl Application {
         let mode_size = (unsafe { drm_screen_width() }, unsafe {
                     drm_screen_height()
                 });
-        let mut surface = create_surface(fb_info, &mut gr_context);
+        let mut surface = create_surface(&mut gr_context);
         let _scale = if let Ok(window) = self.window() {
-            window.state().unwrap().scale
+            window.core.state().unwrap().scale
         } else {
             Scale::default()
         };
@@ -133,19 +132,49 @@ impl Application {
         let main_window = self.window().unwrap();
         let size = main_window.size().unwrap();
         let canvas = surface.canvas();
-        if mode_size.1 > mode_size.0 {
-            canvas.rotate(90., Some(skia_safe::Point::default()));//Some(skia_safe::Point::new(size.width as f32 / 2., 0.)));
-            canvas.translate((0., -size.height as f32));
+        let rotation = main_window
+            .rotation()
+            .unwrap_or(if mode_size.1 > mode_size.0 {
+                Rotation::Rotate90
+            } else {
+                Rotation::Rotate0
+            });
+        main_window.set_rotation(rotation);
+        // Without a cursor plane the window draws the cursor into each frame.
+        match Card::open().and_then(CursorPlane::new) {
+            Ok(plane) => main_window.set_cursor_plane(plane),
+            Err(err) => log::info!("no hardware cursor, drawing the cursor instead: {}", err),
         }
-        canvas.clear(Color::BLACK);
+        rotate_canvas(canvas, rotation, size);
+        let background = main_window.background().map(|color| {
+            let (r, g, b, a) = color.as_rgba8();
+            Color::from_argb(a, r, g, b)
+        });
+        canvas.clear(background.unwrap_or(Color::BLACK));
         canvas.flush();
         unsafe {
             swap_buffers();
         }
+        let mut input = Input::new((mode_size.0 as u32, mode_size.1 as u32), rotation)?;
+        // Without vblank events we can only present as fast as we render.
+        let mut card = match Card::open() {
+            Ok(card) => Some(card),
+            Err(err) => {
+                log::warn!("failed to open the DRM device, vsync is disabled: {}", err);
+                None
+            }
+        };
+        let mut hotplug = match Card::open().and_then(Hotplug::new) {
+            Ok(hotplug) => Some(hotplug),
+            Err(err) => {
+                log::warn!("failed to watch the DRM connectors: {}", err);
+                None
+            }
+        };
         let mut last_ts = Instant::now();
         let mut time = Duration::default();
         let mut frames_cnt = 0;
-        loop {
+        while !self.quitting() {
             {
                 // frame rate
                 frames_cnt += 1;
@@ -153,31 +182,81 @@ impl Application {
                 time += duration;
                 last_ts = Instant::now();
                 if time > Duration::from_secs(1) {
-                    log::info!("{}", frames_cnt);
+                    log::info!("{} fps", frames_cnt);
                     frames_cnt = 0;
                     time = time.max(Duration::from_secs(1)) - Duration::from_secs(1);
                 }
             }
+            input.dispatch(&main_window);
             let now = Instant::now();
-            main_window.run_timers(now);
+            match hotplug.as_mut().map(|hotplug| hotplug.poll(now)) {
+                Some(Ok(true)) => main_window.display_changed(),
+                Some(Err(err)) => {
+                    log::warn!("failed to read the DRM connectors: {}", err);
+                    hotplug = None;
+                }
+                _ => (),
+            }
+            main_window.core.run_timers(now);
             main_window.run_idle();
 
             let surface_canvas = surface.canvas();
             let main_window = self.window().unwrap();
             main_window.render(&mut *surface_canvas).unwrap();
             surface_canvas.flush();
+            // Present at most once per refresh, right after the display scanned out the last frame.
+            if let Some(Err(err)) = card.as_ref().map(Card::wait_vblank) {
+                log::warn!("failed to wait for vblank, vsync is disabled: {}", err);
+                card = None;
+            }
             unsafe {
                 swap_buffers();
             }
         }
+        main_window.destroy();
+        Ok(())
+    }
+
+    fn quitting(&self) -> bool {
+        borrow!(self.state)
+            .map(|state| state.quitting)
+            .unwrap_or(false)
     }
 
-    pub fn quit(&self) {}
+    /// Stop the render loop after the current frame.
+    pub fn quit(&self) {
+        match borrow_mut!(self.state) {
+            Ok(mut state) => state.quitting = true,
+            Err(e) => log::error!("Application::quit - {}", e),
+        }
+    }
 
     pub fn clipboard(&self) -> Clipboard {
         Clipboard
     }
 
+    /// There are no system settings to read, so the theme is configured through the
+    /// `DRUID_SHELL_THEME` environment variable.
+    pub fn system_theme(&self) -> Theme {
+        match std::env::var("DRUID_SHELL_THEME") {
+            Ok(theme) if theme.eq_ignore_ascii_case("dark") => Theme::Dark,
+            _ => Theme::Light,
+        }
+    }
+
+    /// There is no window system to grab keys from, so global hotkeys never fire.
+    pub fn register_global_hotkey(&self, _hotkey: HotKey, _token: IdleToken) -> Result<(), Error> {
+        Ok(())
+    }
+
+    pub fn unregister_global_hotkey(&self, _token: IdleToken) -> Result<(), Error> {
+        Ok(())
+    }
+
+    pub fn display_modes(&self) -> Vec<DisplayMode> {
+        screen::display_modes()
+    }
+
     #[cfg(target_os = "macos")]
     pub fn hide(&self) {}
 
@@ -185,7 +264,6 @@ impl Application {
     pub fn hide_others(&self) {}
 
     pub fn get_locale() -> String {
-        //TODO ahem
-        "en-US".into()
+        shared::system_locale()
     }
 }
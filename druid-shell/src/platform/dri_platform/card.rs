@@ -0,0 +1,188 @@
+// Copyright 2020 The Druid Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Direct access to the DRM device, for the display control `dri::kms` doesn't expose.
+
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::os::unix::io::{AsFd, BorrowedFd};
+use std::time::{Duration, Instant};
+
+use drm::buffer::{Buffer, DrmFourcc};
+use drm::control::dumbbuffer::DumbBuffer;
+use drm::control::{connector, crtc, Device as ControlDevice};
+use drm::{Device, DriverCapability, VblankWaitFlags, VblankWaitTarget};
+
+/// The primary DRM device node that `dri::kms` renders to.
+const CARD_PATH: &str = "/dev/dri/card0";
+
+/// The cursor size to use when the driver doesn't report one.
+const DEFAULT_CURSOR_SIZE: u64 = 64;
+
+/// How often `Hotplug` looks at the connectors.
+const HOTPLUG_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A second handle on the DRM device.
+///
+/// `dri::kms` holds the DRM master lock, so this can only be used for requests
+/// that don't need it. Setting the gamma tables needs master, which is why the backend
+/// has no gamma or brightness control.
+pub(crate) struct Card(File);
+
+impl AsFd for Card {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.0.as_fd()
+    }
+}
+
+impl drm::Device for Card {}
+impl drm::control::Device for Card {}
+
+impl Card {
+    pub(crate) fn open() -> io::Result<Card> {
+        let file = OpenOptions::new().read(true).write(true).open(CARD_PATH)?;
+        Ok(Card(file))
+    }
+
+    /// Block until the next vertical blank of the first CRTC.
+    pub(crate) fn wait_vblank(&self) -> io::Result<()> {
+        drm::Device::wait_vblank(
+            self,
+            VblankWaitTarget::Relative(1),
+            VblankWaitFlags::empty(),
+            0,
+            0,
+        )?;
+        Ok(())
+    }
+}
+
+/// A cursor shown by the display hardware on top of the framebuffer.
+///
+/// Moving it doesn't touch the framebuffer, so there is nothing to redraw.
+pub(crate) struct CursorPlane {
+    card: Card,
+    /// The CRTC that scans out the display.
+    crtc: crtc::Handle,
+    buffer: DumbBuffer,
+}
+
+// The legacy cursor ioctls are deprecated in favour of cursor planes, but unlike planes they
+// work without enabling the universal planes client capability.
+#[allow(deprecated)]
+impl CursorPlane {
+    /// Set up the cursor of the CRTC that is scanning out.
+    ///
+    /// This fails if the kernel doesn't let `card` change the cursor, e.g. because
+    /// another handle on the device is the DRM master.
+    pub(crate) fn new(card: Card) -> io::Result<CursorPlane> {
+        let capability = |cap| {
+            card.get_driver_capability(cap)
+                .unwrap_or(DEFAULT_CURSOR_SIZE)
+        };
+        let width = capability(DriverCapability::CursorWidth) as u32;
+        let height = capability(DriverCapability::CursorHeight) as u32;
+        let mut active = None;
+        for &handle in card.resource_handles()?.crtcs() {
+            if card.get_crtc(handle)?.mode().is_some() {
+                active = Some(handle);
+                break;
+            }
+        }
+        let crtc = active
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no CRTC is scanning out"))?;
+        let buffer = card.create_dumb_buffer((width, height), DrmFourcc::Argb8888, 32)?;
+        // Hiding the cursor checks that we are allowed to touch it at all.
+        card.set_cursor(crtc, None::<&DumbBuffer>)?;
+        Ok(CursorPlane { card, crtc, buffer })
+    }
+
+    /// The size of the cursor image, in pixels.
+    pub(crate) fn size(&self) -> (u32, u32) {
+        self.buffer.size()
+    }
+
+    /// Show `pixels` as the cursor.
+    ///
+    /// `pixels` are premultiplied BGRA rows of `size().0` pixels.
+    pub(crate) fn set_image(&mut self, pixels: &[u8]) -> io::Result<()> {
+        let pitch = self.buffer.pitch() as usize;
+        let row_bytes = self.buffer.size().0 as usize * 4;
+        {
+            let mut mapping = self.card.map_dumb_buffer(&mut self.buffer)?;
+            for (dst, src) in mapping.chunks_mut(pitch).zip(pixels.chunks(row_bytes)) {
+                dst[..row_bytes].copy_from_slice(src);
+            }
+        }
+        self.card.set_cursor(self.crtc, Some(&self.buffer))
+    }
+
+    pub(crate) fn hide(&self) -> io::Result<()> {
+        self.card.set_cursor(self.crtc, None::<&DumbBuffer>)
+    }
+
+    /// Move the top left corner of the cursor image to `pos`, in display pixels.
+    pub(crate) fn move_to(&self, pos: (i32, i32)) -> io::Result<()> {
+        self.card.move_cursor(self.crtc, pos)
+    }
+}
+
+/// Notices displays being plugged in or out.
+///
+/// The kernel updates the connector states on hotplug interrupts, so reading them
+/// from time to time is enough and doesn't need DRM master.
+pub(crate) struct Hotplug {
+    card: Card,
+    /// The connectors that had a display plugged in at the last check.
+    connected: Vec<connector::Handle>,
+    last_check: Instant,
+}
+
+impl Hotplug {
+    pub(crate) fn new(card: Card) -> io::Result<Hotplug> {
+        let connected = connected(&card)?;
+        Ok(Hotplug {
+            card,
+            connected,
+            last_check: Instant::now(),
+        })
+    }
+
+    /// Returns whether displays were plugged in or out since the last check.
+    ///
+    /// The connectors are read at most once per `HOTPLUG_INTERVAL`.
+    pub(crate) fn poll(&mut self, now: Instant) -> io::Result<bool> {
+        if now < self.last_check + HOTPLUG_INTERVAL {
+            return Ok(false);
+        }
+        self.last_check = now;
+        let connected = connected(&self.card)?;
+        if connected == self.connected {
+            return Ok(false);
+        }
+        self.connected = connected;
+        Ok(true)
+    }
+}
+
+/// The connectors that have a display plugged in.
+fn connected(card: &Card) -> io::Result<Vec<connector::Handle>> {
+    let mut connected = Vec::new();
+    for &handle in card.resource_handles()?.connectors() {
+        if card.get_connector(handle, false)?.state() == connector::State::Connected {
+            connected.push(handle);
+        }
+    }
+    Ok(connected)
+}
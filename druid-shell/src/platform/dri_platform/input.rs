@@ -0,0 +1,216 @@
+// Copyright 2020 The Druid Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Keyboard, mouse and touch input, read from libinput.
+
+use std::fs::{File, OpenOptions};
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::OwnedFd;
+use std::path::Path;
+
+use anyhow::{anyhow, Error};
+use input::event::keyboard::{KeyState as LibinputKeyState, KeyboardEvent, KeyboardEventTrait};
+use input::event::pointer::{Axis, ButtonState, PointerEvent};
+use input::event::touch::{TouchEvent, TouchEventPosition, TouchEventSlot};
+use input::{Event, Libinput, LibinputInterface};
+
+use crate::keyboard::KeyState;
+use crate::kurbo::{Point, Size, Vec2};
+use crate::mouse::MouseButton;
+
+use super::keycodes;
+use super::window::{untransform_point, Rotation, Window};
+
+// Button codes from linux/input-event-codes.h.
+const BTN_LEFT: u32 = 0x110;
+const BTN_RIGHT: u32 = 0x111;
+const BTN_MIDDLE: u32 = 0x112;
+const BTN_SIDE: u32 = 0x113;
+const BTN_EXTRA: u32 = 0x114;
+
+/// The wheel delta reported for one notch of a scroll wheel.
+///
+/// We use a delta of 120 per notch to match the behavior of Windows.
+const SCROLL_LINE_DELTA: f64 = 120.0;
+
+/// Opens the input devices for libinput.
+///
+/// Devices are opened directly, so the process needs read access to `/dev/input`.
+struct Interface;
+
+impl LibinputInterface for Interface {
+    fn open_restricted(&mut self, path: &Path, flags: i32) -> Result<OwnedFd, i32> {
+        let access = flags & libc::O_ACCMODE;
+        OpenOptions::new()
+            .custom_flags(flags)
+            .read(access == libc::O_RDONLY || access == libc::O_RDWR)
+            .write(access == libc::O_WRONLY || access == libc::O_RDWR)
+            .open(path)
+            .map(|file| file.into())
+            .map_err(|err| -err.raw_os_error().unwrap_or(libc::EIO))
+    }
+
+    fn close_restricted(&mut self, fd: OwnedFd) {
+        drop(File::from(fd));
+    }
+}
+
+/// The input devices of the seat, and the cursor they move.
+pub(crate) struct Input {
+    libinput: Libinput,
+    /// The cursor position, in display points.
+    cursor: Point,
+    /// The size of the display mode, in pixels.
+    screen_size: (u32, u32),
+    /// How the window is rotated to fit the display.
+    rotation: Rotation,
+    /// The seat slot of the touch that acts as the mouse.
+    ///
+    /// Only the first contact is tracked, other touches are ignored while it is down.
+    touch_slot: Option<u32>,
+}
+
+impl Input {
+    pub(crate) fn new(screen_size: (u32, u32), rotation: Rotation) -> Result<Input, Error> {
+        let mut libinput = Libinput::new_with_udev(Interface);
+        libinput
+            .udev_assign_seat("seat0")
+            .map_err(|()| anyhow!("failed to assign seat0 to libinput"))?;
+        Ok(Input {
+            libinput,
+            cursor: Point::ZERO,
+            screen_size,
+            rotation,
+            touch_slot: None,
+        })
+    }
+
+    /// Read the pending input events and dispatch them to `window`.
+    ///
+    /// This doesn't block if there are no events.
+    pub(crate) fn dispatch(&mut self, window: &Window) {
+        if let Err(e) = self.libinput.dispatch() {
+            log::error!("Failed to read input events: {}", e);
+            return;
+        }
+        let size = match window.size() {
+            Ok(size) => size,
+            Err(e) => {
+                log::error!("Input::dispatch - failed to get window size: {}", e);
+                return;
+            }
+        };
+        for event in &mut self.libinput {
+            match event {
+                Event::Keyboard(KeyboardEvent::Key(key)) => {
+                    let state = match key.key_state() {
+                        LibinputKeyState::Pressed => KeyState::Down,
+                        LibinputKeyState::Released => KeyState::Up,
+                    };
+                    window.handle_key_press(keycodes::evdev_to_code(key.key()), state);
+                }
+                Event::Pointer(PointerEvent::Motion(motion)) => {
+                    // Relative motion moves the cursor, which can't leave the screen.
+                    let delta = Point::new(motion.dx(), motion.dy());
+                    let delta = untransform_point(delta, self.rotation, size)
+                        - untransform_point(Point::ZERO, self.rotation, size);
+                    let pos = self.cursor + delta;
+                    self.cursor = Point::new(
+                        pos.x.max(0.0).min(size.width),
+                        pos.y.max(0.0).min(size.height),
+                    );
+                    window.handle_motion_notify(self.cursor);
+                }
+                Event::Pointer(PointerEvent::MotionAbsolute(motion)) => {
+                    let (width, height) = self.screen_size;
+                    let pos = Point::new(
+                        motion.absolute_x_transformed(width),
+                        motion.absolute_y_transformed(height),
+                    );
+                    self.cursor = untransform_point(pos, self.rotation, size);
+                    window.handle_motion_notify(self.cursor);
+                }
+                Event::Pointer(PointerEvent::Button(event)) => {
+                    if let Some(button) = convert_button(event.button()) {
+                        match event.button_state() {
+                            ButtonState::Pressed => window.handle_button_press(button),
+                            ButtonState::Released => window.handle_button_release(button),
+                        }
+                    }
+                }
+                Event::Pointer(PointerEvent::Axis(axis)) => {
+                    let value = |direction| {
+                        if !axis.has_axis(direction) {
+                            return 0.0;
+                        }
+                        match axis.axis_value_discrete(direction) {
+                            Some(notches) => notches * SCROLL_LINE_DELTA,
+                            None => axis.axis_value(direction),
+                        }
+                    };
+                    let delta = Vec2::new(value(Axis::Horizontal), value(Axis::Vertical));
+                    window.handle_mouse_wheel(delta);
+                }
+                Event::Touch(TouchEvent::Down(touch)) => {
+                    if self.touch_slot.is_none() {
+                        self.touch_slot = Some(touch.seat_slot());
+                        self.cursor = self.touch_position(&touch, size);
+                        window.handle_motion_notify(self.cursor);
+                        window.handle_button_press(MouseButton::Left);
+                    }
+                }
+                Event::Touch(TouchEvent::Motion(touch)) => {
+                    if self.touch_slot == Some(touch.seat_slot()) {
+                        self.cursor = self.touch_position(&touch, size);
+                        window.handle_motion_notify(self.cursor);
+                    }
+                }
+                Event::Touch(TouchEvent::Up(touch)) => {
+                    if self.touch_slot == Some(touch.seat_slot()) {
+                        self.touch_slot = None;
+                        window.handle_button_release(MouseButton::Left);
+                    }
+                }
+                Event::Touch(TouchEvent::Cancel(touch)) => {
+                    if self.touch_slot == Some(touch.seat_slot()) {
+                        self.touch_slot = None;
+                        window.handle_button_release(MouseButton::Left);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// The position of a touch in window coordinates.
+    ///
+    /// Touch screens report positions on the display, so the rotation of the drawing
+    /// has to be undone for taps to land where they look.
+    fn touch_position(&self, touch: &impl TouchEventPosition, window_size: Size) -> Point {
+        let (width, height) = self.screen_size;
+        let pos = Point::new(touch.x_transformed(width), touch.y_transformed(height));
+        untransform_point(pos, self.rotation, window_size)
+    }
+}
+
+fn convert_button(button: u32) -> Option<MouseButton> {
+    match button {
+        BTN_LEFT => Some(MouseButton::Left),
+        BTN_RIGHT => Some(MouseButton::Right),
+        BTN_MIDDLE => Some(MouseButton::Middle),
+        BTN_SIDE => Some(MouseButton::X1),
+        BTN_EXTRA => Some(MouseButton::X2),
+        _ => None,
+    }
+}
@@ -13,10 +13,11 @@
 // limitations under the License.
 
 pub mod application;
+mod card;
 pub mod clipboard;
 pub mod error;
+mod input;
 pub mod keycodes;
 pub mod menu;
 pub mod screen;
-pub mod util;
 pub mod window;
@@ -12,11 +12,110 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-//! Monitor and Screen information ignored for now. TODO
+//! Monitor information, from the connectors of the DRM device.
 
+use std::io;
+
+use drm::control::{connector, crtc, Device as ControlDevice, Mode, ModeTypeFlags};
+
+use super::card::Card;
+use crate::kurbo::{Point, Rect, Size};
+use crate::scale::Scale;
 use crate::screen::Monitor;
 
+/// A video mode of a display.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DisplayMode {
+    /// The width of the mode, in pixels.
+    pub width: u32,
+    /// The height of the mode, in pixels.
+    pub height: u32,
+    /// The vertical refresh rate, in Hz.
+    pub refresh_rate: u32,
+    /// Whether the display reports this as its preferred mode.
+    pub preferred: bool,
+}
+
 pub(crate) fn get_monitors() -> Vec<Monitor> {
-    log::warn!("Screen::get_monitors() is not implemented for web.");
-    Vec::new()
+    match Card::open().and_then(|card| connected_monitors(&card)) {
+        Ok(monitors) => monitors,
+        Err(err) => {
+            log::warn!("failed to list the DRM connectors: {}", err);
+            Vec::new()
+        }
+    }
+}
+
+/// A monitor for each connected display that is scanning out.
+///
+/// Displays are placed by the position of their CRTC, the one at the origin is the primary.
+fn connected_monitors(card: &Card) -> io::Result<Vec<Monitor>> {
+    let mut monitors = Vec::new();
+    for &handle in card.resource_handles()?.connectors() {
+        let connector = card.get_connector(handle, false)?;
+        let (crtc, mode) = match scanout(card, &connector)? {
+            Some(crtc) => crtc,
+            None => continue,
+        };
+        let (x, y) = crtc.position();
+        let (width, height) = mode.size();
+        let rect = Rect::from_origin_size(
+            Point::new(x as f64, y as f64),
+            Size::new(width as f64, height as f64),
+        );
+        let interface = connector.interface();
+        let name = format!("{}-{}", interface.as_str(), connector.interface_id());
+        let refresh_rate = Some(mode.vrefresh() as f64).filter(|&rate| rate > 0.);
+        // There are no panels or docks on the bare display.
+        let monitor = Monitor::new(x == 0 && y == 0, rect, rect);
+        monitors.push(monitor.with_details(Some(name), Scale::default(), refresh_rate));
+    }
+    Ok(monitors)
+}
+
+/// The modes of the display that is scanning out, the first one if there are several.
+pub(crate) fn display_modes() -> Vec<DisplayMode> {
+    match Card::open().and_then(|card| scanout_modes(&card)) {
+        Ok(modes) => modes,
+        Err(err) => {
+            log::warn!("failed to list the DRM modes: {}", err);
+            Vec::new()
+        }
+    }
+}
+
+fn scanout_modes(card: &Card) -> io::Result<Vec<DisplayMode>> {
+    for &handle in card.resource_handles()?.connectors() {
+        let connector = card.get_connector(handle, false)?;
+        if scanout(card, &connector)?.is_none() {
+            continue;
+        }
+        let modes = connector.modes().iter().map(|mode| {
+            let (width, height) = mode.size();
+            DisplayMode {
+                width: width.into(),
+                height: height.into(),
+                refresh_rate: mode.vrefresh(),
+                preferred: mode.mode_type().contains(ModeTypeFlags::PREFERRED),
+            }
+        });
+        return Ok(modes.collect());
+    }
+    Ok(Vec::new())
+}
+
+/// The CRTC that scans out to `connector` and its current mode, if it is connected and on.
+fn scanout(card: &Card, connector: &connector::Info) -> io::Result<Option<(crtc::Info, Mode)>> {
+    if connector.state() != connector::State::Connected {
+        return Ok(None);
+    }
+    let encoder = match connector.current_encoder() {
+        Some(encoder) => card.get_encoder(encoder)?,
+        None => return Ok(None),
+    };
+    let crtc = match encoder.crtc() {
+        Some(crtc) => card.get_crtc(crtc)?,
+        None => return Ok(None),
+    };
+    Ok(crtc.mode().map(|mode| (crtc, mode)))
 }
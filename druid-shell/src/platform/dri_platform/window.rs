@@ -14,71 +14,238 @@
 
 //! Window creation and management.
 
-use std::any::Any;
-use std::cell::{Cell, RefCell};
-use std::collections::BinaryHeap;
+use std::cell::RefCell;
+use std::collections::HashSet;
 use std::ffi::OsString;
-use std::panic::Location;
 use std::rc::{Rc, Weak};
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
+use std::time::Duration;
 
 use instant::Instant;
 
 use crate::{
-    kurbo::{Point, Rect, Size},
-    piet::{Piet, PietText},
+    kurbo::{BezPath, Line, Point, Rect, Size, Vec2},
+    piet::{Color, InterpolationMode, Piet, PietImage, PietText, RenderContext},
+    Code,
 };
 
 use anyhow::Error as AnyError;
-use skia_safe::IRect;
+use skia_safe::{AlphaType, ColorType, IRect, ImageInfo};
 
 use super::application::Application;
+use super::card::CursorPlane;
 use super::error::Error;
+use super::keycodes;
 use super::menu::Menu;
-use super::util::Timer;
-use crate::common_util::IdleCallback;
+use crate::common_util::ClickCounter;
 use crate::dialog::{FileDialogOptions, FileDialogType};
 use crate::error::Error as ShellError;
-use crate::scale::{Scalable, Scale, ScaledArea};
+use crate::keyboard::{KeyEvent, KeyState, Modifiers};
+use crate::platform::shared::{DamageHistory, WindowCore, DEFAULT_BUFFER_COUNT};
+use crate::scale::{Scalable, Scale};
 
-use crate::mouse::{Cursor, CursorDesc, MouseButton, MouseButtons};
+use crate::mouse::{Cursor, CursorDesc, MouseButton, MouseButtons, MouseEvent};
 use crate::region::Region;
 use crate::window;
-use crate::window::{FileDialogToken, IdleToken, TimerToken, WinHandler, WindowLevel};
+use crate::window::{FileDialogToken, TimerToken, WinHandler, WindowLevel};
+
+pub use crate::platform::shared::IdleHandle;
 
 pub struct Window {
-    handler: RefCell<Box<dyn WinHandler>>,
-    window_state: RefCell<WindowState>,
-    idle_queue: Arc<Mutex<Vec<IdleKind>>>,
-    timer_queue: Mutex<BinaryHeap<Timer>>,
+    pub(crate) core: WindowCore<WindowState>,
+    /// The hardware cursor, the cursor is drawn into the frames without it.
+    cursor_plane: RefCell<Option<CursorPlane>>,
+    /// The image of the custom cursor, made the first time it is drawn.
+    cursor_image: RefCell<Option<PietImage>>,
+}
+
+/// How the window is rotated to fit the display, clockwise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rotation {
+    Rotate0,
+    Rotate90,
+    Rotate180,
+    Rotate270,
+}
+
+/// Map a point in a window of size `w` x `h` to the display.
+fn transform_point(x: i32, y: i32, rotation: Rotation, (w, h): (i32, i32)) -> (i32, i32) {
+    match rotation {
+        Rotation::Rotate0 => (x, y),
+        Rotation::Rotate90 => (h - y, x),
+        Rotation::Rotate180 => (w - x, h - y),
+        Rotation::Rotate270 => (y, w - x),
+    }
 }
 
 // we need it to manually transform clipping rectangles. More info on why we need to do this here:
 // https://docs.microsoft.com/en-us/xamarin/xamarin-forms/user-interface/graphics/skiasharp/curves/clipping
-/// Rotate 90 degrees and shift to perfectly fit the display
-fn transform_clip_rect(rect: IRect, h: i32) -> IRect{
-    let (x1, y1) = (rect.left, rect.bottom);
-    let (x2, y2) = (rect.right, rect.top);
-
-    let (left, bottom) = (h - y1, x2);
-    let (right, top) = (h - y2, x1);
-    IRect::new(left, top, right, bottom)
+/// Rotate and shift the rect to perfectly fit the display
+fn transform_clip_rect(rect: IRect, rotation: Rotation, size: (i32, i32)) -> IRect {
+    let (x1, y1) = transform_point(rect.left, rect.top, rotation, size);
+    let (x2, y2) = transform_point(rect.right, rect.bottom, rotation, size);
+    IRect::new(x1.min(x2), y1.min(y2), x1.max(x2), y1.max(y2))
+}
+
+/// Apply the same transform as `transform_clip_rect` to the canvas.
+pub(crate) fn rotate_canvas(canvas: &mut skia_safe::Canvas, rotation: Rotation, size: Size) {
+    let (width, height) = (size.width as f32, size.height as f32);
+    let (degrees, offset) = match rotation {
+        Rotation::Rotate0 => return,
+        Rotation::Rotate90 => (90., (0., -height)),
+        Rotation::Rotate180 => (180., (-width, -height)),
+        Rotation::Rotate270 => (270., (-width, 0.)),
+    };
+    canvas.rotate(degrees, Some(skia_safe::Point::default()));
+    canvas.translate(offset);
+}
+
+/// The highest click count reported for repeated clicks.
+const MAX_CLICK_COUNT: u8 = 3;
+
+/// The outline of the arrow drawn for the system cursors, with the tip at the origin.
+const ARROW: [(f64, f64); 7] = [
+    (0., 0.),
+    (0., 16.),
+    (4., 12.),
+    (7., 19.),
+    (10., 18.),
+    (7., 11.),
+    (12., 11.),
+];
+
+/// Half the height of the I-beam cursor, its hot spot is in the middle.
+const IBEAM_HALF_HEIGHT: f64 = 8.;
+
+/// The area covered by `cursor` with its hot spot at `pos`, in display points.
+///
+/// The outlines of the system cursors are stroked, the area leaves room for that.
+fn cursor_rect(cursor: &Cursor, pos: Point) -> Rect {
+    match cursor {
+        Cursor::Custom(custom) => custom.rect(pos),
+        Cursor::IBeam => {
+            let beam = Rect::new(-3., -IBEAM_HALF_HEIGHT, 3., IBEAM_HALF_HEIGHT);
+            beam.inflate(2., 2.) + pos.to_vec2()
+        }
+        _ => Rect::new(0., 0., 12., 19.).inflate(1., 1.) + pos.to_vec2(),
+    }
+}
+
+/// Draw `cursor` with its hot spot at `pos` on top of whatever is in `piet`.
+///
+/// `image` caches the image of a custom cursor, it must be cleared when the cursor changes.
+fn draw_cursor(piet: &mut Piet, cursor: &Cursor, pos: Point, image: &mut Option<PietImage>) {
+    match cursor {
+        Cursor::Custom(custom) => {
+            let image = image.get_or_insert_with(|| custom.0.image.to_image(piet));
+            piet.draw_image(image, custom.rect(pos), InterpolationMode::Bilinear);
+        }
+        Cursor::IBeam => {
+            let (top, bottom) = (pos.y - IBEAM_HALF_HEIGHT, pos.y + IBEAM_HALF_HEIGHT);
+            let lines = [
+                Line::new((pos.x - 3., top), (pos.x + 3., top)),
+                Line::new((pos.x, top), (pos.x, bottom)),
+                Line::new((pos.x - 3., bottom), (pos.x + 3., bottom)),
+            ];
+            // A light outline keeps the beam visible on dark backgrounds.
+            for line in lines.iter() {
+                piet.stroke(*line, &Color::WHITE, 3.);
+            }
+            for line in lines.iter() {
+                piet.stroke(*line, &Color::BLACK, 1.);
+            }
+        }
+        _ => {
+            let mut path = BezPath::new();
+            path.move_to(pos + Vec2::from(ARROW[0]));
+            for point in ARROW[1..].iter() {
+                path.line_to(pos + Vec2::from(*point));
+            }
+            path.close_path();
+            piet.fill(&path, &Color::WHITE);
+            piet.stroke(&path, &Color::BLACK, 1.);
+        }
+    }
+}
+
+/// Draw `cursor` at `scale` into an image for a cursor plane of `plane_size` pixels.
+///
+/// The image is rotated like the window and the top left corner of the cursor area is at
+/// its origin. Returns the premultiplied BGRA pixels, or `None` if the cursor doesn't fit.
+fn rasterize_cursor(
+    cursor: &Cursor,
+    rotation: Rotation,
+    scale: Scale,
+    plane_size: (u32, u32),
+) -> Option<Vec<u8>> {
+    let rect = cursor_rect(cursor, Point::ZERO);
+    let size_px = rect.size().to_px(scale);
+    let (width, height) = match rotation {
+        Rotation::Rotate0 | Rotation::Rotate180 => (size_px.width, size_px.height),
+        Rotation::Rotate90 | Rotation::Rotate270 => (size_px.height, size_px.width),
+    };
+    if width.ceil() > plane_size.0 as f64 || height.ceil() > plane_size.1 as f64 {
+        return None;
+    }
+    let (width, height) = (plane_size.0 as i32, plane_size.1 as i32);
+    let mut surface = skia_safe::Surface::new_raster_n32_premul((width, height))?;
+    let canvas = surface.canvas();
+    canvas.clear(skia_safe::Color::TRANSPARENT);
+    rotate_canvas(canvas, rotation, size_px);
+    canvas.scale((scale.x() as f32, scale.y() as f32));
+    let hot = Point::ZERO - rect.origin().to_vec2();
+    draw_cursor(&mut Piet::new(canvas), cursor, hot, &mut None);
+    let info = ImageInfo::new(
+        (width, height),
+        ColorType::BGRA8888,
+        AlphaType::Premul,
+        None,
+    );
+    let row_bytes = width as usize * 4;
+    let mut pixels = vec![0; row_bytes * height as usize];
+    if !surface.read_pixels(&info, &mut pixels, row_bytes, (0, 0)) {
+        return None;
+    }
+    Some(pixels)
+}
+
+/// Track the state of the modifier keys, libinput only reports raw key presses.
+fn update_modifiers(mods: &mut Modifiers, code: Code, state: KeyState) {
+    let modifier = match code {
+        Code::ShiftLeft | Code::ShiftRight => Modifiers::SHIFT,
+        Code::ControlLeft | Code::ControlRight => Modifiers::CONTROL,
+        Code::AltLeft | Code::AltRight => Modifiers::ALT,
+        Code::MetaLeft | Code::MetaRight => Modifiers::META,
+        _ => return,
+    };
+    mods.set(modifier, state == KeyState::Down);
+}
+
+/// Map a point on the display back to a window of size `size`.
+///
+/// This is the inverse of the rotation done by `transform_clip_rect`.
+pub(crate) fn untransform_point(point: Point, rotation: Rotation, size: Size) -> Point {
+    let (w, h) = (size.width, size.height);
+    match rotation {
+        Rotation::Rotate0 => point,
+        Rotation::Rotate90 => Point::new(point.y, h - point.x),
+        Rotation::Rotate180 => Point::new(w - point.x, h - point.y),
+        Rotation::Rotate270 => Point::new(w - point.y, point.x),
+    }
 }
 
 impl Window {
     pub fn render(&self, canvas: &mut skia_safe::Canvas) -> Result<(), AnyError> {
         let size = self.size().unwrap();
+        let rotation = self.rotation().unwrap_or(Rotation::Rotate0);
         // important for AnimStart and invalidation of required regions
-        self.with_handler(|h| h.prepare_paint());
-        let invalid =
-            std::mem::replace(&mut borrow_mut!(self.window_state)?.invalid, Region::EMPTY);
-        let prev_invalid = borrow_mut!(self.window_state)?.prev_invalid.clone();
-        let mut buffer_damage = invalid.clone();
-        buffer_damage.union_with(&prev_invalid);
+        self.core.with_handler(|h| h.prepare_paint());
+        let invalid = std::mem::replace(&mut self.core.state_mut()?.invalid, Region::EMPTY);
+        let buffer_damage = self.core.state_mut()?.damage.buffer_damage(invalid);
         canvas.save();
         let mut region = skia_safe::region::Region::new();
         for rect in buffer_damage.rects() {
-            let scale = self.state()?.scale;
+            let scale = self.core.state()?.scale;
             let rect = rect.to_px(scale);
                 let clip_rect = skia_safe::IRect {
                 left: rect.x0 as i32,
@@ -86,114 +253,249 @@ impl Window {
                 right: rect.x1 as i32,
                 bottom: rect.y1 as i32,
             };
-            let clip_rect = transform_clip_rect(clip_rect, size.height as i32);
+            let clip_rect =
+                transform_clip_rect(clip_rect, rotation, (size.width as i32, size.height as i32));
             region.op_rect(clip_rect, skia_safe::region::RegionOp::Union);
 
         }
         canvas.clip_region(&region, None);
-        let mut piet_ctx = Piet::new(canvas);
-        let mut win_handler = borrow_mut!(self.handler).unwrap();
-
-        win_handler.paint(&mut piet_ctx, &buffer_damage);
+        // The clip keeps the clear to the damaged region.
+        if let Some(color) = self.background() {
+            let (r, g, b, a) = color.as_rgba8();
+            canvas.clear(skia_safe::Color::from_argb(a, r, g, b));
+        }
+        self.core
+            .with_handler_and_dont_check_the_other_borrows(|h| {
+                h.pre_paint(canvas);
+                let mut piet_ctx = Piet::new(canvas);
+                h.paint(&mut piet_ctx, &buffer_damage);
+                h.post_paint(canvas);
+            });
+        if let Some((cursor, pos)) = self.drawn_cursor() {
+            // The cursor goes on top of everything the handler drew.
+            let mut image = borrow_mut!(self.cursor_image)?;
+            draw_cursor(&mut Piet::new(canvas), &cursor, pos, &mut image);
+        }
         canvas.restore();
-        borrow_mut!(self.window_state)?.prev_invalid = invalid;
         Ok(())
     }
 
-    #[track_caller]
-    fn with_handler<T, F: FnOnce(&mut dyn WinHandler) -> T>(&self, f: F) -> Option<T> {
-        if self.handler.try_borrow_mut().is_err() || self.state_mut().is_err() {
-            log::error!("other RefCells were borrowed when calling into the handler");
-            return None;
-        }
+    pub fn connect(&self, handle: WindowHandle) -> Result<(), AnyError> {
+        let size = self.size()?;
+        let scale = self.core.state()?.scale;
+        self.core
+            .with_handler_and_dont_check_the_other_borrows(|h| {
+                h.connect(&handle.into());
+                h.scale(scale);
+                h.size(size)
+            });
+        Ok(())
+    }
+
+    // TODO this is pub temporary cause we are using it to create window from application.rs
+    pub fn size(&self) -> Result<Size, AnyError> {
+        Ok(self.core.state()?.size)
+    }
 
-        self.with_handler_and_dont_check_the_other_borrows(f)
+    /// The color the damaged region is cleared to before painting.
+    pub(crate) fn background(&self) -> Option<Color> {
+        self.core.state().ok()?.background.clone()
     }
 
-    #[track_caller]
-    fn with_handler_and_dont_check_the_other_borrows<T, F: FnOnce(&mut dyn WinHandler) -> T>(
-        &self,
-        f: F,
-    ) -> Option<T> {
-        match self.handler.try_borrow_mut() {
-            Ok(mut h) => Some(f(&mut **h)),
-            Err(_) => {
-                log::error!("failed to borrow WinHandler at {}", Location::caller());
-                None
+    /// The rotation of the window on the display.
+    ///
+    /// This is `None` until the application has picked one for the display mode.
+    pub(crate) fn rotation(&self) -> Option<Rotation> {
+        self.core.state().ok()?.rotation
+    }
+
+    pub(crate) fn set_rotation(&self, rotation: Rotation) {
+        match self.core.state_mut() {
+            Ok(mut state) => state.rotation = Some(rotation),
+            Err(err) => log::error!("Window::set_rotation - {}", err),
+        }
+        // The cursor image is rotated with the window.
+        self.update_cursor_plane();
+    }
+
+    /// Show the cursor on `plane` instead of drawing it into the frames.
+    pub(crate) fn set_cursor_plane(&self, plane: CursorPlane) {
+        match borrow_mut!(self.cursor_plane) {
+            Ok(mut cursor_plane) => *cursor_plane = Some(plane),
+            Err(err) => {
+                log::error!("Window::set_cursor_plane - {}", err);
+                return;
             }
         }
+        self.update_cursor_plane();
     }
 
-    pub fn connect(&self, handle: WindowHandle) -> Result<(), AnyError> {
-        let size = self.size()?;
-        let scale = self.state()?.scale;
-        self.with_handler_and_dont_check_the_other_borrows(|h| {
-            h.connect(&handle.into());
-            h.scale(scale);
-            h.size(size)
-        });
-        Ok(())
+    /// A display was plugged in or out, repaint everything in case it shows the window again.
+    ///
+    /// This is all a hotplug does. `dri::kms` set the mode once at startup and can't set it
+    /// again, so the framebuffer and the window keep their size and the handler gets no
+    /// `size` call.
+    pub(crate) fn display_changed(&self) {
+        log::info!("the connected displays changed, repainting the window");
+        self.invalidate();
     }
 
-    // TODO this is pub temporary cause we are using it to create window from application.rs
-    pub fn size(&self) -> Result<Size, AnyError> {
-        Ok(borrow!(self.window_state)?.size)
+    pub(crate) fn destroy(&self) {
+        self.core.with_handler(|h| h.destroy());
     }
 
     pub(crate) fn run_idle(&self) {
-        let mut queue = Vec::new();
-        std::mem::swap(&mut *self.idle_queue.lock().unwrap(), &mut queue);
-
-        let mut needs_redraw = false;
-        self.with_handler(|handler| {
-            for callback in queue {
-                match callback {
-                    IdleKind::Callback(f) => {
-                        f.call(handler.as_any());
-                    }
-                    IdleKind::Token(tok) => {
-                        handler.idle(tok);
-                    }
-                    IdleKind::_Redraw => {
-                        needs_redraw = true;
-                    }
-                }
-            }
-        });
-
-        // TODO
-        //if needs_redraw {
-        //    if let Err(e) = self.redraw_now() {
-        //        log::error!("Error redrawing: {}", e);
-        //    }
-        //}
+        if self.core.run_idle() {
+            self.request_anim_frame();
+        }
     }
 
-    pub(crate) fn next_timeout(&self) -> Option<Instant> {
-        if let Some(timer) = self.timer_queue.lock().unwrap().peek() {
-            Some(timer.deadline())
-        } else {
-            None
+    pub fn handle_key_press(&self, code: Code, state: KeyState) {
+        let mods = match self.core.state_mut() {
+            Ok(mut window_state) => {
+                update_modifiers(&mut window_state.mods, code, state);
+                match state {
+                    KeyState::Down => window_state.keys_down.insert(code),
+                    KeyState::Up => window_state.keys_down.remove(&code),
+                };
+                window_state.mods
+            }
+            Err(err) => {
+                log::error!("Window::handle_key_press - {}", err);
+                return;
+            }
+        };
+        let key_event = KeyEvent {
+            code,
+            key: keycodes::code_to_key(code, mods),
+            mods,
+            // TODO location
+            location: crate::Location::Standard,
+            state,
+            repeat: false,
+            is_composing: false,
+        };
+        match state {
+            KeyState::Down => {
+                self.core.with_handler(|h| h.key_down(key_event));
+            }
+            KeyState::Up => {
+                self.core.with_handler(|h| h.key_up(key_event));
+            }
         }
     }
 
-    pub(crate) fn run_timers(&self, now: Instant) {
-        while let Some(deadline) = self.next_timeout() {
-            if deadline > now {
-                break;
+    /// Move the mouse to `pos`, in display points.
+    pub fn handle_motion_notify(&self, pos: Point) {
+        self.invalidate_cursor();
+        let (buttons, mods, first_motion, on_plane) = match self.core.state_mut() {
+            Ok(mut state) => {
+                state.mouse_pos = pos;
+                let first_motion = !std::mem::replace(&mut state.pointer_moved, true);
+                let on_plane = state.cursor_on_plane;
+                (state.buttons, state.mods, first_motion, on_plane)
+            }
+            Err(err) => {
+                log::error!("Window::handle_motion_notify - {}", err);
+                return;
+            }
+        };
+        if first_motion {
+            self.update_cursor_plane();
+        } else if on_plane {
+            if let Err(err) = self.move_cursor_plane() {
+                self.drop_cursor_plane(err);
             }
-            // Remove the timer and get the token
-            let token = self.timer_queue.lock().unwrap().pop().unwrap().token();
-            self.with_handler(|h| h.timer(token));
         }
+        self.invalidate_cursor();
+        let mouse_event = MouseEvent {
+            pos,
+            buttons,
+            mods,
+            count: 0,
+            focus: false,
+            button: MouseButton::None,
+            wheel_delta: Vec2::ZERO,
+        };
+        self.core.with_handler(|h| h.mouse_move(&mouse_event));
     }
 
-    pub(crate) fn state_mut(&self) -> Result<std::cell::RefMut<WindowState>, AnyError> {
-        borrow_mut!(self.window_state)
+    pub fn handle_button_press(&self, button: MouseButton) {
+        let (pos, buttons, mods, count) = match self.core.state_mut() {
+            Ok(mut state) => {
+                // Pressing a different button than last time starts a new sequence.
+                if state.last_click_button != button {
+                    state.click_counter.reset();
+                    state.last_click_button = button;
+                }
+                let pos = state.mouse_pos;
+                let count = state
+                    .click_counter
+                    .count_for_click(pos)
+                    .min(MAX_CLICK_COUNT);
+                state.buttons.insert(button);
+                (pos, state.buttons, state.mods, count)
+            }
+            Err(err) => {
+                log::error!("Window::handle_button_press - {}", err);
+                return;
+            }
+        };
+        let mouse_event = MouseEvent {
+            pos,
+            buttons,
+            mods,
+            count,
+            focus: false,
+            button,
+            wheel_delta: Vec2::ZERO,
+        };
+        self.core.with_handler(|h| h.mouse_down(&mouse_event));
+    }
+
+    pub fn handle_button_release(&self, button: MouseButton) {
+        let (pos, buttons, mods) = match self.core.state_mut() {
+            Ok(mut state) => {
+                // druid doesn't want the released button to be included.
+                state.buttons.remove(button);
+                (state.mouse_pos, state.buttons, state.mods)
+            }
+            Err(err) => {
+                log::error!("Window::handle_button_release - {}", err);
+                return;
+            }
+        };
+        let mouse_event = MouseEvent {
+            pos,
+            buttons,
+            mods,
+            count: 0,
+            focus: false,
+            button,
+            wheel_delta: Vec2::ZERO,
+        };
+        self.core.with_handler(|h| h.mouse_up(&mouse_event));
     }
 
-    pub(crate) fn state(&self) -> Result<std::cell::Ref<WindowState>, AnyError> {
-        borrow!(self.window_state)
+    /// Scroll by `delta`, in druid's wheel polarity.
+    pub fn handle_mouse_wheel(&self, delta: Vec2) {
+        let (pos, buttons, mods) = match self.core.state() {
+            Ok(state) => (state.mouse_pos, state.buttons, state.mods),
+            Err(err) => {
+                log::error!("Window::handle_mouse_wheel - {}", err);
+                return;
+            }
+        };
+        let mouse_event = MouseEvent {
+            pos,
+            buttons,
+            mods,
+            count: 0,
+            focus: false,
+            button: MouseButton::None,
+            wheel_delta: delta,
+        };
+        self.core.with_handler(|h| h.wheel(&mouse_event));
     }
 
     /// Schedule a redraw on the idle loop, or if we are waiting on present then schedule it for
@@ -216,7 +518,7 @@ impl Window {
     }
 
     pub fn invalidate(&self) {
-        match self.state().map(|state| state.size) {
+        match self.core.state().map(|state| state.size) {
             Ok(size) => {
                 self.invalidate_rect(size.to_rect())
             }
@@ -224,6 +526,150 @@ impl Window {
         }
     }
 
+    /// Show `cursor` at the pointer position from the next frame on.
+    pub fn set_cursor(&self, cursor: &Cursor) {
+        if self.core.state().map_or(false, |state| state.cursor == *cursor) {
+            return;
+        }
+        self.invalidate_cursor();
+        match self.core.state_mut() {
+            Ok(mut state) => state.cursor = cursor.clone(),
+            Err(err) => {
+                log::error!("Window::set_cursor - {}", err);
+                return;
+            }
+        }
+        if let Ok(mut image) = borrow_mut!(self.cursor_image) {
+            *image = None;
+        }
+        self.update_cursor_plane();
+        self.invalidate_cursor();
+    }
+
+    /// Show or hide the cursor, whether it is a system or a custom one.
+    pub fn set_cursor_visible(&self, visible: bool) {
+        self.invalidate_cursor();
+        match self.core.state_mut() {
+            Ok(mut state) => state.cursor_visible = visible,
+            Err(err) => {
+                log::error!("Window::set_cursor_visible - {}", err);
+                return;
+            }
+        }
+        self.update_cursor_plane();
+        self.invalidate_cursor();
+    }
+
+    /// The cursor and the position of its hot spot, if it is drawn.
+    ///
+    /// The cursor only shows up once a pointing device moved.
+    fn shown_cursor(&self) -> Option<(Cursor, Point)> {
+        let state = self.core.state().ok()?;
+        if !state.pointer_moved || !state.cursor_visible {
+            return None;
+        }
+        Some((state.cursor.clone(), state.mouse_pos))
+    }
+
+    /// The cursor and the position of its hot spot, if it is drawn into the frames.
+    fn drawn_cursor(&self) -> Option<(Cursor, Point)> {
+        if self.core.state().ok()?.cursor_on_plane {
+            return None;
+        }
+        self.shown_cursor()
+    }
+
+    /// Damage the area under the cursor, so that it is drawn again or erased.
+    fn invalidate_cursor(&self) {
+        if let Some((cursor, pos)) = self.drawn_cursor() {
+            self.invalidate_rect(cursor_rect(&cursor, pos));
+        }
+    }
+
+    /// Show the cursor on the cursor plane, if there is one and the cursor fits on it.
+    fn update_cursor_plane(&self) {
+        self.invalidate_cursor();
+        match self.show_on_cursor_plane() {
+            Ok(on_plane) => match self.core.state_mut() {
+                Ok(mut state) => state.cursor_on_plane = on_plane,
+                Err(err) => log::error!("Window::update_cursor_plane - {}", err),
+            },
+            Err(err) => self.drop_cursor_plane(err),
+        }
+        self.invalidate_cursor();
+    }
+
+    /// Upload the shown cursor to the cursor plane, returns whether the plane shows it.
+    fn show_on_cursor_plane(&self) -> Result<bool, AnyError> {
+        let mut plane = borrow_mut!(self.cursor_plane)?;
+        let plane = match plane.as_mut() {
+            Some(plane) => plane,
+            None => return Ok(false),
+        };
+        let (cursor, pos) = match self.shown_cursor() {
+            Some(shown) => shown,
+            None => {
+                plane.hide()?;
+                return Ok(true);
+            }
+        };
+        let (rotation, scale) = {
+            let state = self.core.state()?;
+            (state.rotation.unwrap_or(Rotation::Rotate0), state.scale)
+        };
+        match rasterize_cursor(&cursor, rotation, scale, plane.size()) {
+            Some(pixels) => {
+                plane.set_image(&pixels)?;
+                plane.move_to(self.cursor_plane_position(&cursor, pos)?)?;
+                Ok(true)
+            }
+            // Cursors that are too large for the plane are drawn instead.
+            None => {
+                plane.hide()?;
+                Ok(false)
+            }
+        }
+    }
+
+    /// Follow the pointer with the cursor plane.
+    fn move_cursor_plane(&self) -> Result<(), AnyError> {
+        if let Some((cursor, pos)) = self.shown_cursor() {
+            let position = self.cursor_plane_position(&cursor, pos)?;
+            if let Some(plane) = borrow!(self.cursor_plane)?.as_ref() {
+                plane.move_to(position)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Where the top left corner of the cursor plane image goes, in display pixels.
+    fn cursor_plane_position(&self, cursor: &Cursor, pos: Point) -> Result<(i32, i32), AnyError> {
+        let state = self.core.state()?;
+        let rect = cursor_rect(cursor, pos).to_px(state.scale).round();
+        let rect = IRect::new(
+            rect.x0 as i32,
+            rect.y0 as i32,
+            rect.x1 as i32,
+            rect.y1 as i32,
+        );
+        let rotation = state.rotation.unwrap_or(Rotation::Rotate0);
+        let size = (state.size.width as i32, state.size.height as i32);
+        let rect = transform_clip_rect(rect, rotation, size);
+        Ok((rect.left, rect.top))
+    }
+
+    /// Stop using the cursor plane after it failed, and draw the cursor instead.
+    fn drop_cursor_plane(&self, err: AnyError) {
+        log::warn!("cursor plane failed, drawing the cursor instead: {}", err);
+        if let Ok(mut plane) = borrow_mut!(self.cursor_plane) {
+            *plane = None;
+        }
+        if let Ok(mut state) = self.core.state_mut() {
+            state.cursor_on_plane = false;
+        }
+        self.invalidate_cursor();
+    }
+
     pub fn invalidate_rect(&self, rect: Rect) {
         if let Err(err) = self.add_invalid_rect(rect) {
             log::error!("Window::invalidate_rect - failed to enlarge rect: {}", err);
@@ -232,9 +678,18 @@ impl Window {
     }
 
     pub fn add_invalid_rect(&self, rect: Rect) -> Result<(), AnyError> {
-        let scale = self.state()?.scale;
+        let (scale, size) = {
+            let state = self.core.state()?;
+            (state.scale, state.size)
+        };
+        // Damage outside of the window would only grow the clip region. Rects that are fully
+        // outside of it are dropped here, expanding them would give them a pixel of area.
+        let rect = rect.intersect(size.to_rect());
+        if rect.area() <= 0.0 {
+            return Ok(());
+        }
         let rect = rect.to_px(scale).expand().to_dp(scale);
-        self.state_mut()?.invalid.add_rect(rect);
+        self.core.state_mut()?.invalid.add_rect(rect);
         Ok(())
     }
 }
@@ -242,82 +697,66 @@ impl Window {
 #[derive(Clone, Default)]
 pub struct WindowHandle(Weak<Window>);
 
-/// A handle that can get used to schedule an idle handler. Note that
-/// this handle can be cloned and sent between threads.
-#[derive(Clone)]
-pub struct IdleHandle {
-    queue: Arc<Mutex<Vec<IdleKind>>>,
-    // TODO why there was file descriptor x11, what's the purpose of it
-    // note that it's created in application.rs also
-    //    pipe: RawFd,
+pub(crate) struct WindowState {
+    pub(crate) scale: Scale,
+    size: Size,
+    rotation: Option<Rotation>,
+    /// The last known cursor position, in display points.
+    mouse_pos: Point,
+    /// The mouse buttons that are currently held down.
+    buttons: MouseButtons,
+    /// The modifier keys that are currently held down.
+    mods: Modifiers,
+    /// The keys that are currently held down.
+    keys_down: HashSet<Code>,
+    /// The cursor drawn at `mouse_pos`.
+    cursor: Cursor,
+    /// Whether the cursor is shown, see `set_cursor_visible`.
+    cursor_visible: bool,
+    /// Whether a pointing device moved yet, there is no cursor before that.
+    pointer_moved: bool,
+    /// Whether the cursor plane shows the cursor, instead of the frames.
+    cursor_on_plane: bool,
+    click_counter: ClickCounter,
+    /// The button of the last press, used to restart the click count.
+    last_click_button: MouseButton,
+    invalid: Region,
+    /// The invalid regions of the frames still shown in the other buffers.
+    damage: DamageHistory,
+    background: Option<Color>,
 }
 
-pub(crate) enum IdleKind {
-    Callback(Box<dyn IdleCallback>),
-    Token(IdleToken),
-    _Redraw,
-}
+/// A cursor image, drawn by the window on top of each frame.
+#[derive(Clone)]
+pub struct CustomCursor(Arc<CursorDesc>);
 
-impl IdleHandle {
-    fn wake(&self) {
-        //        loop {
-        //            match nix::unistd::write(self.pipe, &[0]) {
-        //                Err(nix::Error::Sys(nix::errno::Errno::EINTR)) => {}
-        //                Err(nix::Error::Sys(nix::errno::Errno::EAGAIN)) => {}
-        //                Err(e) => {
-        //                    log::error!("Failed to write to idle pipe: {}", e);
-        //                    break;
-        //                }
-        //                Ok(_) => {
-        //                    break;
-        //                }
-        //            }
-        //        }
-    }
-
-    pub(crate) fn _schedule_redraw(&self) {
-        self.queue.lock().unwrap().push(IdleKind::_Redraw);
-        self.wake();
-    }
-
-    pub fn add_idle_callback<F>(&self, callback: F)
-    where
-        F: FnOnce(&dyn Any) + Send + 'static,
-    {
-        self.queue
-            .lock()
-            .unwrap()
-            .push(IdleKind::Callback(Box::new(callback)));
-        self.wake();
-    }
-
-    pub fn add_idle_token(&self, token: IdleToken) {
-        self.queue.lock().unwrap().push(IdleKind::Token(token));
-        self.wake();
+impl PartialEq for CustomCursor {
+    fn eq(&self, other: &CustomCursor) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
     }
 }
 
-pub(crate) struct WindowState {
-    pub(crate) scale: Scale,
-    _area: Cell<ScaledArea>,
-    _idle_queue: Arc<Mutex<Vec<IdleKind>>>,
-    size: Size,
-    invalid: Region,
-    prev_invalid: Region,
+impl CustomCursor {
+    /// The area covered by the cursor with its hot spot at `pos`.
+    ///
+    /// Each image pixel covers one display point.
+    fn rect(&self, pos: Point) -> Rect {
+        let size = Size::new(self.0.image.width() as f64, self.0.image.height() as f64);
+        Rect::from_origin_size(pos - self.0.hot.to_vec2(), size)
+    }
 }
 
-// TODO: support custom cursors
-#[derive(Clone, PartialEq)]
-pub struct CustomCursor;
-
 /// Builder abstraction for creating new windows.
 pub(crate) struct WindowBuilder {
     app: Application,
     handler: Option<Box<dyn WinHandler>>,
     _title: String,
-    _cursor: Cursor,
+    cursor: Cursor,
     _menu: Option<Menu>,
     size: Size,
+    rotation: Option<Rotation>,
+    background: Option<Color>,
+    buffer_count: usize,
 }
 
 impl WindowBuilder {
@@ -326,9 +765,12 @@ impl WindowBuilder {
             app,
             handler: None,
             _title: String::new(),
-            _cursor: Cursor::Arrow,
+            cursor: Cursor::Arrow,
             _menu: None,
             size: Size::new(1920., 1080.),
+            rotation: None,
+            background: None,
+            buffer_count: DEFAULT_BUFFER_COUNT,
         }
     }
 
@@ -361,6 +803,10 @@ impl WindowBuilder {
         // Ignored
     }
 
+    pub fn set_fullscreen(&mut self, _fullscreen: bool) {
+        // The window always covers the whole display.
+    }
+
     pub fn set_level(&mut self, _level: WindowLevel) {
         // ignored
     }
@@ -373,22 +819,48 @@ impl WindowBuilder {
         self._menu = Some(menu);
     }
 
+    /// Set how the window is rotated to fit the display.
+    ///
+    /// By default windows are rotated by 90 degrees on portrait display modes.
+    pub fn set_rotation(&mut self, rotation: Rotation) {
+        self.rotation = Some(rotation);
+    }
+
+    /// Clear the damaged region to `color` before the handler paints.
+    pub fn set_background_color(&mut self, color: Color) {
+        self.background = Some(color);
+    }
+
+    /// Set how many buffers the swapchain cycles through, to know how much to redraw.
+    pub fn set_buffer_count(&mut self, count: usize) {
+        self.buffer_count = count;
+    }
+
     pub fn build(self) -> Result<WindowHandle, Error> {
         let handler = self.handler.unwrap();
         // TODO
         let state = WindowState {
             scale: Scale::new(1., 1.),
-            _area: Cell::new(ScaledArea::default()),
-            _idle_queue: Default::default(),
             size: self.size,
+            rotation: self.rotation,
+            mouse_pos: Point::ZERO,
+            buttons: MouseButtons::new(),
+            mods: Modifiers::empty(),
+            keys_down: HashSet::new(),
+            cursor: self.cursor,
+            cursor_visible: true,
+            pointer_moved: false,
+            cursor_on_plane: false,
+            click_counter: ClickCounter::default(),
+            last_click_button: MouseButton::None,
             invalid: Region::EMPTY,
-            prev_invalid: Region::EMPTY,
+            damage: DamageHistory::new(self.buffer_count),
+            background: self.background,
         };
         let window = Rc::new(Window {
-            handler: RefCell::new(handler),
-            window_state: RefCell::new(state),
-            idle_queue: Arc::new(Mutex::new(Vec::new())),
-            timer_queue: Mutex::new(BinaryHeap::new()),
+            core: WindowCore::new(handler, state, None),
+            cursor_plane: RefCell::new(None),
+            cursor_image: RefCell::new(None),
         });
 
         let handle = WindowHandle(Rc::downgrade(&window));
@@ -427,14 +899,23 @@ impl WindowHandle {
     }
 
     pub fn get_size(&self) -> Size {
-        log::warn!("WindowHandle::get_size unimplemented for web.");
-        Size::new(0.0, 0.0)
+        if let Some(w) = self.0.upgrade() {
+            match w.size() {
+                Ok(size) => return size,
+                Err(err) => log::error!("WindowHandle::get_size - {}", err),
+            }
+        }
+        Size::ZERO
     }
 
     pub fn set_window_state(&self, _state: window::WindowState) {
         log::warn!("WindowHandle::set_window_state unimplemented for web.");
     }
 
+    pub fn set_fullscreen(&self, _fullscreen: bool) {
+        // The window always covers the whole display.
+    }
+
     pub fn get_window_state(&self) -> window::WindowState {
         log::warn!("WindowHandle::get_window_state unimplemented for web.");
         window::WindowState::RESTORED
@@ -476,20 +957,37 @@ impl WindowHandle {
     }
 
     pub fn request_timer(&self, deadline: Instant) -> TimerToken {
+        self.0
+            .upgrade()
+            .map_or(TimerToken::INVALID, |w| w.core.request_timer(deadline))
+    }
+
+    pub fn request_interval_timer(&self, deadline: Instant, interval: Duration) -> TimerToken {
+        self.0.upgrade().map_or(TimerToken::INVALID, |w| {
+            w.core.request_interval_timer(deadline, interval)
+        })
+    }
+
+    pub fn cancel_timer(&self, token: TimerToken) {
         if let Some(w) = self.0.upgrade() {
-            let timer = Timer::new(deadline);
-            w.timer_queue.lock().unwrap().push(timer);
-            timer.token()
-        } else {
-            TimerToken::INVALID
+            w.core.cancel_timer(token);
         }
     }
 
-    pub fn set_cursor(&mut self, _cursor: &Cursor) {}
+    pub fn set_cursor(&mut self, cursor: &Cursor) {
+        if let Some(w) = self.0.upgrade() {
+            w.set_cursor(cursor);
+        }
+    }
 
-    pub fn make_cursor(&self, _cursor_desc: &CursorDesc) -> Option<Cursor> {
-        log::warn!("Custom cursors are not yet supported in the web backend");
-        None
+    pub fn set_cursor_visible(&self, visible: bool) {
+        if let Some(w) = self.0.upgrade() {
+            w.set_cursor_visible(visible);
+        }
+    }
+
+    pub fn make_cursor(&self, cursor_desc: &CursorDesc) -> Option<Cursor> {
+        Some(Cursor::Custom(CustomCursor(Arc::new(cursor_desc.clone()))))
     }
 
     pub fn open_file(&mut self, _options: FileDialogOptions) -> Option<FileDialogToken> {
@@ -512,25 +1010,36 @@ impl WindowHandle {
 
     /// Get a handle that can be used to schedule an idle task.
     pub fn get_idle_handle(&self) -> Option<IdleHandle> {
-        if let Some(w) = self.0.upgrade() {
-            Some(IdleHandle {
-                queue: Arc::clone(&w.idle_queue),
-                //pipe: w.idle_pipe,
-            })
-        } else {
-            None
-        }
+        self.0.upgrade().map(|w| w.core.idle_handle())
     }
 
     /// Get the `Scale` of the window.
     pub fn get_scale(&self) -> Result<Scale, ShellError> {
-        unimplemented!();
-        //Ok(self
-        //    .0
-        //    .upgrade()
-        //    .ok_or(ShellError::WindowDropped)?
-        //    .scale
-        //    .get())
+        let window = self.0.upgrade().ok_or(ShellError::WindowDropped)?;
+        let scale = window.core.state()?.scale;
+        Ok(scale)
+    }
+
+    pub fn query_modifiers(&self) -> Modifiers {
+        self.0
+            .upgrade()
+            .and_then(|w| w.core.state().ok().map(|state| state.mods))
+            .unwrap_or_default()
+    }
+
+    pub fn is_key_down(&self, code: Code) -> bool {
+        self.0.upgrade().map_or(false, |w| {
+            w.core
+                .state()
+                .map_or(false, |state| state.keys_down.contains(&code))
+        })
+    }
+
+    pub fn buffer_count(&self) -> usize {
+        self.0
+            .upgrade()
+            .and_then(|w| w.core.state().ok().map(|state| state.damage.buffer_count()))
+            .unwrap_or(DEFAULT_BUFFER_COUNT)
     }
 
     pub fn set_menu(&self, _menu: Menu) {
@@ -547,8 +1056,6 @@ impl WindowHandle {
     }
 }
 
-unsafe impl Send for IdleHandle {}
-
 fn _mouse_button(button: i16) -> Option<MouseButton> {
     match button {
         0 => Some(MouseButton::Left),
@@ -579,3 +1086,80 @@ fn _mouse_buttons(mask: u16) -> MouseButtons {
     }
     buttons
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SIZE: (i32, i32) = (100, 50);
+
+    #[test]
+    fn clip_rect_rotation() {
+        let rect = IRect::new(10, 5, 30, 15);
+        let cases = [
+            (Rotation::Rotate0, IRect::new(10, 5, 30, 15)),
+            (Rotation::Rotate90, IRect::new(35, 10, 45, 30)),
+            (Rotation::Rotate180, IRect::new(70, 35, 90, 45)),
+            (Rotation::Rotate270, IRect::new(5, 70, 15, 90)),
+        ];
+        for (rotation, expected) in cases.iter() {
+            assert_eq!(transform_clip_rect(rect, *rotation, SIZE), *expected);
+        }
+    }
+
+    #[test]
+    fn untransform_inverts_transform() {
+        let size = Size::new(SIZE.0 as f64, SIZE.1 as f64);
+        for rotation in [
+            Rotation::Rotate0,
+            Rotation::Rotate90,
+            Rotation::Rotate180,
+            Rotation::Rotate270,
+        ]
+        .iter()
+        {
+            let (x, y) = transform_point(12, 34, *rotation, SIZE);
+            let point = untransform_point(Point::new(x as f64, y as f64), *rotation, size);
+            assert_eq!(point, Point::new(12., 34.));
+        }
+    }
+
+    #[test]
+    fn cursor_rect_covers_the_cursor() {
+        use crate::piet::{ImageBuf, ImageFormat};
+
+        let pos = Point::new(50., 20.);
+        let image = ImageBuf::from_raw(vec![0; 4 * 6 * 4], ImageFormat::RgbaSeparate, 6, 4);
+        let custom = Cursor::Custom(CustomCursor(Arc::new(CursorDesc::new(image, (2., 1.)))));
+        assert_eq!(cursor_rect(&custom, pos), Rect::new(48., 19., 54., 23.));
+
+        let arrow = cursor_rect(&Cursor::Arrow, pos);
+        for point in ARROW.iter() {
+            assert!(arrow.contains(pos + Vec2::from(*point)));
+        }
+        let ibeam = cursor_rect(&Cursor::IBeam, pos);
+        assert!(ibeam.contains(pos - Vec2::new(0., IBEAM_HALF_HEIGHT)));
+        assert!(ibeam.contains(pos + Vec2::new(0., IBEAM_HALF_HEIGHT)));
+    }
+
+    #[test]
+    fn only_small_cursors_go_on_the_plane() {
+        use crate::piet::{ImageBuf, ImageFormat};
+
+        let scale = Scale::new(1., 1.);
+        let pixels = rasterize_cursor(&Cursor::Arrow, Rotation::Rotate90, scale, (64, 64)).unwrap();
+        assert_eq!(pixels.len(), 64 * 64 * 4);
+        assert!(pixels.iter().any(|&byte| byte != 0));
+
+        let image = ImageBuf::from_raw(vec![0; 80 * 8 * 4], ImageFormat::RgbaSeparate, 80, 8);
+        let wide = Cursor::Custom(CustomCursor(Arc::new(CursorDesc::new(image, (0., 0.)))));
+        assert!(rasterize_cursor(&wide, Rotation::Rotate0, scale, (64, 64)).is_none());
+
+        // The plane holds pixels, so cursors grow with the scale.
+        let image = ImageBuf::from_raw(vec![0; 40 * 8 * 4], ImageFormat::RgbaSeparate, 40, 8);
+        let custom = Cursor::Custom(CustomCursor(Arc::new(CursorDesc::new(image, (0., 0.)))));
+        assert!(rasterize_cursor(&custom, Rotation::Rotate0, scale, (64, 64)).is_some());
+        let double = Scale::new(2., 2.);
+        assert!(rasterize_cursor(&custom, Rotation::Rotate0, double, (64, 64)).is_none());
+    }
+}
@@ -0,0 +1,134 @@
+// Copyright 2020 The Druid Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Headless application, runs idle callbacks and timers until there is nothing left to do.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::Instant;
+
+use crate::application::{AppHandler, Theme};
+
+use super::clipboard::Clipboard;
+use super::window::Window;
+
+use anyhow::Error;
+
+/// The locale reported to handlers, so that tests don't depend on the machine they run on.
+const LOCALE: &str = "en-US";
+
+#[derive(Clone)]
+pub(crate) struct Application {
+    /// The mutable `Application` state.
+    state: Rc<RefCell<State>>,
+}
+
+/// The mutable `Application` state.
+struct State {
+    /// Whether `Application::quit` has already been called.
+    quitting: bool,
+    /// A collection of all the `Application` windows.
+    windows: Vec<Rc<Window>>,
+}
+
+impl Application {
+    pub fn new() -> Result<Application, Error> {
+        let state = Rc::new(RefCell::new(State {
+            quitting: false,
+            windows: Vec::new(),
+        }));
+        Ok(Application { state })
+    }
+
+    pub(crate) fn add_window(&self, window: Rc<Window>) -> Result<(), Error> {
+        borrow_mut!(self.state)?.windows.push(window);
+        Ok(())
+    }
+
+    /// The windows that haven't been closed yet.
+    fn windows(&self) -> Result<Vec<Rc<Window>>, Error> {
+        let mut state = borrow_mut!(self.state)?;
+        state.windows.retain(|window| !window.is_closed());
+        Ok(state.windows.clone())
+    }
+
+    /// Run idle callbacks and timers until the application quits, or until no
+    /// window has any work scheduled.
+    ///
+    /// Nothing is painted here, windows are only rendered when asked to with
+    /// `WindowHandle::render_to_image`.
+    pub fn run(self, _handler: Option<Box<dyn AppHandler>>) {
+        if let Err(e) = self.run_inner() {
+            log::error!("{}", e);
+        }
+    }
+
+    fn run_inner(&self) -> Result<(), Error> {
+        while !self.quitting() {
+            let windows = self.windows()?;
+            let now = Instant::now();
+            for window in &windows {
+                window.core.run_timers(now);
+                window.core.run_idle();
+            }
+            if windows.iter().any(|window| window.core.has_idle()) {
+                continue;
+            }
+            let next_timeout = windows.iter().filter_map(|w| w.core.next_timeout()).min();
+            match next_timeout {
+                Some(deadline) => {
+                    std::thread::sleep(deadline.saturating_duration_since(Instant::now()))
+                }
+                None => break,
+            }
+        }
+        for window in self.windows()? {
+            window.close();
+        }
+        Ok(())
+    }
+
+    fn quitting(&self) -> bool {
+        borrow!(self.state)
+            .map(|state| state.quitting)
+            .unwrap_or(false)
+    }
+
+    /// Stop running after the current iteration.
+    pub fn quit(&self) {
+        match borrow_mut!(self.state) {
+            Ok(mut state) => state.quitting = true,
+            Err(e) => log::error!("Application::quit - {}", e),
+        }
+    }
+
+    pub fn clipboard(&self) -> Clipboard {
+        Clipboard
+    }
+
+    /// Always light, so that tests don't depend on the machine they run on.
+    pub fn system_theme(&self) -> Theme {
+        Theme::Light
+    }
+
+    #[cfg(target_os = "macos")]
+    pub fn hide(&self) {}
+
+    #[cfg(target_os = "macos")]
+    pub fn hide_others(&self) {}
+
+    pub fn get_locale() -> String {
+        LOCALE.to_string()
+    }
+}
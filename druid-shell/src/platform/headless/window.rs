@@ -0,0 +1,533 @@
+// Copyright 2020 The Druid Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Windows that render into memory instead of onto a display.
+
+use std::cell::{Cell, RefCell};
+use std::rc::{Rc, Weak};
+use std::time::{Duration, Instant};
+
+use crate::{
+    kurbo::{Point, Rect, Size},
+    piet::{Piet, PietText},
+};
+
+use anyhow::Error as AnyError;
+use skia_safe::{AlphaType, ColorType, IRect, ImageInfo, Surface};
+
+use super::application::Application;
+use super::menu::Menu;
+use crate::dialog::FileDialogOptions;
+use crate::error::Error as ShellError;
+use crate::platform::shared::WindowCore;
+use crate::scale::{Scalable, Scale};
+
+use crate::mouse::{Cursor, CursorDesc};
+use crate::region::Region;
+use crate::window;
+use crate::window::{FileDialogToken, TimerToken, WinHandler, WindowLevel};
+
+pub use crate::platform::shared::IdleHandle;
+
+/// The bytes per pixel of images returned by `Window::render_to_image`.
+const BYTES_PER_PIXEL: usize = 4;
+
+pub struct Window {
+    pub(crate) core: WindowCore<WindowState>,
+    /// The pixels of the window, `None` while it has no area.
+    surface: RefCell<Option<Surface>>,
+    closed: Cell<bool>,
+}
+
+/// Create a raster surface covering `size` display points.
+fn create_surface(size: Size, scale: Scale) -> Option<Surface> {
+    let size = size.to_px(scale).round();
+    Surface::new_raster_n32_premul((size.width as i32, size.height as i32))
+}
+
+impl Window {
+    /// Paint the invalid region of the window into its surface.
+    fn render(&self) -> Result<(), AnyError> {
+        // important for AnimStart and invalidation of required regions
+        self.core.with_handler(|h| h.prepare_paint());
+        let invalid = std::mem::replace(&mut self.core.state_mut()?.invalid, Region::EMPTY);
+        let scale = self.core.state()?.scale;
+        let mut surface = borrow_mut!(self.surface)?;
+        let surface = match surface.as_mut() {
+            Some(surface) => surface,
+            None => return Ok(()),
+        };
+        // The surface keeps its pixels between renders, so only the invalid region is painted.
+        let canvas = surface.canvas();
+        canvas.save();
+        let mut region = skia_safe::region::Region::new();
+        for rect in invalid.rects() {
+            let rect = rect.to_px(scale);
+            let rect = IRect::new(
+                rect.x0 as i32,
+                rect.y0 as i32,
+                rect.x1 as i32,
+                rect.y1 as i32,
+            );
+            region.op_rect(rect, skia_safe::region::RegionOp::Union);
+        }
+        canvas.clip_region(&region, None);
+        let mut piet_ctx = Piet::new(canvas);
+        self.core
+            .with_handler_and_dont_check_the_other_borrows(|h| h.paint(&mut piet_ctx, &invalid));
+        canvas.restore();
+        Ok(())
+    }
+
+    /// Paint the invalid region of the window and read back all of its pixels.
+    ///
+    /// The pixels are RGBA8 with straight (not premultiplied) alpha, in rows from
+    /// top to bottom. The image is empty if the window has no area.
+    pub fn render_to_image(&self) -> Result<Vec<u8>, AnyError> {
+        self.render()?;
+        let mut surface = borrow_mut!(self.surface)?;
+        let surface = match surface.as_mut() {
+            Some(surface) => surface,
+            None => return Ok(Vec::new()),
+        };
+        let info = ImageInfo::new(
+            (surface.width(), surface.height()),
+            ColorType::RGBA8888,
+            AlphaType::Unpremul,
+            None,
+        );
+        let row_bytes = surface.width() as usize * BYTES_PER_PIXEL;
+        let mut pixels = vec![0; row_bytes * surface.height() as usize];
+        if !surface.read_pixels(&info, &mut pixels, row_bytes, (0, 0)) {
+            return Err(anyhow::anyhow!("failed to read the surface pixels"));
+        }
+        Ok(pixels)
+    }
+
+    /// Render the window, and return its pixels with their size in pixels.
+    ///
+    /// See `render_to_image` for the pixel format.
+    pub fn capture(&self) -> Result<(Vec<u8>, Size), AnyError> {
+        let pixels = self.render_to_image()?;
+        let size = match borrow!(self.surface)?.as_ref() {
+            Some(surface) => Size::new(surface.width() as f64, surface.height() as f64),
+            None => Size::ZERO,
+        };
+        Ok((pixels, size))
+    }
+
+    fn connect(&self, handle: WindowHandle) -> Result<(), AnyError> {
+        let size = self.size()?;
+        let scale = self.core.state()?.scale;
+        self.core
+            .with_handler_and_dont_check_the_other_borrows(|h| {
+                h.connect(&handle.into());
+                h.scale(scale);
+                h.size(size)
+            });
+        self.invalidate();
+        Ok(())
+    }
+
+    /// The size of the window, in display points.
+    pub fn size(&self) -> Result<Size, AnyError> {
+        Ok(self.core.state()?.size)
+    }
+
+    /// Resize the window, the whole window is invalidated.
+    fn set_size(&self, size: Size) -> Result<(), AnyError> {
+        let scale = {
+            let mut state = self.core.state_mut()?;
+            state.size = size;
+            state.scale
+        };
+        *borrow_mut!(self.surface)? = create_surface(size, scale);
+        self.core.with_handler(|h| h.size(size));
+        self.invalidate();
+        Ok(())
+    }
+
+    pub(crate) fn is_closed(&self) -> bool {
+        self.closed.get()
+    }
+
+    /// Destroy the window, the application drops it on its next iteration.
+    pub(crate) fn close(&self) {
+        if !self.closed.replace(true) {
+            self.core.with_handler(|h| h.destroy());
+        }
+    }
+
+    pub fn invalidate(&self) {
+        match self.size() {
+            Ok(size) => self.invalidate_rect(size.to_rect()),
+            Err(err) => log::error!("Window::invalidate - failed to get size: {}", err),
+        }
+    }
+
+    pub fn invalidate_rect(&self, rect: Rect) {
+        if let Err(err) = self.add_invalid_rect(rect) {
+            log::error!("Window::invalidate_rect - failed to enlarge rect: {}", err);
+        }
+    }
+
+    fn add_invalid_rect(&self, rect: Rect) -> Result<(), AnyError> {
+        let mut state = self.core.state_mut()?;
+        let scale = state.scale;
+        let rect = rect.intersect(state.size.to_rect());
+        // Expanding a rect outside of the window would give it a pixel of area.
+        if rect.area() <= 0.0 {
+            return Ok(());
+        }
+        let rect = rect.to_px(scale).expand().to_dp(scale);
+        state.invalid.add_rect(rect);
+        Ok(())
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct WindowHandle(Weak<Window>);
+
+pub(crate) struct WindowState {
+    pub(crate) scale: Scale,
+    size: Size,
+    position: Point,
+    window_state: window::WindowState,
+    invalid: Region,
+}
+
+#[derive(Clone, PartialEq)]
+pub struct CustomCursor;
+
+/// Builder abstraction for creating new windows.
+pub(crate) struct WindowBuilder {
+    app: Application,
+    handler: Option<Box<dyn WinHandler>>,
+    size: Size,
+    position: Point,
+    window_state: window::WindowState,
+}
+
+impl WindowBuilder {
+    pub fn new(app: Application) -> WindowBuilder {
+        WindowBuilder {
+            app,
+            handler: None,
+            size: Size::new(800., 600.),
+            position: Point::ZERO,
+            window_state: window::WindowState::RESTORED,
+        }
+    }
+
+    /// This takes ownership, and is typically used with UiMain
+    pub fn set_handler(&mut self, handler: Box<dyn WinHandler>) {
+        self.handler = Some(handler);
+    }
+
+    pub fn set_size(&mut self, size: Size) {
+        self.size = size;
+    }
+
+    pub fn set_min_size(&mut self, _: Size) {
+        // Ignored
+    }
+
+    pub fn resizable(&mut self, _resizable: bool) {
+        // Ignored
+    }
+
+    pub fn show_titlebar(&mut self, _show_titlebar: bool) {
+        // Ignored
+    }
+
+    pub fn set_position(&mut self, position: Point) {
+        self.position = position;
+    }
+
+    pub fn set_window_state(&mut self, state: window::WindowState) {
+        self.window_state = state;
+    }
+
+    pub fn set_fullscreen(&mut self, _fullscreen: bool) {
+        // Ignored
+    }
+
+    pub fn set_level(&mut self, _level: WindowLevel) {
+        // Ignored
+    }
+
+    pub fn set_title<S: Into<String>>(&mut self, _title: S) {
+        // Ignored
+    }
+
+    pub fn set_menu(&mut self, _menu: Menu) {
+        // Ignored
+    }
+
+    pub fn build(self) -> Result<WindowHandle, ShellError> {
+        let handler = self
+            .handler
+            .ok_or_else(|| anyhow::anyhow!("the window has no handler"))?;
+        let scale = Scale::new(1., 1.);
+        let state = WindowState {
+            scale,
+            size: self.size,
+            position: self.position,
+            window_state: self.window_state,
+            invalid: Region::EMPTY,
+        };
+        let window = Rc::new(Window {
+            core: WindowCore::new(handler, state, None),
+            surface: RefCell::new(create_surface(self.size, scale)),
+            closed: Cell::new(false),
+        });
+
+        let handle = WindowHandle(Rc::downgrade(&window));
+        window.connect(handle.clone())?;
+        self.app.add_window(window)?;
+        Ok(handle)
+    }
+}
+
+impl WindowHandle {
+    pub fn show(&self) {}
+
+    pub fn resizable(&self, _resizable: bool) {}
+
+    pub fn show_titlebar(&self, _show_titlebar: bool) {}
+
+    pub fn set_position(&self, position: Point) {
+        if let Some(window) = self.0.upgrade() {
+            match window.core.state_mut() {
+                Ok(mut state) => state.position = position,
+                Err(err) => log::error!("WindowHandle::set_position - {}", err),
+            }
+        }
+    }
+
+    pub fn get_position(&self) -> Point {
+        self.0
+            .upgrade()
+            .and_then(|window| window.core.state().ok().map(|state| state.position))
+            .unwrap_or_default()
+    }
+
+    pub fn set_level(&self, _level: WindowLevel) {}
+
+    pub fn set_size(&self, size: Size) {
+        if let Some(window) = self.0.upgrade() {
+            if let Err(err) = window.set_size(size) {
+                log::error!("WindowHandle::set_size - {}", err);
+            }
+        }
+    }
+
+    pub fn get_size(&self) -> Size {
+        self.0
+            .upgrade()
+            .and_then(|window| window.size().ok())
+            .unwrap_or_default()
+    }
+
+    pub fn set_window_state(&self, window_state: window::WindowState) {
+        if let Some(window) = self.0.upgrade() {
+            match window.core.state_mut() {
+                Ok(mut state) => state.window_state = window_state,
+                Err(err) => log::error!("WindowHandle::set_window_state - {}", err),
+            }
+        }
+    }
+
+    pub fn get_window_state(&self) -> window::WindowState {
+        self.0
+            .upgrade()
+            .and_then(|window| window.core.state().ok().map(|state| state.window_state))
+            .unwrap_or(window::WindowState::RESTORED)
+    }
+
+    pub fn set_fullscreen(&self, _fullscreen: bool) {}
+
+    pub fn handle_titlebar(&self, _val: bool) {}
+
+    pub fn close(&self) {
+        if let Some(window) = self.0.upgrade() {
+            window.close();
+        }
+    }
+
+    pub fn bring_to_front_and_focus(&self) {}
+
+    pub fn request_anim_frame(&self) {
+        // Windows are only painted by `render_to_image`.
+    }
+
+    pub fn invalidate_rect(&self, rect: Rect) {
+        if let Some(window) = self.0.upgrade() {
+            window.invalidate_rect(rect);
+        }
+    }
+
+    pub fn invalidate(&self) {
+        if let Some(window) = self.0.upgrade() {
+            window.invalidate();
+        }
+    }
+
+    /// Paint the window and read back its pixels, see `Window::render_to_image`.
+    pub fn render_to_image(&self) -> Result<Vec<u8>, ShellError> {
+        let window = self.0.upgrade().ok_or(ShellError::WindowDropped)?;
+        Ok(window.render_to_image()?)
+    }
+
+    pub fn request_capture(&self) {
+        // Windows are painted when they are captured.
+    }
+
+    pub fn capture(&self) -> Result<(Vec<u8>, Size), ShellError> {
+        let window = self.0.upgrade().ok_or(ShellError::WindowDropped)?;
+        Ok(window.capture()?)
+    }
+
+    pub fn text(&self) -> PietText {
+        PietText::new()
+    }
+
+    pub fn request_timer(&self, deadline: Instant) -> TimerToken {
+        self.0
+            .upgrade()
+            .map_or(TimerToken::INVALID, |w| w.core.request_timer(deadline))
+    }
+
+    pub fn request_interval_timer(&self, deadline: Instant, interval: Duration) -> TimerToken {
+        self.0.upgrade().map_or(TimerToken::INVALID, |w| {
+            w.core.request_interval_timer(deadline, interval)
+        })
+    }
+
+    pub fn cancel_timer(&self, token: TimerToken) {
+        if let Some(w) = self.0.upgrade() {
+            w.core.cancel_timer(token);
+        }
+    }
+
+    pub fn set_cursor(&mut self, _cursor: &Cursor) {}
+
+    pub fn make_cursor(&self, _cursor_desc: &CursorDesc) -> Option<Cursor> {
+        None
+    }
+
+    pub fn open_file(&mut self, _options: FileDialogOptions) -> Option<FileDialogToken> {
+        None
+    }
+
+    pub fn save_as(&mut self, _options: FileDialogOptions) -> Option<FileDialogToken> {
+        None
+    }
+
+    /// Get a handle that can be used to schedule an idle task.
+    pub fn get_idle_handle(&self) -> Option<IdleHandle> {
+        self.0.upgrade().map(|w| w.core.idle_handle())
+    }
+
+    /// Get the `Scale` of the window.
+    pub fn get_scale(&self) -> Result<Scale, ShellError> {
+        let window = self.0.upgrade().ok_or(ShellError::WindowDropped)?;
+        let scale = window.core.state()?.scale;
+        Ok(scale)
+    }
+
+    pub fn set_menu(&self, _menu: Menu) {}
+
+    pub fn show_context_menu(&self, _menu: Menu, _pos: Point) {}
+
+    pub fn set_title(&self, _title: impl Into<String>) {}
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::any::Any;
+
+    use crate::piet::{Color, RenderContext};
+
+    /// Fills everything it's asked to paint with its color.
+    struct FillHandler(Rc<RefCell<Color>>);
+
+    impl WinHandler for FillHandler {
+        fn connect(&mut self, _handle: &window::WindowHandle) {}
+
+        fn prepare_paint(&mut self) {}
+
+        fn paint(&mut self, piet: &mut Piet, invalid: &Region) {
+            let color = self.0.borrow().clone();
+            for rect in invalid.rects() {
+                piet.fill(rect, &color);
+            }
+        }
+
+        fn as_any(&mut self) -> &mut dyn Any {
+            self
+        }
+    }
+
+    fn make_window(color: Rc<RefCell<Color>>, size: Size) -> Rc<Window> {
+        let app = Application::new().unwrap();
+        let mut builder = WindowBuilder::new(app.clone());
+        builder.set_handler(Box::new(FillHandler(color)));
+        builder.set_size(size);
+        // `app` keeps the window alive until we have upgraded the handle.
+        let handle = builder.build().unwrap();
+        handle.0.upgrade().unwrap()
+    }
+
+    fn pixel(image: &[u8], width: usize, x: usize, y: usize) -> &[u8] {
+        let start = (y * width + x) * BYTES_PER_PIXEL;
+        &image[start..start + BYTES_PER_PIXEL]
+    }
+
+    #[test]
+    fn render_paints_the_whole_window_initially() {
+        let color = Rc::new(RefCell::new(Color::rgb8(255, 0, 0)));
+        let window = make_window(color, Size::new(4., 2.));
+
+        let image = window.render_to_image().unwrap();
+        assert_eq!(image.len(), 4 * 2 * BYTES_PER_PIXEL);
+        assert!(image.chunks(BYTES_PER_PIXEL).all(|p| p == [255, 0, 0, 255]));
+    }
+
+    #[test]
+    fn render_only_repaints_invalid_rects() {
+        let color = Rc::new(RefCell::new(Color::rgb8(255, 0, 0)));
+        let window = make_window(color.clone(), Size::new(4., 2.));
+        window.render_to_image().unwrap();
+
+        *color.borrow_mut() = Color::rgb8(0, 0, 255);
+        window.invalidate_rect(Rect::new(2., 0., 4., 2.));
+        let image = window.render_to_image().unwrap();
+        assert_eq!(pixel(&image, 4, 1, 1), [255, 0, 0, 255]);
+        assert_eq!(pixel(&image, 4, 2, 0), [0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn set_size_resizes_the_image() {
+        let color = Rc::new(RefCell::new(Color::rgb8(255, 0, 0)));
+        let window = make_window(color, Size::new(4., 2.));
+
+        window.set_size(Size::new(3., 3.)).unwrap();
+        let image = window.render_to_image().unwrap();
+        assert_eq!(image.len(), 3 * 3 * BYTES_PER_PIXEL);
+        assert_eq!(pixel(&image, 3, 2, 2), [255, 0, 0, 255]);
+    }
+}
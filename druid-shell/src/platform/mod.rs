@@ -15,6 +15,9 @@
 //! Platform specific implementations.
 
 // It would be clearer to use cfg_if! macros here, but that breaks rustfmt.
+//
+// `skia_backend`, `dri_backend` and `headless_backend` are set by `build.rs`. Gate API
+// that only some backends implement on the same cfgs, so the two can't drift apart.
 
 #[cfg(target_os = "windows")]
 mod windows;
@@ -42,18 +45,25 @@ pub use self::gtk::*;
 #[cfg(all(feature = "gtk", target_os = "linux"))]
 pub(crate) mod shared;
 
-#[cfg(all(any(feature = "winit_x11", feature = "winit_wayland"), any(target_os = "linux", target_os = "macos")))]
+#[cfg(skia_backend)]
 mod skia;
-#[cfg(all(any(feature = "winit_x11", feature = "winit_wayland"), any(target_os = "linux", target_os = "macos")))]
+#[cfg(skia_backend)]
 pub use skia::*;
-#[cfg(all(any(feature = "winit_x11", feature = "winit_wayland"), any(target_os = "linux", target_os = "macos")))]
+#[cfg(skia_backend)]
 pub(crate) mod shared;
 
-#[cfg(all(feature = "direct_render", target_os = "linux"))]
+#[cfg(dri_backend)]
 mod dri_platform;
-#[cfg(all(feature = "direct_render", target_os = "linux"))]
+#[cfg(dri_backend)]
 pub use dri_platform::*;
-#[cfg(all(feature = "direct_render", target_os = "linux"))]
+#[cfg(dri_backend)]
+pub(crate) mod shared;
+
+#[cfg(headless_backend)]
+mod headless;
+#[cfg(headless_backend)]
+pub use headless::*;
+#[cfg(headless_backend)]
 pub(crate) mod shared;
 
 #[cfg(target_arch = "wasm32")]
@@ -0,0 +1,93 @@
+// Copyright 2020 The Druid Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Tracking which parts of the back buffers are out of date.
+
+use std::collections::VecDeque;
+
+use crate::region::Region;
+
+/// The number of buffers of a double buffered swapchain.
+pub(crate) const DEFAULT_BUFFER_COUNT: usize = 2;
+
+/// The invalid regions of the last few frames.
+///
+/// With `n` buffers, the buffer that is drawn into was last drawn `n - 1` frames ago,
+/// so everything invalidated since then has to be redrawn.
+pub(crate) struct DamageHistory {
+    /// The invalid regions of the previous frames, the most recent first.
+    frames: VecDeque<Region>,
+    buffer_count: usize,
+}
+
+impl DamageHistory {
+    pub(crate) fn new(buffer_count: usize) -> DamageHistory {
+        DamageHistory {
+            frames: VecDeque::new(),
+            buffer_count: buffer_count.max(1),
+        }
+    }
+
+    /// The number of buffers the swapchain cycles through.
+    pub(crate) fn buffer_count(&self) -> usize {
+        self.buffer_count
+    }
+
+    /// Returns the region of the back buffer to redraw for a frame that invalidated
+    /// `invalid`, and remembers `invalid` for the next frames.
+    pub(crate) fn buffer_damage(&mut self, invalid: Region) -> Region {
+        let mut damage = invalid.clone();
+        for region in &self.frames {
+            damage.union_with(region);
+        }
+        self.frames.push_front(invalid);
+        self.frames.truncate(self.buffer_count - 1);
+        damage
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::kurbo::Rect;
+
+    fn region(rect: Rect) -> Region {
+        let mut region = Region::EMPTY;
+        region.add_rect(rect);
+        region
+    }
+
+    #[test]
+    fn damage_covers_the_buffer_age() {
+        let a = Rect::new(0., 0., 10., 10.);
+        let b = Rect::new(20., 0., 30., 10.);
+        let c = Rect::new(40., 0., 50., 10.);
+
+        let mut double = DamageHistory::new(2);
+        double.buffer_damage(region(a));
+        double.buffer_damage(region(b));
+        assert_eq!(double.buffer_damage(region(c)).rects(), &[c, b]);
+
+        let mut triple = DamageHistory::new(3);
+        triple.buffer_damage(region(a));
+        triple.buffer_damage(region(b));
+        assert_eq!(triple.buffer_damage(region(c)).rects(), &[c, b, a]);
+
+        let mut single = DamageHistory::new(0);
+        single.buffer_damage(region(a));
+        assert_eq!(single.buffer_count(), 1);
+        assert_eq!(single.buffer_damage(region(b)).rects(), &[b]);
+    }
+}
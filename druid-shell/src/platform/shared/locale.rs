@@ -0,0 +1,107 @@
+// Copyright 2020 The Druid Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Detection of the system locale.
+
+/// The locale used when the system doesn't tell us one.
+const DEFAULT_LOCALE: &str = "en-US";
+
+/// The variables that name the language of messages, in the order POSIX gives them
+/// precedence.
+#[cfg(target_os = "linux")]
+const LOCALE_VARS: &[&str] = &["LC_ALL", "LC_MESSAGES", "LANG"];
+
+/// Returns the system locale as a BCP-47 language tag.
+///
+/// On Linux this is read from the `LC_ALL`, `LC_MESSAGES` and `LANG` environment variables.
+pub fn system_locale() -> String {
+    match detect_locale() {
+        Some(locale) => locale,
+        None => {
+            log::warn!(
+                "Failed to detect the system locale, defaulting to {}",
+                DEFAULT_LOCALE
+            );
+            DEFAULT_LOCALE.into()
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn detect_locale() -> Option<String> {
+    locale_from_env(|name| std::env::var(name).ok())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn detect_locale() -> Option<String> {
+    sys_locale::get_locale().filter(|locale| !is_posix_locale(locale))
+}
+
+/// Find the locale in the environment variables that `var` reads.
+///
+/// Like POSIX, an empty variable is treated as if it wasn't set.
+#[cfg(target_os = "linux")]
+fn locale_from_env(var: impl Fn(&str) -> Option<String>) -> Option<String> {
+    let code = LOCALE_VARS
+        .iter()
+        .filter_map(|name| var(name))
+        .find(|value| !value.is_empty())?;
+    // Drop the encoding and the modifier, as in `de_DE.UTF-8@euro`.
+    let language = code.split(|c| c == '.' || c == '@').next()?;
+    if is_posix_locale(language) {
+        None
+    } else {
+        Some(language.replace('_', "-"))
+    }
+}
+
+/// The POSIX locale is what you get when nothing is configured, it names no language.
+fn is_posix_locale(locale: &str) -> bool {
+    locale == "C" || locale == "POSIX"
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod test {
+    use super::*;
+
+    fn env(vars: &'static [(&'static str, &'static str)]) -> impl Fn(&str) -> Option<String> {
+        move |name| {
+            vars.iter()
+                .find(|(var, _)| *var == name)
+                .map(|(_, value)| value.to_string())
+        }
+    }
+
+    #[test]
+    fn lc_all_takes_precedence() {
+        let vars = env(&[("LC_ALL", "fr_FR.UTF-8"), ("LANG", "de_DE.UTF-8")]);
+        assert_eq!(locale_from_env(vars), Some("fr-FR".into()));
+    }
+
+    #[test]
+    fn empty_variables_are_skipped() {
+        let vars = env(&[
+            ("LC_ALL", ""),
+            ("LC_MESSAGES", ""),
+            ("LANG", "de_DE.UTF-8@euro"),
+        ]);
+        assert_eq!(locale_from_env(vars), Some("de-DE".into()));
+    }
+
+    #[test]
+    fn posix_locale_names_no_language() {
+        assert_eq!(locale_from_env(env(&[("LC_ALL", "C.UTF-8")])), None);
+        assert_eq!(locale_from_env(env(&[])), None);
+    }
+}
@@ -15,8 +15,34 @@
 //! Logic that is shared by more than one platform.
 
 cfg_if::cfg_if! {
-    if #[cfg(any(target_os = "macos", target_os = "linux"))] {
+    if #[cfg(all(any(target_os = "macos", target_os = "linux"), not(headless_backend)))] {
         mod keyboard;
         pub use keyboard::*;
     }
 }
+
+cfg_if::cfg_if! {
+    if #[cfg(skia_backend)] {
+        mod glutin_keyboard;
+        pub use glutin_keyboard::*;
+    }
+}
+
+cfg_if::cfg_if! {
+    if #[cfg(any(skia_backend, dri_backend))] {
+        mod damage;
+        mod locale;
+        pub(crate) use damage::*;
+        pub use locale::*;
+    }
+}
+
+cfg_if::cfg_if! {
+    if #[cfg(any(skia_backend, dri_backend, headless_backend))] {
+        mod timer;
+        mod window_core;
+        pub(crate) use timer::*;
+        pub(crate) use window_core::*;
+        pub use window_core::IdleHandle;
+    }
+}
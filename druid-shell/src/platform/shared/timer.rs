@@ -0,0 +1,87 @@
+// Copyright 2020 The Druid Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Timers for the backends that run their own event loop.
+
+use std::cmp::Ordering;
+use std::time::{Duration, Instant};
+
+use crate::window::TimerToken;
+
+/// The shortest interval of a repeating timer, so that it can't fire in a busy loop.
+const MIN_INTERVAL: Duration = Duration::from_millis(1);
+
+/// A timer is a deadline (`std::Time::Instant`) and a `TimerToken`.
+///
+/// Repeating timers also have an interval, they are rescheduled after firing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Timer {
+    deadline: Instant,
+    token: TimerToken,
+    interval: Option<Duration>,
+}
+
+impl Timer {
+    pub(crate) fn new(deadline: Instant) -> Self {
+        let token = TimerToken::next();
+        Self {
+            deadline,
+            token,
+            interval: None,
+        }
+    }
+
+    /// A timer that first fires at `deadline` and then every `interval`.
+    pub(crate) fn with_interval(deadline: Instant, interval: Duration) -> Self {
+        Self {
+            interval: Some(interval.max(MIN_INTERVAL)),
+            ..Self::new(deadline)
+        }
+    }
+
+    /// The next occurence of a repeating timer that fired at `now`.
+    ///
+    /// Occurences that are already in the past are skipped.
+    pub(crate) fn next(&self, now: Instant) -> Option<Self> {
+        let interval = self.interval?;
+        let mut deadline = self.deadline + interval;
+        if deadline <= now {
+            deadline = now + interval;
+        }
+        Some(Self { deadline, ..*self })
+    }
+
+    pub(crate) fn deadline(&self) -> Instant {
+        self.deadline
+    }
+
+    pub(crate) fn token(&self) -> TimerToken {
+        self.token
+    }
+}
+
+impl Ord for Timer {
+    /// Ordering is so that earliest deadline sorts first
+    // "Earliest deadline first" that a std::collections::BinaryHeap will have the earliest timer
+    // at its head, which is just what is needed for timer management.
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.deadline.cmp(&other.deadline).reverse()
+    }
+}
+
+impl PartialOrd for Timer {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
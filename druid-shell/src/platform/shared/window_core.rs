@@ -0,0 +1,253 @@
+// Copyright 2021 The Druid Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The parts of a window that are the same for the backends that run their own event loop.
+
+use std::any::Any;
+use std::cell::{Ref, RefCell, RefMut};
+use std::collections::BinaryHeap;
+use std::panic::Location;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::Error as AnyError;
+
+use super::Timer;
+use crate::common_util::IdleCallback;
+#[cfg(skia_backend)]
+use crate::dialog::FileInfo;
+#[cfg(skia_backend)]
+use crate::window::FileDialogToken;
+use crate::window::{IdleToken, TimerToken, WinHandler};
+
+/// Wakes the event loop up, so that it runs the idle queue.
+pub(crate) type Waker = Arc<dyn Fn() + Send + Sync>;
+
+/// The handler of a window, its backend specific state `S`, and its idle and timer queues.
+pub(crate) struct WindowCore<S> {
+    handler: RefCell<Box<dyn WinHandler>>,
+    state: RefCell<S>,
+    idle_queue: Arc<Mutex<Vec<IdleKind>>>,
+    timer_queue: Mutex<BinaryHeap<Timer>>,
+    /// Called after idle work is queued, `None` if the event loop checks the queue anyway.
+    waker: Option<Waker>,
+}
+
+impl<S> WindowCore<S> {
+    pub(crate) fn new(handler: Box<dyn WinHandler>, state: S, waker: Option<Waker>) -> Self {
+        WindowCore {
+            handler: RefCell::new(handler),
+            state: RefCell::new(state),
+            idle_queue: Arc::new(Mutex::new(Vec::new())),
+            timer_queue: Mutex::new(BinaryHeap::new()),
+            waker,
+        }
+    }
+
+    #[track_caller]
+    pub(crate) fn with_handler<T, F: FnOnce(&mut dyn WinHandler) -> T>(&self, f: F) -> Option<T> {
+        if self.handler.try_borrow_mut().is_err() || self.state_mut().is_err() {
+            log::error!("other RefCells were borrowed when calling into the handler");
+            return None;
+        }
+
+        self.with_handler_and_dont_check_the_other_borrows(f)
+    }
+
+    #[track_caller]
+    pub(crate) fn with_handler_and_dont_check_the_other_borrows<
+        T,
+        F: FnOnce(&mut dyn WinHandler) -> T,
+    >(
+        &self,
+        f: F,
+    ) -> Option<T> {
+        match self.handler.try_borrow_mut() {
+            Ok(mut h) => Some(f(&mut **h)),
+            Err(_) => {
+                log::error!("failed to borrow WinHandler at {}", Location::caller());
+                None
+            }
+        }
+    }
+
+    pub(crate) fn state_mut(&self) -> Result<RefMut<S>, AnyError> {
+        borrow_mut!(self.state)
+    }
+
+    pub(crate) fn state(&self) -> Result<Ref<S>, AnyError> {
+        borrow!(self.state)
+    }
+
+    /// A handle that queues idle work for this window from any thread.
+    pub(crate) fn idle_handle(&self) -> IdleHandle {
+        IdleHandle {
+            queue: Arc::clone(&self.idle_queue),
+            waker: self.waker.clone(),
+        }
+    }
+
+    /// Queue `kind` for the next `run_idle`, and wake the event loop up.
+    #[cfg(skia_backend)]
+    pub(crate) fn push_idle(&self, kind: IdleKind) {
+        self.idle_handle().push(kind);
+    }
+
+    /// Whether there is idle work waiting to run.
+    #[cfg(headless_backend)]
+    pub(crate) fn has_idle(&self) -> bool {
+        !self.idle_queue.lock().unwrap().is_empty()
+    }
+
+    /// Run the queued idle work.
+    ///
+    /// Returns `true` if a redraw was scheduled, the backend has to request the frame.
+    pub(crate) fn run_idle(&self) -> bool {
+        let mut queue = Vec::new();
+        std::mem::swap(&mut *self.idle_queue.lock().unwrap(), &mut queue);
+
+        let mut needs_redraw = false;
+        self.with_handler(|handler| {
+            for callback in queue {
+                match callback {
+                    IdleKind::Callback(f) => {
+                        f.call(handler.as_any());
+                    }
+                    IdleKind::Token(tok) => {
+                        handler.idle(tok);
+                    }
+                    #[cfg(skia_backend)]
+                    IdleKind::OpenFile(token, file_info) => {
+                        handler.open_file(token, file_info);
+                    }
+                    #[cfg(skia_backend)]
+                    IdleKind::OpenFiles(token, files) => {
+                        handler.open_files(token, files);
+                    }
+                    #[cfg(skia_backend)]
+                    IdleKind::SaveAs(token, file_info) => {
+                        handler.save_as(token, file_info);
+                    }
+                    #[cfg(skia_backend)]
+                    IdleKind::Command(id) => {
+                        handler.command(id);
+                    }
+                    IdleKind::_Redraw => {
+                        needs_redraw = true;
+                    }
+                }
+            }
+        });
+        needs_redraw
+    }
+
+    pub(crate) fn request_timer(&self, deadline: Instant) -> TimerToken {
+        self.add_timer(Timer::new(deadline))
+    }
+
+    pub(crate) fn request_interval_timer(
+        &self,
+        deadline: Instant,
+        interval: Duration,
+    ) -> TimerToken {
+        self.add_timer(Timer::with_interval(deadline, interval))
+    }
+
+    fn add_timer(&self, timer: Timer) -> TimerToken {
+        self.timer_queue.lock().unwrap().push(timer);
+        timer.token()
+    }
+
+    pub(crate) fn cancel_timer(&self, token: TimerToken) {
+        let mut queue = self.timer_queue.lock().unwrap();
+        let timers = std::mem::take(&mut *queue);
+        *queue = timers
+            .into_iter()
+            .filter(|timer| timer.token() != token)
+            .collect();
+    }
+
+    pub(crate) fn next_timeout(&self) -> Option<Instant> {
+        self.timer_queue
+            .lock()
+            .unwrap()
+            .peek()
+            .map(|timer| timer.deadline())
+    }
+
+    pub(crate) fn run_timers(&self, now: Instant) {
+        while let Some(deadline) = self.next_timeout() {
+            if deadline > now {
+                break;
+            }
+            // Remove the timer and get the token
+            let token = {
+                let mut queue = self.timer_queue.lock().unwrap();
+                let timer = queue.pop().unwrap();
+                // Reschedule before calling the handler, so that it can cancel the timer.
+                if let Some(next) = timer.next(now) {
+                    queue.push(next);
+                }
+                timer.token()
+            };
+            self.with_handler(|h| h.timer(token));
+        }
+    }
+}
+
+/// A handle that can get used to schedule an idle handler. Note that
+/// this handle can be cloned and sent between threads.
+#[derive(Clone)]
+pub struct IdleHandle {
+    queue: Arc<Mutex<Vec<IdleKind>>>,
+    waker: Option<Waker>,
+}
+
+pub(crate) enum IdleKind {
+    Callback(Box<dyn IdleCallback>),
+    Token(IdleToken),
+    #[cfg(skia_backend)]
+    OpenFile(FileDialogToken, Option<FileInfo>),
+    #[cfg(skia_backend)]
+    OpenFiles(FileDialogToken, Vec<FileInfo>),
+    #[cfg(skia_backend)]
+    SaveAs(FileDialogToken, Option<FileInfo>),
+    #[cfg(skia_backend)]
+    Command(u32),
+    _Redraw,
+}
+
+impl IdleHandle {
+    pub(crate) fn push(&self, kind: IdleKind) {
+        self.queue.lock().unwrap().push(kind);
+        if let Some(waker) = &self.waker {
+            waker();
+        }
+    }
+
+    pub(crate) fn _schedule_redraw(&self) {
+        self.push(IdleKind::_Redraw);
+    }
+
+    pub fn add_idle_callback<F>(&self, callback: F)
+    where
+        F: FnOnce(&dyn Any) + Send + 'static,
+    {
+        self.push(IdleKind::Callback(Box::new(callback)));
+    }
+
+    pub fn add_idle_token(&self, token: IdleToken) {
+        self.push(IdleKind::Token(token));
+    }
+}
@@ -14,38 +14,64 @@
 
 //! Implementation of features at the application scope.
 
+use std::any::Any;
 use std::cell::RefCell;
+use std::collections::HashMap;
 use std::convert::TryInto;
 use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::thread;
 use std::time::{Duration, Instant};
 
-use crate::application::AppHandler;
+use crate::application::{AppHandler, FrameStats, Theme};
+use crate::hotkey::HotKey;
+use crate::kurbo::Size;
+use crate::platform::shared;
 use crate::scale::Scale;
+use crate::screen::Monitor;
+use crate::window::IdleToken;
 
 use super::clipboard::Clipboard;
-use super::window::Window;
+use super::global_hotkeys::GlobalHotKeys;
+use super::menu;
+use super::screen;
+use super::software::Presenter;
+use super::window::{GlOptions, Window};
 
-use glutin::dpi::PhysicalPosition;
-
-use glutin::dpi::LogicalSize;
+use glutin::dpi::{PhysicalPosition, PhysicalSize};
+use raw_window_handle::HasRawWindowHandle;
 
+use glutin::platform::run_return::EventLoopExtRunReturn;
+#[cfg(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd",
+))]
+use glutin::platform::unix::EventLoopExtUnix;
 #[cfg(windows)]
 use glutin::platform::windows::WindowBuilderExtWindows;
 
 use glutin::{
-    event::{Event, WindowEvent},
-    event_loop::{ControlFlow, EventLoop},
-    window::WindowBuilder,
+    event::{DeviceEvent, Event, WindowEvent},
+    event_loop::{ControlFlow, EventLoop, EventLoopProxy, EventLoopWindowTarget},
+    window::{WindowBuilder, WindowId},
     ContextBuilder, GlRequest,
 };
 use skia_safe::{
     gpu::{gl::FramebufferInfo, BackendRenderTarget, SurfaceOrigin},
-    ColorType, Surface,
+    AlphaType, ColorType, ImageInfo, Surface,
 };
 
 use anyhow::{anyhow, Error};
 
-const TARGET_FPS: u64 = 60;
+/// The frame rate the loop paces redraws to, if neither the application nor the monitor
+/// says otherwise.
+const DEFAULT_TARGET_FPS: u32 = 60;
+/// The scheduler could wake us up later than asked, so frames are started this much earlier.
+// We can even calculate it dynamicly as some average :)
+const SCHEDULER_LAG: Duration = Duration::from_millis(1);
 /// Whether to render incrementaly in separate texture and then render it to screen
 //#[cfg(not(target_os = "macos"))]
 //const BLIT_CANVAS: bool = false;
@@ -54,18 +80,528 @@ const TARGET_FPS: u64 = 60;
 
 type WindowedContext = glutin::ContextWrapper<glutin::PossiblyCurrent, glutin::window::Window>;
 
+/// The OpenGL and OpenGL ES versions requested for the windows' contexts.
+///
+/// OpenGL is tried first, then OpenGL ES.
+#[derive(Debug, Clone, Copy)]
+struct GlVersions {
+    opengl: (u8, u8),
+    opengles: (u8, u8),
+}
+
+impl Default for GlVersions {
+    fn default() -> GlVersions {
+        GlVersions {
+            opengl: (4, 6),
+            opengles: (3, 1),
+        }
+    }
+}
+
+/// Events sent to the event loop from other threads.
+#[derive(Debug)]
+pub(crate) enum UserEvent {
+    /// Wake the loop up so that it runs the idle callbacks.
+    Wake,
+    /// A payload for the handler of the focused window.
+    User(Box<dyn Any + Send>),
+    /// The system theme, detected on a background thread.
+    Theme(Theme),
+}
+
+/// Wakes the event loop up, from any thread.
+///
+/// This can be handed out before the loop exists, waking it up does nothing until it runs.
+#[derive(Clone, Default)]
+pub(crate) struct LoopWaker(Arc<Mutex<LoopProxy>>);
+
+#[derive(Default)]
+struct LoopProxy {
+    proxy: Option<EventLoopProxy<UserEvent>>,
+    /// User events sent before the loop existed, they are sent once it does.
+    pending: Vec<UserEvent>,
+}
+
+impl LoopWaker {
+    pub(crate) fn wake(&self) {
+        if let Some(proxy) = &self.0.lock().unwrap().proxy {
+            // This only fails once the loop has exited, then there is nothing left to wake.
+            let _ = proxy.send_event(UserEvent::Wake);
+        }
+    }
+
+    /// Send `event` to the loop, or keep it until the loop runs.
+    fn send(&self, event: UserEvent) -> Result<(), Error> {
+        let mut loop_proxy = self.0.lock().unwrap();
+        match &loop_proxy.proxy {
+            Some(proxy) => proxy
+                .send_event(event)
+                .map_err(|_| anyhow!("The event loop has exited")),
+            None => {
+                loop_proxy.pending.push(event);
+                Ok(())
+            }
+        }
+    }
+
+    fn set_proxy(&self, proxy: EventLoopProxy<UserEvent>) {
+        let mut loop_proxy = self.0.lock().unwrap();
+        for event in loop_proxy.pending.drain(..) {
+            let _ = proxy.send_event(event);
+        }
+        loop_proxy.proxy = Some(proxy);
+    }
+}
+
+/// Sends user events to the event loop, from any thread.
+#[derive(Clone)]
+pub(crate) struct UserEventSender(LoopWaker);
+
+impl UserEventSender {
+    pub(crate) fn send(&self, event: Box<dyn Any + Send>) -> Result<(), Error> {
+        self.0.send(UserEvent::User(event))
+    }
+}
+
 #[derive(Clone)]
 pub(crate) struct Application {
     /// The mutable `Application` state.
     state: Rc<RefCell<State>>,
+    /// The loop driven by `pump_events`, created on its first call.
+    pumped: Rc<RefCell<Option<PumpedLoop>>>,
+}
+
+/// The event loop state that is kept between events.
+struct LoopState {
+    surfaces: HashMap<WindowId, WindowSurface>,
+    /// Whether the host renders with `render_now`, rather than the loop on redraw requests.
+    manual_present: bool,
+    last_ts: Instant,
+    time: Duration,
+    frames_cnt: u32,
+    redraw_timestamp: Instant,
+}
+
+/// An event loop that the host drives with `pump_events` and `render_now`.
+struct PumpedLoop {
+    event_loop: EventLoop<UserEvent>,
+    loop_state: LoopState,
 }
 
 /// The mutable `Application` state.
 struct State {
     /// Whether `Application::quit` has already been called.
-    _quitting: bool,
+    quitting: bool,
+    /// Windows that have been built but don't have a glutin window yet.
+    pending: Vec<Rc<Window>>,
     /// A collection of all the `Application` windows.
-    window: Option<Rc<Window>>, // we only want to support one window for now
+    windows: HashMap<WindowId, Rc<Window>>,
+    /// The system clipboard, opened on first use.
+    clipboard: Option<Clipboard>,
+    /// Wakes the event loop up once it runs.
+    waker: LoopWaker,
+    /// The frame rate set by the application, 0 if redraws aren't paced.
+    ///
+    /// When it isn't set, redraws are paced to `refresh_rate`.
+    target_fps: Option<u32>,
+    /// The refresh rate of the monitor the last window was placed on, if it is known.
+    refresh_rate: Option<u32>,
+    /// The global hotkeys, set up when the first one is registered.
+    global_hotkeys: Option<GlobalHotKeys>,
+    /// The last system theme we saw, `None` until it is first detected.
+    theme: Option<Theme>,
+    /// Whether a background thread is asking the system for its theme.
+    detecting_theme: bool,
+    frame_stats: FrameStats,
+    /// The window that last gained focus.
+    focused: Option<WindowId>,
+    /// The monitors, as of the last time the loop looked. Empty until it runs.
+    monitors: Vec<Monitor>,
+    gl_versions: GlVersions,
+    /// Whether mouse moves are merged into one per loop iteration, see `set_coalesce_mouse_moves`.
+    coalesce_mouse_moves: bool,
+    /// Whether the event loop may run on any thread, see `allow_any_thread`.
+    any_thread: bool,
+}
+
+/// How the frames of one glutin window are rendered and shown.
+enum Renderer {
+    Gl {
+        /// This is only `None` while the context is being made current.
+        gl_context: Option<WindowedContext>,
+        gr_context: skia_safe::gpu::Context,
+    },
+    /// The slow path for when there is no usable GL, frames are rendered on the CPU.
+    Raster {
+        window: glutin::window::Window,
+        presenter: Presenter,
+    },
+}
+
+impl Renderer {
+    fn new_gl(
+        window_builder: WindowBuilder,
+        target: &EventLoopWindowTarget<UserEvent>,
+        options: GlOptions,
+        versions: GlVersions,
+    ) -> Result<Renderer, Error> {
+        let gl_context = ContextBuilder::new()
+            .with_gl(GlRequest::GlThenGles {
+                opengl_version: versions.opengl,
+                opengles_version: versions.opengles,
+            })
+            .with_multisampling(options.sample_count)
+            .with_vsync(options.vsync)
+            .with_stencil_buffer(options.stencil_bits)
+            .with_depth_buffer(options.depth_bits)
+            // seems like double buffering is not supported for wayland in glutin
+            //.with_double_buffer(Some(true))
+            .build_windowed(window_builder, target)
+            .map_err(|e| {
+                anyhow!(
+                    "No OpenGL {}.{} or OpenGL ES {}.{} context: {}",
+                    versions.opengl.0,
+                    versions.opengl.1,
+                    versions.opengles.0,
+                    versions.opengles.1,
+                    e
+                )
+            })?;
+
+        // Load OpenGL, and make the context current.
+        let gl_context = unsafe { gl_context.make_current().map_err(|e| e.1)? };
+
+        gl::load_with(|name| gl_context.get_proc_address(name));
+
+        let gr_context = skia_safe::gpu::Context::new_gl(None, None)
+            .ok_or_else(|| anyhow!("failed to create context"))?;
+
+        Ok(Renderer::Gl {
+            gl_context: Some(gl_context),
+            gr_context,
+        })
+    }
+
+    fn new_raster(
+        window_builder: WindowBuilder,
+        target: &EventLoopWindowTarget<UserEvent>,
+    ) -> Result<Renderer, Error> {
+        let window = window_builder.build(target)?;
+        let presenter = Presenter::new(&window)?;
+        Ok(Renderer::Raster { window, presenter })
+    }
+
+    fn window(&self) -> &glutin::window::Window {
+        match self {
+            Renderer::Gl { gl_context, .. } => gl_context
+                .as_ref()
+                .expect("the GL context is only taken while making it current")
+                .window(),
+            Renderer::Raster { window, .. } => window,
+        }
+    }
+
+    /// Make this window's GL context the current one, if it isn't already.
+    fn make_current(&mut self) -> Result<(), Error> {
+        let gl_context = match self {
+            Renderer::Gl { gl_context, .. } => gl_context,
+            Renderer::Raster { .. } => return Ok(()),
+        };
+        match gl_context.take() {
+            Some(context) if context.is_current() => *gl_context = Some(context),
+            Some(context) => match unsafe { context.make_current() } {
+                Ok(context) => *gl_context = Some(context),
+                Err((context, err)) => {
+                    *gl_context = Some(context);
+                    return Err(err.into());
+                }
+            },
+            None => {}
+        }
+        Ok(())
+    }
+
+    /// Create a surface covering the whole window.
+    fn create_surface(&mut self) -> Result<Surface, Error> {
+        match self {
+            Renderer::Gl {
+                gl_context: Some(gl_context),
+                gr_context,
+            } => create_surface(gl_context, gr_context),
+            Renderer::Raster { window, .. } => {
+                let size = window.inner_size();
+                Surface::new_raster_n32_premul((size.width.try_into()?, size.height.try_into()?))
+                    .ok_or_else(|| anyhow!("failed to create a raster surface"))
+            }
+            _ => Err(anyhow!("the GL context is being made current")),
+        }
+    }
+
+    fn resize(&self, physical_size: PhysicalSize<u32>) {
+        if let Renderer::Gl {
+            gl_context: Some(gl_context),
+            ..
+        } = self
+        {
+            gl_context.resize(physical_size);
+        }
+    }
+
+    /// Show the frame that was rendered into `surface`.
+    fn present(&mut self, surface: &mut Surface) -> Result<(), Error> {
+        match self {
+            Renderer::Gl {
+                gl_context: Some(gl_context),
+                ..
+            } => gl_context.swap_buffers()?,
+            Renderer::Raster { presenter, .. } => presenter.present(surface)?,
+            // The context is only taken while it is being made current.
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// The renderer and skia surface of one glutin window.
+struct WindowSurface {
+    renderer: Renderer,
+    surface: Surface,
+    /// The size from the last `Resized` event, the surface is only recreated for it
+    /// right before the next frame.
+    pending_size: Option<PhysicalSize<u32>>,
+    cursor_position: PhysicalPosition<f64>,
+    /// The position of the last `CursorMoved` that hasn't been delivered yet.
+    pending_motion: Option<PhysicalPosition<f64>>,
+    /// Whether buffer swaps wait for the display, which paces the frames.
+    vsync: bool,
+}
+
+impl WindowSurface {
+    fn new(
+        window: &Window,
+        target: &EventLoopWindowTarget<UserEvent>,
+        versions: GlVersions,
+    ) -> Result<WindowSurface, Error> {
+        let window_builder = || -> Result<WindowBuilder, Error> {
+            let window_builder = window.window_builder()?;
+            #[cfg(windows)]
+            let window_builder = window_builder.with_drag_and_drop(false);
+            Ok(window_builder)
+        };
+
+        // Drop the optional GL features one at a time until a context can be created.
+        let mut options = window.gl_options()?;
+        let gl_renderer = loop {
+            match Renderer::new_gl(window_builder()?, target, options, versions) {
+                Err(err) if options.sample_count > 0 => {
+                    log::warn!(
+                        "Failed to set up GL with {} samples, disabling multisampling: {}",
+                        options.sample_count,
+                        err
+                    );
+                    options.sample_count = 0;
+                }
+                Err(err) if options.vsync => {
+                    log::warn!("Failed to set up GL with vsync, disabling it: {}", err);
+                    options.vsync = false;
+                }
+                Err(err) if options.stencil_bits > 0 || options.depth_bits > 0 => {
+                    log::warn!(
+                        "Failed to set up GL with stencil and depth buffers, disabling them: {}",
+                        err
+                    );
+                    options.stencil_bits = 0;
+                    options.depth_bits = 0;
+                }
+                gl_renderer => break gl_renderer,
+            }
+        };
+        let (mut renderer, vsync) = match gl_renderer {
+            Ok(renderer) => (renderer, options.vsync),
+            Err(err) => {
+                log::warn!(
+                    "Failed to set up GL, falling back to slow software rendering: {}",
+                    err
+                );
+                (Renderer::new_raster(window_builder()?, target)?, false)
+            }
+        };
+
+        let surface = renderer.create_surface()?;
+        let scale_factor = renderer.window().scale_factor();
+        window.set_scale(Scale::new(scale_factor, scale_factor))?;
+
+        let mut window_surface = WindowSurface {
+            renderer,
+            surface,
+            pending_size: None,
+            cursor_position: PhysicalPosition::new(0., 0.),
+            pending_motion: None,
+            vsync,
+        };
+        window_surface.apply_scale(window)?;
+        Ok(window_surface)
+    }
+
+    fn window(&self) -> &glutin::window::Window {
+        self.renderer.window()
+    }
+
+    /// Deliver the last coalesced mouse move, if there is one.
+    fn flush_motion(&mut self, window: &Window) {
+        if let Some(position) = self.pending_motion.take() {
+            window.handle_motion_notify(position);
+        }
+    }
+
+    fn apply_scale(&mut self, window: &Window) -> Result<(), Error> {
+        let scale = window.core.state()?.scale;
+        self.surface
+            .canvas()
+            .scale((scale.x() as f32, scale.y() as f32));
+        Ok(())
+    }
+
+    /// Tell the window about its new size, and recreate the surface before the next frame.
+    ///
+    /// Interactive resizing sends many of these per frame, until then the old surface is
+    /// drawn at the stale size.
+    fn resize(&mut self, window: &Window, physical_size: PhysicalSize<u32>) -> Result<(), Error> {
+        self.pending_size = Some(physical_size);
+        window.request_anim_frame();
+        window.screen_size_changed(physical_size)
+    }
+
+    fn apply_pending_size(&mut self, window: &Window) -> Result<(), Error> {
+        let physical_size = match self.pending_size.take() {
+            Some(physical_size) => physical_size,
+            None => return Ok(()),
+        };
+        self.renderer.resize(physical_size);
+        self.surface = self.renderer.create_surface()?;
+        self.apply_scale(window)
+    }
+
+    /// Recreate the surface for a new scale factor.
+    fn rescale(
+        &mut self,
+        window: &Window,
+        scale_factor: f64,
+        physical_size: PhysicalSize<u32>,
+    ) -> Result<(), Error> {
+        window.set_scale(Scale::new(scale_factor, scale_factor))?;
+        self.resize(window, physical_size)?;
+        // Everything has to be laid out and painted again at the new scale.
+        window.invalidate();
+        Ok(())
+    }
+
+    fn render(&mut self, window: &Window) -> Result<(), Error> {
+        self.renderer.make_current()?;
+        self.apply_pending_size(window)?;
+        let surface_canvas = self.surface.canvas();
+        window.render(&mut *surface_canvas)?;
+        surface_canvas.flush();
+        if window.take_capture_request() {
+            let (pixels, size) = read_pixels(&mut self.surface)?;
+            window.set_capture(pixels, size);
+        }
+        self.renderer.present(&mut self.surface)
+    }
+}
+
+/// Returns `true` if `err` means that the GL context can't be used anymore.
+///
+/// This happens on GPU resets, and on some drivers after suspending.
+fn is_context_lost(err: &Error) -> bool {
+    matches!(
+        err.downcast_ref::<glutin::ContextError>(),
+        Some(glutin::ContextError::ContextLost)
+    )
+}
+
+/// Read back the whole surface as straight alpha RGBA8, with its size in pixels.
+fn read_pixels(surface: &mut Surface) -> Result<(Vec<u8>, Size), Error> {
+    let (width, height) = (surface.width(), surface.height());
+    let info = ImageInfo::new(
+        (width, height),
+        ColorType::RGBA8888,
+        AlphaType::Unpremul,
+        None,
+    );
+    let row_bytes = width as usize * 4;
+    let mut pixels = vec![0; row_bytes * height as usize];
+    if !surface.read_pixels(&info, &mut pixels, row_bytes, (0, 0)) {
+        return Err(anyhow!("failed to read the surface pixels"));
+    }
+    Ok((pixels, Size::new(width as f64, height as f64)))
+}
+
+/// The framebuffer that is bound in the current GL context.
+///
+/// Some drivers bind a different framebuffer after the window was resized, so this is
+/// queried for every new surface.
+fn framebuffer_info() -> Result<FramebufferInfo, Error> {
+    let mut fboid: gl::types::GLint = 0;
+    unsafe { gl::GetIntegerv(gl::FRAMEBUFFER_BINDING, &mut fboid) };
+
+    Ok(FramebufferInfo {
+        fboid: fboid.try_into()?,
+        format: skia_safe::gpu::gl::Format::RGBA8.into(),
+    })
+}
+
+/// Create the event loop, which may live on any thread if `any_thread` is set.
+#[cfg(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd",
+))]
+fn new_event_loop(any_thread: bool) -> EventLoop<UserEvent> {
+    if any_thread {
+        EventLoop::new_any_thread()
+    } else {
+        EventLoop::with_user_event()
+    }
+}
+
+/// Create the event loop, it has to be on the main thread on this platform.
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "dragonfly",
+    target_os = "freebsd",
+    target_os = "netbsd",
+    target_os = "openbsd",
+)))]
+fn new_event_loop(_any_thread: bool) -> EventLoop<UserEvent> {
+    EventLoop::with_user_event()
+}
+
+/// Create a surface for the window of `windowed_context`, which must be current.
+fn create_surface(
+    windowed_context: &WindowedContext,
+    gr_context: &mut skia_safe::gpu::Context,
+) -> Result<skia_safe::Surface, Error> {
+    let fb_info = framebuffer_info()?;
+    let pixel_format = windowed_context.get_pixel_format();
+    let size = windowed_context.window().inner_size();
+    let backend_render_target = BackendRenderTarget::new_gl(
+        (size.width.try_into()?, size.height.try_into()?),
+        pixel_format.multisampling.and_then(|s| s.try_into().ok()),
+        pixel_format.stencil_bits.try_into()?,
+        fb_info,
+    );
+    Surface::from_backend_render_target(
+        gr_context,
+        &backend_render_target,
+        SurfaceOrigin::BottomLeft,
+        ColorType::RGBA8888,
+        None,
+        None,
+    )
+    .ok_or_else(|| anyhow!("No window"))
 }
 
 impl Application {
@@ -78,213 +614,792 @@ impl Application {
         }
         //use super::super::strip_access_key;
         let state = Rc::new(RefCell::new(State {
-            _quitting: false,
-            window: None,
+            quitting: false,
+            pending: Vec::new(),
+            windows: HashMap::new(),
+            clipboard: None,
+            waker: LoopWaker::default(),
+            target_fps: None,
+            refresh_rate: None,
+            global_hotkeys: None,
+            theme: None,
+            detecting_theme: false,
+            frame_stats: FrameStats::default(),
+            focused: None,
+            monitors: Vec::new(),
+            gl_versions: GlVersions::default(),
+            coalesce_mouse_moves: true,
+            any_thread: false,
         }));
-        Ok(Application { state })
+        Ok(Application {
+            state,
+            pumped: Rc::new(RefCell::new(None)),
+        })
     }
 
+    /// Add a window, the glutin window is created once the event loop picks it up.
     pub fn add_window(&self, window: Rc<Window>) -> Result<(), Error> {
-        borrow_mut!(self.state)?.window = Some(window);
+        borrow_mut!(self.state)?.pending.push(window);
         Ok(())
     }
 
-    pub fn window(&self) -> Result<Rc<Window>, Error> {
-        let state = borrow_mut!(self.state)?;
-        state
-            .window
-            .as_ref()
-            .cloned()
-            .ok_or_else(|| anyhow!("No window"))
+    pub(crate) fn waker(&self) -> LoopWaker {
+        match borrow!(self.state) {
+            Ok(state) => state.waker.clone(),
+            Err(e) => {
+                log::error!("Application::waker - {}", e);
+                LoopWaker::default()
+            }
+        }
     }
 
-    pub fn run(self, _handler: Option<Box<dyn AppHandler>>) {
-        if let Err(e) = self.run_inner() {
-            log::error!("{}", e);
+    fn window(&self, id: WindowId) -> Option<Rc<Window>> {
+        borrow!(self.state).ok()?.windows.get(&id).cloned()
+    }
+
+    /// Create glutin windows for all the windows that were added since the last call.
+    fn create_pending_windows(
+        &self,
+        target: &EventLoopWindowTarget<UserEvent>,
+        surfaces: &mut HashMap<WindowId, WindowSurface>,
+    ) -> Result<(), Error> {
+        let pending = std::mem::take(&mut borrow_mut!(self.state)?.pending);
+        for window in pending {
+            self.create_surface(window, target, surfaces)?;
         }
+        Ok(())
     }
 
-    pub fn run_inner(self) -> Result<(), Error> {
-        let window_size = self.window().unwrap().size()?;
-        let event_loop = EventLoop::new();
-        let logical_window_size = LogicalSize::new(window_size.width, window_size.height);
-
-        // Open a window.
-        let window_builder = WindowBuilder::new()
-            .with_title("Minimal example")
-            .with_inner_size(logical_window_size);
-        #[cfg(windows)]
-        let window_builder = window_builder.with_drag_and_drop(false);
-
-        // Create an OpenGL 3.x context for Pathfinder to use.
-        let gl_context = ContextBuilder::new()
-            .with_gl(GlRequest::GlThenGles {
-                opengl_version: (4, 6),
-                opengles_version: (3, 1),
-            })
-            // seems like double buffering is not supported for wayland in glutin
-            //.with_double_buffer(Some(true))
-            .build_windowed(window_builder, &event_loop)?;
+    /// Create a glutin window for `window`.
+    fn create_surface(
+        &self,
+        window: Rc<Window>,
+        target: &EventLoopWindowTarget<UserEvent>,
+        surfaces: &mut HashMap<WindowId, WindowSurface>,
+    ) -> Result<(), Error> {
+        window.apply_centering(self.primary_monitor())?;
+        let surface = WindowSurface::new(&window, target, self.gl_versions())?;
+        window.apply_position(surface.window());
+        window.set_raw_window_handle(surface.window().raw_window_handle());
+        let id = surface.window().id();
+        self.update_refresh_rate(surface.window());
+        surfaces.insert(id, surface);
+        borrow_mut!(self.state)?.windows.insert(id, window);
+        Ok(())
+    }
 
-        // Load OpenGL, and make the context current.
-        let gl_context = unsafe { gl_context.make_current().map_err(|e| e.1)? };
+    /// Replace the glutin window `id` after its GL context was lost, and repaint it.
+    ///
+    /// A lost context can't be made current again, and glutin only creates contexts
+    /// together with a new window.
+    fn recreate_surface(
+        &self,
+        id: WindowId,
+        target: &EventLoopWindowTarget<UserEvent>,
+        surfaces: &mut HashMap<WindowId, WindowSurface>,
+    ) -> Result<(), Error> {
+        surfaces.remove(&id);
+        let window = borrow_mut!(self.state)?
+            .windows
+            .remove(&id)
+            .ok_or_else(|| anyhow!("No window"))?;
+        if let Err(e) = self.create_surface(window.clone(), target, surfaces) {
+            window.close();
+            return Err(e);
+        }
+        window.invalidate();
+        Ok(())
+    }
 
-        gl::load_with(|name| gl_context.get_proc_address(name));
+    pub(crate) fn quitting(&self) -> bool {
+        borrow!(self.state)
+            .map(|state| state.quitting)
+            .unwrap_or(false)
+    }
 
-        let mut gr_context = skia_safe::gpu::Context::new_gl(None, None)
-            .ok_or_else(|| anyhow!("failed to create context"))?;
+    /// Close all windows, including the ones that don't have a glutin window yet.
+    pub(crate) fn close_all_windows(&self) {
+        let windows = match borrow_mut!(self.state) {
+            Ok(mut state) => {
+                let mut windows = std::mem::take(&mut state.pending);
+                windows.extend(state.windows.values().cloned());
+                windows
+            }
+            Err(e) => {
+                log::error!("Application::close_all_windows - {}", e);
+                return;
+            }
+        };
+        for window in windows {
+            window.close();
+        }
+    }
 
-        let fb_info = {
-            let mut fboid: gl::types::GLint = 0;
-            unsafe { gl::GetIntegerv(gl::FRAMEBUFFER_BINDING, &mut fboid) };
+    /// Drop the glutin windows of all the windows that have been closed.
+    fn remove_closed_windows(&self, surfaces: &mut HashMap<WindowId, WindowSurface>) {
+        if let Ok(mut state) = borrow_mut!(self.state) {
+            state.windows.retain(|_, window| !window.is_closed());
+            surfaces.retain(|id, _| state.windows.contains_key(id));
+        }
+    }
 
-            FramebufferInfo {
-                fboid: fboid.try_into()?,
-                format: skia_safe::gpu::gl::Format::RGBA8.into(),
+    /// Deliver menu activations to the window that owns the menu item.
+    fn dispatch_menu_commands(&self) {
+        for id in menu::take_activated_items() {
+            match self
+                .windows()
+                .into_iter()
+                .find(|(_, window)| window.has_menu_item(id))
+            {
+                Some((_, window)) => window.handle_command(id),
+                None => log::warn!("No window has a menu item with id {}", id),
             }
+        }
+    }
+
+    /// Deliver the tokens of the pressed global hotkeys to one of the windows.
+    fn dispatch_global_hotkeys(&self) {
+        let tokens = match borrow!(self.state) {
+            Ok(state) => match &state.global_hotkeys {
+                Some(hotkeys) => hotkeys.take_pressed(),
+                None => return,
+            },
+            Err(_) => return,
         };
+        if tokens.is_empty() {
+            return;
+        }
+        match self.target_window() {
+            Some(window) => {
+                for token in tokens {
+                    window.handle_idle_token(token);
+                }
+            }
+            None => log::warn!("No window to deliver global hotkeys to"),
+        }
+    }
 
-        fn create_surface(
-            windowed_context: &WindowedContext,
-            fb_info: FramebufferInfo,
-            gr_context: &mut skia_safe::gpu::Context,
-        ) -> Result<skia_safe::Surface, Error> {
-            let pixel_format = windowed_context.get_pixel_format();
-            let size = windowed_context.window().inner_size();
-            let backend_render_target = BackendRenderTarget::new_gl(
-                (size.width.try_into()?, size.height.try_into()?),
-                pixel_format.multisampling.and_then(|s| s.try_into().ok()),
-                pixel_format.stencil_bits.try_into()?,
-                fb_info,
-            );
-            Surface::from_backend_render_target(
-                gr_context,
-                &backend_render_target,
-                SurfaceOrigin::BottomLeft,
-                ColorType::RGBA8888,
-                None,
-                None,
-            )
-            .ok_or_else(|| anyhow!("No window"))
-        }
-
-        let mut surface = create_surface(&gl_context, fb_info, &mut gr_context)?;
-        // It's not working on wayland for some reason.
-        let _sf = gl_context.window().scale_factor() as f32;
-        let scale = if let Ok(window) = self.window() {
-            window.state().unwrap().scale
+    /// Ask the system for its theme on a background thread, the answer arrives as a
+    /// `UserEvent::Theme`.
+    ///
+    /// Detection can block on D-Bus, so it never runs on the loop thread.
+    fn detect_theme_in_background(&self) {
+        let waker = match borrow_mut!(self.state) {
+            Ok(mut state) if !state.detecting_theme => {
+                state.detecting_theme = true;
+                state.waker.clone()
+            }
+            Ok(_) => return,
+            Err(e) => {
+                log::error!("Application::detect_theme_in_background - {}", e);
+                return;
+            }
+        };
+        let spawned = thread::Builder::new()
+            .name("druid-shell-theme".into())
+            .spawn(move || {
+                let _ = waker.send(UserEvent::Theme(detect_theme()));
+            });
+        if let Err(e) = spawned {
+            log::error!("Failed to spawn the theme detection thread: {}", e);
+            if let Ok(mut state) = borrow_mut!(self.state) {
+                state.detecting_theme = false;
+            }
+        }
+    }
+
+    /// Remember `theme`, and tell the windows if it differs from the last one we saw.
+    fn set_theme(&self, theme: Theme) {
+        let previous = match borrow_mut!(self.state) {
+            Ok(mut state) => state.theme.replace(theme),
+            Err(e) => {
+                log::error!("Application::set_theme - {}", e);
+                return;
+            }
+        };
+        if previous.map_or(false, |previous| previous != theme) {
+            for (_, window) in self.windows() {
+                window.handle_theme_changed(theme);
+            }
+        }
+    }
+
+    /// Deliver a user event to the focused window, or to any window if none has had focus.
+    fn dispatch_user_event(&self, event: Box<dyn Any + Send>) {
+        match self.target_window() {
+            Some(window) => window.handle_user_event(event),
+            None => log::warn!("No window to deliver a user event to"),
+        }
+    }
+
+    /// The window for events that don't belong to one: the focused window, or any window
+    /// if none has focus.
+    fn target_window(&self) -> Option<Rc<Window>> {
+        let focused = borrow!(self.state)
+            .ok()
+            .and_then(|state| state.focused)
+            .and_then(|id| self.window(id));
+        focused.or_else(|| self.windows().into_iter().next().map(|(_, window)| window))
+    }
+
+    fn windows(&self) -> Vec<(WindowId, Rc<Window>)> {
+        match borrow!(self.state) {
+            Ok(state) => state
+                .windows
+                .iter()
+                .map(|(id, window)| (*id, window.clone()))
+                .collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// Sleep until the next event, the next timer, or `next_frame` if a window has
+    /// something to draw.
+    fn control_flow(&self, next_frame: Instant) -> ControlFlow {
+        let windows = self.windows();
+        let next_timer = windows
+            .iter()
+            .filter_map(|(_, window)| window.core.next_timeout())
+            .min();
+        let wake_at = if windows.iter().any(|(_, window)| window.needs_redraw()) {
+            Some(next_timer.map_or(next_frame, |timer| timer.min(next_frame)))
         } else {
-            Scale::default()
+            next_timer
         };
+        match wake_at {
+            Some(deadline) if deadline <= Instant::now() => ControlFlow::Poll,
+            Some(deadline) => ControlFlow::WaitUntil(deadline),
+            None => ControlFlow::Wait,
+        }
+    }
 
-        surface.canvas().scale((scale.x() as f32, scale.y() as f32));
-
-        let mut cursor_position = PhysicalPosition::new(0., 0.);
-        let mut last_ts = Instant::now();
-        let mut time = Duration::default();
-        let mut frames_cnt = 0;
-        let mut redraw_timestamp = Instant::now();
-        // scheduler chould wake up us later so we want to say to it to wake up us earlier
-        // We can even calculate it dynamicly as some average :)
-        let scheduler_lag = Duration::from_millis(1);
-        let frame_time = Duration::from_secs_f64(1. / TARGET_FPS as f64) - scheduler_lag;
-        event_loop.run(move |event, _, control_flow| {
-            *control_flow = ControlFlow::Poll;
-
-            let _size = gl_context.window().inner_size();
-            let _size = (_size.width as f32, _size.height as f32);
-            {
-                let main_window = self.window().unwrap();
-                main_window.run_idle();
-                let now = Instant::now();
-                main_window.run_timers(now);
-            }
-            match event {
-                Event::WindowEvent {
-                    event: WindowEvent::CloseRequested,
-                    ..
-                } => {
-                    *control_flow = ControlFlow::Exit;
-                }
-                Event::WindowEvent {
-                    event: WindowEvent::KeyboardInput { input, .. },
-                    ..
-                } => {
-                    let main_window = self.window().unwrap();
-                    main_window.handle_key_press(input);
+    /// The time between the starts of two frames.
+    ///
+    /// This is zero if the frame rate isn't capped.
+    fn frame_time(&self) -> Duration {
+        let target_fps = borrow!(self.state)
+            .ok()
+            .and_then(|state| state.target_fps.or(state.refresh_rate))
+            .unwrap_or(DEFAULT_TARGET_FPS);
+        if target_fps == 0 {
+            return Duration::default();
+        }
+        Duration::from_secs_f64(1. / target_fps as f64)
+            .checked_sub(SCHEDULER_LAG)
+            .unwrap_or_default()
+    }
+
+    fn update_frame_stats(&self, f: impl FnOnce(&mut FrameStats)) {
+        match borrow_mut!(self.state) {
+            Ok(mut state) => f(&mut state.frame_stats),
+            Err(e) => log::error!("Application::update_frame_stats - {}", e),
+        }
+    }
+
+    /// Pace the redraws to the monitor `window` is on.
+    ///
+    /// The active refresh rate isn't known, so this uses the fastest one the monitor
+    /// supports. That never paces slower than the display, at worst it draws frames
+    /// that aren't shown.
+    fn update_refresh_rate(&self, window: &glutin::window::Window) {
+        let refresh_rate = window
+            .current_monitor()
+            .and_then(|monitor| screen::max_refresh_rate(&monitor))
+            .map(u32::from);
+        match borrow_mut!(self.state) {
+            Ok(mut state) => state.refresh_rate = refresh_rate,
+            Err(e) => log::error!("Application::update_refresh_rate - {}", e),
+        }
+    }
+
+    fn update_monitors(&self, target: &EventLoopWindowTarget<UserEvent>) {
+        let monitors = screen::available_monitors(target);
+        match borrow_mut!(self.state) {
+            Ok(mut state) => state.monitors = monitors,
+            Err(e) => log::error!("Application::update_monitors - {}", e),
+        }
+    }
+
+    pub fn monitors(&self) -> Vec<Monitor> {
+        match borrow!(self.state) {
+            Ok(state) => state.monitors.clone(),
+            Err(e) => {
+                log::error!("Application::monitors - {}", e);
+                Vec::new()
+            }
+        }
+    }
+
+    pub fn primary_monitor(&self) -> Option<Monitor> {
+        self.monitors()
+            .into_iter()
+            .find(|monitor| monitor.is_primary())
+    }
+
+    pub(crate) fn user_event_sender(&self) -> UserEventSender {
+        UserEventSender(self.waker())
+    }
+
+    pub fn frame_stats(&self) -> FrameStats {
+        match borrow!(self.state) {
+            Ok(state) => state.frame_stats,
+            Err(e) => {
+                log::error!("Application::frame_stats - {}", e);
+                FrameStats::default()
+            }
+        }
+    }
+
+    /// Set whether the mouse moves a window gets between two frames are merged into one.
+    pub fn set_coalesce_mouse_moves(&self, coalesce: bool) {
+        match borrow_mut!(self.state) {
+            Ok(mut state) => state.coalesce_mouse_moves = coalesce,
+            Err(e) => log::error!("Application::set_coalesce_mouse_moves - {}", e),
+        }
+    }
+
+    fn coalesce_mouse_moves(&self) -> bool {
+        borrow!(self.state)
+            .map(|state| state.coalesce_mouse_moves)
+            .unwrap_or(true)
+    }
+
+    /// Whether the event loop can run on a thread other than the main one.
+    pub(crate) fn supports_any_thread() -> bool {
+        cfg!(any(
+            target_os = "linux",
+            target_os = "dragonfly",
+            target_os = "freebsd",
+            target_os = "netbsd",
+            target_os = "openbsd",
+        ))
+    }
+
+    /// Let the event loop run on the thread the application was created on, even if it
+    /// isn't the main one. Only call this if `supports_any_thread` returns true.
+    pub(crate) fn allow_any_thread(&self) {
+        match borrow_mut!(self.state) {
+            Ok(mut state) => state.any_thread = true,
+            Err(e) => log::error!("Application::allow_any_thread - {}", e),
+        }
+    }
+
+    fn new_event_loop(&self) -> EventLoop<UserEvent> {
+        let any_thread = borrow!(self.state)
+            .map(|state| state.any_thread)
+            .unwrap_or(false);
+        new_event_loop(any_thread)
+    }
+
+    /// Set the OpenGL and OpenGL ES versions to request for windows created from now on.
+    pub fn set_gl_versions(&self, opengl: (u8, u8), opengles: (u8, u8)) {
+        match borrow_mut!(self.state) {
+            Ok(mut state) => state.gl_versions = GlVersions { opengl, opengles },
+            Err(e) => log::error!("Application::set_gl_versions - {}", e),
+        }
+    }
+
+    fn gl_versions(&self) -> GlVersions {
+        borrow!(self.state)
+            .map(|state| state.gl_versions)
+            .unwrap_or_default()
+    }
+
+    /// Set the frame rate that redraws are paced to, 0 removes the cap.
+    pub fn set_target_fps(&self, fps: u32) {
+        match borrow_mut!(self.state) {
+            Ok(mut state) => state.target_fps = Some(fps),
+            Err(e) => log::error!("Application::set_target_fps - {}", e),
+        }
+    }
+
+    /// Register `hotkey` so that it delivers `token` even when the application isn't focused.
+    pub fn register_global_hotkey(&self, hotkey: HotKey, token: IdleToken) -> Result<(), Error> {
+        let waker = self.waker();
+        let mut state = borrow_mut!(self.state)?;
+        if state.global_hotkeys.is_none() {
+            state.global_hotkeys = Some(GlobalHotKeys::new(waker)?);
+        }
+        state
+            .global_hotkeys
+            .as_mut()
+            .unwrap()
+            .register(&hotkey, token)
+    }
+
+    pub fn unregister_global_hotkey(&self, token: IdleToken) -> Result<(), Error> {
+        match &mut borrow_mut!(self.state)?.global_hotkeys {
+            Some(hotkeys) => hotkeys.unregister(token),
+            None => Ok(()),
+        }
+    }
+
+    pub fn run(self, _handler: Option<Box<dyn AppHandler>>) {
+        if let Err(e) = self.run_inner() {
+            log::error!("{}", e);
+        }
+    }
+
+    pub fn run_inner(self) -> Result<(), Error> {
+        let event_loop = self.new_event_loop();
+        let mut loop_state = self.start_loop(&event_loop, false)?;
+        event_loop.run(move |event, target, control_flow| {
+            self.handle_event(event, target, &mut loop_state, control_flow);
+        });
+    }
+
+    /// Process the pending input, timers and idle callbacks once, without rendering.
+    ///
+    /// This is for hosts that own the main loop, they call this and [`render_now`] instead
+    /// of [`run`]. Returns `false` once all the windows are closed.
+    ///
+    /// [`render_now`]: Application::render_now
+    /// [`run`]: Application::run
+    pub fn pump_events(&self) -> bool {
+        let mut pumped = match borrow_mut!(self.pumped) {
+            Ok(pumped) => pumped,
+            Err(e) => {
+                log::error!("Application::pump_events - {}", e);
+                return false;
+            }
+        };
+        if pumped.is_none() {
+            let event_loop = self.new_event_loop();
+            match self.start_loop(&event_loop, true) {
+                Ok(loop_state) => {
+                    *pumped = Some(PumpedLoop {
+                        event_loop,
+                        loop_state,
+                    })
                 }
-                Event::WindowEvent {
-                    event: WindowEvent::Resized(physical_size),
-                    ..
-                } => {
-                    gl_context.resize(physical_size);
-                    // TODO something with these unwraps
-                    surface = create_surface(&gl_context, fb_info, &mut gr_context).unwrap();
-                    surface.canvas().scale((scale.x() as f32, scale.y() as f32));
-                    let main_window = self.window().unwrap();
-                    main_window.screen_size_changed(physical_size).unwrap();
+                Err(e) => {
+                    log::error!("Failed to start the event loop: {}", e);
+                    return false;
                 }
-                Event::WindowEvent {
-                    event: WindowEvent::CursorMoved { position, .. },
-                    ..
-                } => {
-                    cursor_position = position;
-                    let main_window = self.window().unwrap();
-                    main_window.handle_motion_notify(position);
+            }
+        }
+        let PumpedLoop {
+            event_loop,
+            loop_state,
+        } = pumped.as_mut().unwrap();
+        event_loop.run_return(|event, target, control_flow| {
+            let cleared = matches!(event, Event::RedrawEventsCleared);
+            self.handle_event(event, target, loop_state, control_flow);
+            // Return to the host after one pass instead of waiting for more events.
+            if cleared {
+                *control_flow = ControlFlow::Exit;
+            }
+        });
+        !loop_state.surfaces.is_empty()
+    }
+
+    /// Render and present every window that has damage or asked for an animation frame.
+    ///
+    /// This does nothing until [`pump_events`] has been called.
+    ///
+    /// [`pump_events`]: Application::pump_events
+    pub fn render_now(&self) {
+        let mut pumped = match borrow_mut!(self.pumped) {
+            Ok(pumped) => pumped,
+            Err(e) => {
+                log::error!("Application::render_now - {}", e);
+                return;
+            }
+        };
+        let PumpedLoop {
+            event_loop,
+            loop_state,
+        } = match pumped.as_mut() {
+            Some(pumped) => pumped,
+            None => return,
+        };
+        for (id, window) in self.windows() {
+            if window.needs_redraw() {
+                window.take_anim_frame_request();
+                self.render_window(id, event_loop, loop_state);
+            }
+        }
+    }
+
+    /// Set the loop up to deliver events to the windows added so far.
+    fn start_loop(
+        &self,
+        event_loop: &EventLoop<UserEvent>,
+        manual_present: bool,
+    ) -> Result<LoopState, Error> {
+        self.waker().set_proxy(event_loop.create_proxy());
+        self.update_monitors(event_loop);
+        let mut surfaces = HashMap::new();
+        self.create_pending_windows(event_loop, &mut surfaces)?;
+        Ok(LoopState {
+            surfaces,
+            manual_present,
+            last_ts: Instant::now(),
+            time: Duration::default(),
+            frames_cnt: 0,
+            redraw_timestamp: Instant::now(),
+        })
+    }
+
+    /// Handle one event of the loop, this is the body of both `run` and `pump_events`.
+    fn handle_event(
+        &self,
+        event: Event<'_, UserEvent>,
+        target: &EventLoopWindowTarget<UserEvent>,
+        loop_state: &mut LoopState,
+        control_flow: &mut ControlFlow,
+    ) {
+        if self.quitting() {
+            self.close_all_windows();
+        }
+        let surfaces = &mut loop_state.surfaces;
+        if let Err(e) = self.create_pending_windows(target, surfaces) {
+            log::error!("Failed to create window: {}", e);
+        }
+        for (id, window) in self.windows() {
+            window.run_idle();
+            let now = Instant::now();
+            window.core.run_timers(now);
+            if let Some(surface) = surfaces.get(&id) {
+                window.run_deferred(surface.window());
+            }
+        }
+        self.dispatch_menu_commands();
+        self.dispatch_global_hotkeys();
+        self.remove_closed_windows(surfaces);
+        if surfaces.is_empty() {
+            *control_flow = ControlFlow::Exit;
+            return;
+        }
+
+        match event {
+            Event::WindowEvent { window_id, event } => {
+                let surface = surfaces.get_mut(&window_id);
+                let (window, surface) = match (self.window(window_id), surface) {
+                    (Some(window), Some(surface)) => (window, surface),
+                    _ => return,
+                };
+                if !matches!(event, WindowEvent::CursorMoved { .. }) {
+                    // The pending move happened before this event.
+                    surface.flush_motion(&window);
                 }
-                Event::WindowEvent {
-                    event: WindowEvent::MouseInput { button, state, .. },
-                    ..
-                } => {
-                    let main_window = self.window().unwrap();
-                    match state {
+                match event {
+                    WindowEvent::CloseRequested => {
+                        window.close();
+                    }
+                    WindowEvent::ThemeChanged(theme) => self.set_theme(match theme {
+                        glutin::window::Theme::Light => Theme::Light,
+                        glutin::window::Theme::Dark => Theme::Dark,
+                    }),
+                    WindowEvent::KeyboardInput { input, .. } => {
+                        window.handle_key_press(input);
+                    }
+                    WindowEvent::Resized(physical_size) => {
+                        if let Err(e) = surface.resize(&window, physical_size) {
+                            log::error!("Failed to resize: {}", e);
+                        }
+                    }
+                    WindowEvent::Moved(position) => {
+                        self.update_refresh_rate(surface.window());
+                        window.handle_moved(position);
+                    }
+                    WindowEvent::ScaleFactorChanged {
+                        scale_factor,
+                        new_inner_size,
+                    } => {
+                        // Monitors were likely added, removed or reconfigured.
+                        self.update_monitors(target);
+                        self.update_refresh_rate(surface.window());
+                        if let Err(e) = surface.rescale(&window, scale_factor, *new_inner_size) {
+                            log::error!("Failed to change the scale: {}", e);
+                        }
+                    }
+                    WindowEvent::CursorMoved { position, .. } => {
+                        surface.cursor_position = position;
+                        // Drags get every position, so that their paths stay accurate.
+                        if self.coalesce_mouse_moves() && !window.has_buttons_down() {
+                            surface.pending_motion = Some(position);
+                        } else {
+                            window.handle_motion_notify(position);
+                        }
+                    }
+                    WindowEvent::CursorEntered { .. } => {
+                        window.handle_cursor_entered();
+                    }
+                    WindowEvent::CursorLeft { .. } => {
+                        window.handle_cursor_left();
+                    }
+                    WindowEvent::Focused(focused) => {
+                        if focused {
+                            if let Ok(mut state) = borrow_mut!(self.state) {
+                                state.focused = Some(window_id);
+                            }
+                        }
+                        window.handle_focus(focused);
+                        // Most platforms don't report theme changes, the user may
+                        // have switched while another application had focus.
+                        if focused {
+                            self.detect_theme_in_background();
+                        }
+                    }
+                    WindowEvent::ModifiersChanged(modifiers) => {
+                        window.handle_modifiers_changed(modifiers);
+                    }
+                    WindowEvent::MouseWheel { delta, .. } => {
+                        window.handle_mouse_wheel(delta);
+                    }
+                    WindowEvent::MouseInput { button, state, .. } => match state {
                         glutin::event::ElementState::Pressed => {
-                            main_window.handle_button_press(cursor_position, button);
+                            window.handle_button_press(surface.cursor_position, button);
                         }
                         glutin::event::ElementState::Released => {
-                            main_window.handle_button_release(cursor_position, button);
+                            window.handle_button_release(surface.cursor_position, button);
                         }
+                    },
+                    _ => {}
+                }
+            }
+            Event::RedrawRequested(window_id) => {
+                if loop_state.manual_present {
+                    // The host decides when to render, the next `render_now` repaints it.
+                    if let Some(window) = self.window(window_id) {
+                        window.invalidate();
+                    }
+                    return;
+                }
+                self.render_window(window_id, target, loop_state);
+            }
+            Event::DeviceEvent {
+                event: DeviceEvent::MouseMotion { delta },
+                ..
+            } => {
+                let focused = borrow!(self.state)
+                    .ok()
+                    .and_then(|state| state.focused)
+                    .and_then(|id| self.window(id));
+                if let Some(window) = focused {
+                    window.handle_raw_motion(delta);
+                }
+            }
+            // The idle queues were drained above, waking up was all this had to do.
+            Event::UserEvent(UserEvent::Wake) => {}
+            Event::UserEvent(UserEvent::User(event)) => self.dispatch_user_event(event),
+            Event::UserEvent(UserEvent::Theme(theme)) => {
+                if let Ok(mut state) = borrow_mut!(self.state) {
+                    state.detecting_theme = false;
+                }
+                self.set_theme(theme);
+            }
+            Event::MainEventsCleared => {
+                for (id, window) in self.windows() {
+                    if let Some(surface) = surfaces.get_mut(&id) {
+                        surface.flush_motion(&window);
                     }
                 }
-                Event::RedrawRequested(_) => {
-                    {
-                        // frame rate
-                        frames_cnt += 1;
-                        let duration = Instant::now() - last_ts;
-                        time += duration;
-                        last_ts = Instant::now();
-                        if time > Duration::from_secs(1) {
-                            log::info!("{}", frames_cnt);
-                            frames_cnt = 0;
-                            time = time.max(Duration::from_secs(1)) - Duration::from_secs(1);
+                if loop_state.manual_present {
+                    return;
+                }
+                let windows = self.windows();
+                if !windows.iter().any(|(_, window)| window.needs_redraw()) {
+                    return;
+                }
+                let frame_time = self.frame_time();
+                let since_last_redraw = Instant::now().duration_since(loop_state.redraw_timestamp);
+                let frame_due = since_last_redraw >= frame_time;
+                let mut requested = false;
+                for (id, window) in windows {
+                    if let Some(surface) = surfaces.get(&id) {
+                        // With vsync the buffer swap blocks until the display is ready,
+                        // which already paces the frames.
+                        if window.needs_redraw() && (frame_due || surface.vsync) {
+                            window.take_anim_frame_request();
+                            surface.window().request_redraw();
+                            requested = true;
                         }
                     }
-                    let surface_canvas = surface.canvas();
-                    let main_window = self.window().unwrap();
-                    main_window.render(&mut *surface_canvas).unwrap();
-                    surface_canvas.flush();
-                    gl_context.swap_buffers().unwrap();
-                    redraw_timestamp = Instant::now();
                 }
-                _ => {
-                    let since_last_redraw = Instant::now().duration_since(redraw_timestamp);
-                    if since_last_redraw > frame_time {
-                        gl_context.window().request_redraw();
-                    } else {
-                        let wait_time = frame_time - since_last_redraw;
-                        //*control_flow = ControlFlow::WaitUntil(Instant::now() + wait_time);
-                        // wayland..
-                        std::thread::sleep(wait_time);
+                if !requested && cfg!(feature = "sleep_frame_pacing") {
+                    if let Some(remaining) = frame_time.checked_sub(since_last_redraw) {
+                        std::thread::sleep(remaining);
                     }
                 }
             }
+            Event::RedrawEventsCleared => {
+                *control_flow = self.control_flow(loop_state.redraw_timestamp + self.frame_time());
+            }
+            _ => {}
+        }
+    }
+
+    /// Render and present the window `window_id`, keeping the frame statistics.
+    fn render_window(
+        &self,
+        window_id: WindowId,
+        target: &EventLoopWindowTarget<UserEvent>,
+        loop_state: &mut LoopState,
+    ) {
+        {
+            // frame rate
+            loop_state.frames_cnt += 1;
+            let duration = Instant::now() - loop_state.last_ts;
+            loop_state.time += duration;
+            loop_state.last_ts = Instant::now();
+            if loop_state.time > Duration::from_secs(1) {
+                let fps = loop_state.frames_cnt as f64 / loop_state.time.as_secs_f64();
+                log::trace!("{:.1} fps", fps);
+                self.update_frame_stats(|stats| stats.fps = fps);
+                loop_state.frames_cnt = 0;
+                loop_state.time =
+                    loop_state.time.max(Duration::from_secs(1)) - Duration::from_secs(1);
+            }
+        }
+        let surfaces = &mut loop_state.surfaces;
+        let render_start = Instant::now();
+        let result = match (self.window(window_id), surfaces.get_mut(&window_id)) {
+            (Some(window), Some(surface)) => surface.render(&window),
+            _ => Ok(()),
+        };
+        let render_time = render_start.elapsed();
+        let frame_time = self.frame_time();
+        self.update_frame_stats(|stats| {
+            stats.last_frame_time = render_time;
+            if frame_time > Duration::default() && render_time > frame_time {
+                stats.dropped += 1;
+            }
         });
+        match result {
+            Ok(()) => {}
+            Err(e) if is_context_lost(&e) => {
+                log::warn!("The GL context was lost, recreating the window");
+                if let Err(e) = self.recreate_surface(window_id, target, surfaces) {
+                    log::error!("Failed to recreate the window: {}", e);
+                }
+            }
+            Err(e) => log::error!("Failed to render: {}", e),
+        }
+        loop_state.redraw_timestamp = Instant::now();
     }
 
-    pub fn quit(&self) {}
+    pub fn quit(&self) {
+        match borrow_mut!(self.state) {
+            Ok(mut state) => state.quitting = true,
+            Err(e) => {
+                log::error!("Application::quit - {}", e);
+                return;
+            }
+        }
+        // The loop closes the windows and exits the next time it runs.
+        self.waker().wake();
+    }
 
     pub fn clipboard(&self) -> Clipboard {
-        Clipboard
+        match borrow_mut!(self.state) {
+            Ok(mut state) => state.clipboard.get_or_insert_with(Clipboard::new).clone(),
+            Err(e) => {
+                log::error!("Application::clipboard - {}", e);
+                Clipboard::default()
+            }
+        }
     }
 
     #[cfg(target_os = "macos")]
@@ -293,8 +1408,29 @@ impl Application {
     #[cfg(target_os = "macos")]
     pub fn hide_others(&self) {}
 
+    /// The system theme, it is only asked for the first time and then kept up to date by
+    /// the event loop.
+    pub fn system_theme(&self) -> Theme {
+        if let Ok(Some(theme)) = borrow!(self.state).map(|state| state.theme) {
+            return theme;
+        }
+        let theme = detect_theme();
+        match borrow_mut!(self.state) {
+            Ok(mut state) => state.theme = Some(theme),
+            Err(e) => log::error!("Application::system_theme - {}", e),
+        }
+        theme
+    }
+
     pub fn get_locale() -> String {
-        //TODO ahem
-        "en-US".into()
+        shared::system_locale()
+    }
+}
+
+/// Asks the system for its theme, this can take a while on Linux as it goes through D-Bus.
+fn detect_theme() -> Theme {
+    match dark_light::detect() {
+        dark_light::Mode::Dark => Theme::Dark,
+        dark_light::Mode::Light | dark_light::Mode::Default => Theme::Light,
     }
 }
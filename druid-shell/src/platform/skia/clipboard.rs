@@ -12,49 +12,134 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-//! blank implementation for clipboard
+//! Clipboard access, backed by `arboard`.
+
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
 
 use crate::clipboard::{ClipboardFormat, FormatId};
 
-/// The browser clipboard.
-#[derive(Debug, Clone, Default)]
-pub struct Clipboard;
+/// The system clipboard.
+///
+/// Only UTF-8 text is supported for now.
+#[derive(Clone, Default)]
+pub struct Clipboard(Option<Rc<RefCell<arboard::Clipboard>>>);
+
+impl fmt::Debug for Clipboard {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Clipboard").finish()
+    }
+}
 
 impl Clipboard {
+    pub(crate) fn new() -> Clipboard {
+        match arboard::Clipboard::new() {
+            Ok(clipboard) => Clipboard(Some(Rc::new(RefCell::new(clipboard)))),
+            Err(e) => {
+                log::error!("Failed to open the system clipboard: {}", e);
+                Clipboard(None)
+            }
+        }
+    }
+
+    fn with_clipboard<T>(
+        &self,
+        f: impl FnOnce(&mut arboard::Clipboard) -> Result<T, arboard::Error>,
+    ) -> Option<T> {
+        let clipboard = self.0.as_ref()?;
+        let mut clipboard = match clipboard.try_borrow_mut() {
+            Ok(clipboard) => clipboard,
+            Err(e) => {
+                log::error!("Failed to borrow the clipboard: {}", e);
+                return None;
+            }
+        };
+        match f(&mut clipboard) {
+            Ok(value) => Some(value),
+            // An empty clipboard is not worth logging.
+            Err(arboard::Error::ContentNotAvailable) => None,
+            Err(e) => {
+                log::warn!("Clipboard operation failed: {}", e);
+                None
+            }
+        }
+    }
+
     /// Put a string onto the system clipboard.
-    pub fn put_string(&mut self, _s: impl AsRef<str>) {
-        log::warn!("unimplemented");
+    pub fn put_string(&mut self, s: impl AsRef<str>) {
+        let s = s.as_ref().to_owned();
+        self.with_clipboard(|clipboard| clipboard.set_text(s));
     }
 
     /// Put multi-format data on the system clipboard.
-    pub fn put_formats(&mut self, _formats: &[ClipboardFormat]) {
-        log::warn!("unimplemented");
+    ///
+    /// Only the [`ClipboardFormat::TEXT`] format is supported, other formats are ignored.
+    pub fn put_formats(&mut self, formats: &[ClipboardFormat]) {
+        for format in formats {
+            if format.identifier == ClipboardFormat::TEXT {
+                match std::str::from_utf8(&format.data) {
+                    Ok(s) => self.put_string(s),
+                    Err(e) => log::warn!("Clipboard text is not valid UTF-8: {}", e),
+                }
+            } else {
+                log::warn!("Unsupported clipboard format {}", format.identifier);
+            }
+        }
     }
 
     /// Get a string from the system clipboard, if one is available.
     pub fn get_string(&self) -> Option<String> {
-        log::warn!("unimplemented");
-        None
+        self.with_clipboard(|clipboard| clipboard.get_text())
+    }
+
+    /// Put a string into the primary selection, which is pasted with a middle click.
+    #[cfg(target_os = "linux")]
+    pub fn set_primary_selection(&mut self, s: impl AsRef<str>) {
+        use arboard::{ClipboardExtLinux, LinuxClipboardKind};
+
+        let s = s.as_ref().to_owned();
+        self.with_clipboard(|clipboard| {
+            clipboard.set_text_with_clipboard(s, LinuxClipboardKind::Primary)
+        });
+    }
+
+    /// Get the primary selection, if there is one.
+    #[cfg(target_os = "linux")]
+    pub fn get_primary_selection(&self) -> Option<String> {
+        use arboard::{ClipboardExtLinux, LinuxClipboardKind};
+
+        self.with_clipboard(|clipboard| {
+            clipboard.get_text_with_clipboard(LinuxClipboardKind::Primary)
+        })
     }
 
     /// Given a list of supported clipboard types, returns the supported type which has
     /// highest priority on the system clipboard, or `None` if no types are supported.
-    pub fn preferred_format(&self, _formats: &[FormatId]) -> Option<FormatId> {
-        log::warn!("unimplemented");
-        None
+    pub fn preferred_format(&self, formats: &[FormatId]) -> Option<FormatId> {
+        if formats.contains(&ClipboardFormat::TEXT) && self.get_string().is_some() {
+            Some(ClipboardFormat::TEXT)
+        } else {
+            None
+        }
     }
 
     /// Return data in a given format, if available.
     ///
     /// It is recommended that the `fmt` argument be a format returned by
     /// [`Clipboard::preferred_format`]
-    pub fn get_format(&self, _format: FormatId) -> Option<Vec<u8>> {
-        log::warn!("unimplemented");
-        None
+    pub fn get_format(&self, format: FormatId) -> Option<Vec<u8>> {
+        if format == ClipboardFormat::TEXT {
+            self.get_string().map(String::into_bytes)
+        } else {
+            None
+        }
     }
 
     pub fn available_type_names(&self) -> Vec<String> {
-        log::warn!("unimplemented");
-        Vec::new()
+        match self.get_string() {
+            Some(_) => vec![ClipboardFormat::TEXT.to_string()],
+            None => Vec::new(),
+        }
     }
 }
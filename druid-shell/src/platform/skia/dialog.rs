@@ -0,0 +1,60 @@
+// Copyright 2020 The Druid Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! File open/save dialogs, through `rfd`.
+
+use std::path::PathBuf;
+
+use crate::dialog::{FileDialogOptions, FileDialogType, FileSpec};
+
+/// Show a modal file dialog, returning the selected paths.
+///
+/// Returns `None` if the dialog was cancelled.
+pub(crate) fn get_file_dialog_paths(
+    ty: FileDialogType,
+    options: FileDialogOptions,
+) -> Option<Vec<PathBuf>> {
+    let mut dialog = rfd::FileDialog::new();
+
+    // rfd selects the first filter, so the default type goes first.
+    let mut filters: Vec<FileSpec> = options.default_type.into_iter().collect();
+    for spec in options.allowed_types.unwrap_or_default() {
+        if !filters.contains(&spec) {
+            filters.push(spec);
+        }
+    }
+    for spec in filters {
+        dialog = dialog.add_filter(spec.name, spec.extensions);
+    }
+
+    if let Some(dir) = &options.starting_directory {
+        dialog = dialog.set_directory(dir);
+    }
+    if let Some(name) = &options.default_name {
+        dialog = dialog.set_file_name(name);
+    }
+    if let Some(title) = &options.title {
+        dialog = dialog.set_title(title);
+    }
+
+    match ty {
+        // rfd 0.6 can only pick a single folder, even with `multi_selection`.
+        FileDialogType::Open if options.select_directories => {
+            dialog.pick_folder().map(|path| vec![path])
+        }
+        FileDialogType::Open if options.multi_selection => dialog.pick_files(),
+        FileDialogType::Open => dialog.pick_file().map(|path| vec![path]),
+        FileDialogType::Save => dialog.save_file().map(|path| vec![path]),
+    }
+}
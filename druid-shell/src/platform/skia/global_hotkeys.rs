@@ -0,0 +1,234 @@
+// Copyright 2020 The Druid Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Hotkeys that fire while the application doesn't have focus, through `global-hotkey`.
+
+use std::collections::HashMap;
+use std::sync::{Mutex, Once};
+
+use anyhow::{anyhow, Error};
+use global_hotkey::hotkey::{Code, HotKey as NativeHotKey, Modifiers as NativeModifiers};
+use global_hotkey::{GlobalHotKeyEvent, GlobalHotKeyManager, HotKeyState};
+use lazy_static::lazy_static;
+
+use super::application::LoopWaker;
+use crate::hotkey::HotKey;
+use crate::keyboard::{KbKey, Modifiers};
+use crate::window::IdleToken;
+
+lazy_static! {
+    /// The ids of the hotkeys pressed since they were last taken, and the loop to wake
+    /// when one is pressed.
+    static ref PRESSED: Mutex<(Vec<u32>, LoopWaker)> = Default::default();
+}
+
+/// The global hotkeys registered by the application.
+pub(crate) struct GlobalHotKeys {
+    manager: GlobalHotKeyManager,
+    /// The registered hotkeys, by the token they deliver.
+    hotkeys: HashMap<IdleToken, NativeHotKey>,
+}
+
+impl GlobalHotKeys {
+    pub(crate) fn new(waker: LoopWaker) -> Result<GlobalHotKeys, Error> {
+        static INSTALL_HANDLER: Once = Once::new();
+        INSTALL_HANDLER.call_once(|| {
+            GlobalHotKeyEvent::set_event_handler(Some(|event: GlobalHotKeyEvent| {
+                if event.state() == HotKeyState::Pressed {
+                    let mut pressed = PRESSED.lock().unwrap();
+                    pressed.0.push(event.id());
+                    pressed.1.wake();
+                }
+            }));
+        });
+        PRESSED.lock().unwrap().1 = waker;
+
+        Ok(GlobalHotKeys {
+            manager: GlobalHotKeyManager::new()?,
+            hotkeys: HashMap::new(),
+        })
+    }
+
+    /// Register `hotkey`, replacing the hotkey previously registered for `token`.
+    pub(crate) fn register(&mut self, hotkey: &HotKey, token: IdleToken) -> Result<(), Error> {
+        let hotkey = convert_hotkey(hotkey)
+            .ok_or_else(|| anyhow!("{:?} can't be registered as a global hotkey", hotkey))?;
+        self.unregister(token)?;
+        self.manager.register(hotkey)?;
+        self.hotkeys.insert(token, hotkey);
+        Ok(())
+    }
+
+    /// Unregister the hotkey that delivers `token`, if there is one.
+    pub(crate) fn unregister(&mut self, token: IdleToken) -> Result<(), Error> {
+        if let Some(hotkey) = self.hotkeys.remove(&token) {
+            self.manager.unregister(hotkey)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the tokens of the hotkeys pressed since the last call.
+    pub(crate) fn take_pressed(&self) -> Vec<IdleToken> {
+        let pressed = std::mem::take(&mut PRESSED.lock().unwrap().0);
+        pressed
+            .into_iter()
+            .filter_map(|id| {
+                self.hotkeys
+                    .iter()
+                    .find(|(_, hotkey)| hotkey.id() == id)
+                    .map(|(token, _)| *token)
+            })
+            .collect()
+    }
+}
+
+fn convert_hotkey(hotkey: &HotKey) -> Option<NativeHotKey> {
+    let mods: Modifiers = hotkey.mods.into();
+    let mut native_mods = NativeModifiers::empty();
+    for (modifier, native) in &[
+        (Modifiers::SHIFT, NativeModifiers::SHIFT),
+        (Modifiers::CONTROL, NativeModifiers::CONTROL),
+        (Modifiers::ALT, NativeModifiers::ALT),
+        (Modifiers::META, NativeModifiers::SUPER),
+    ] {
+        if mods.contains(*modifier) {
+            native_mods |= *native;
+        }
+    }
+    Some(NativeHotKey::new(
+        Some(native_mods),
+        key_to_code(&hotkey.key)?,
+    ))
+}
+
+/// The physical key that types `key` on a US layout, global hotkeys are bound to physical keys.
+fn key_to_code(key: &KbKey) -> Option<Code> {
+    let code = match key {
+        KbKey::Character(text) => {
+            let mut chars = text.chars();
+            let c = chars.next()?.to_ascii_lowercase();
+            if chars.next().is_some() {
+                return None;
+            }
+            match c {
+                'a' => Code::KeyA,
+                'b' => Code::KeyB,
+                'c' => Code::KeyC,
+                'd' => Code::KeyD,
+                'e' => Code::KeyE,
+                'f' => Code::KeyF,
+                'g' => Code::KeyG,
+                'h' => Code::KeyH,
+                'i' => Code::KeyI,
+                'j' => Code::KeyJ,
+                'k' => Code::KeyK,
+                'l' => Code::KeyL,
+                'm' => Code::KeyM,
+                'n' => Code::KeyN,
+                'o' => Code::KeyO,
+                'p' => Code::KeyP,
+                'q' => Code::KeyQ,
+                'r' => Code::KeyR,
+                's' => Code::KeyS,
+                't' => Code::KeyT,
+                'u' => Code::KeyU,
+                'v' => Code::KeyV,
+                'w' => Code::KeyW,
+                'x' => Code::KeyX,
+                'y' => Code::KeyY,
+                'z' => Code::KeyZ,
+                '0' => Code::Digit0,
+                '1' => Code::Digit1,
+                '2' => Code::Digit2,
+                '3' => Code::Digit3,
+                '4' => Code::Digit4,
+                '5' => Code::Digit5,
+                '6' => Code::Digit6,
+                '7' => Code::Digit7,
+                '8' => Code::Digit8,
+                '9' => Code::Digit9,
+                ' ' => Code::Space,
+                '-' => Code::Minus,
+                '=' => Code::Equal,
+                '[' => Code::BracketLeft,
+                ']' => Code::BracketRight,
+                '\\' => Code::Backslash,
+                ';' => Code::Semicolon,
+                '\'' => Code::Quote,
+                ',' => Code::Comma,
+                '.' => Code::Period,
+                '/' => Code::Slash,
+                '`' => Code::Backquote,
+                _ => return None,
+            }
+        }
+        KbKey::Enter => Code::Enter,
+        KbKey::Tab => Code::Tab,
+        KbKey::Backspace => Code::Backspace,
+        KbKey::Escape => Code::Escape,
+        KbKey::Delete => Code::Delete,
+        KbKey::Insert => Code::Insert,
+        KbKey::Home => Code::Home,
+        KbKey::End => Code::End,
+        KbKey::PageUp => Code::PageUp,
+        KbKey::PageDown => Code::PageDown,
+        KbKey::ArrowUp => Code::ArrowUp,
+        KbKey::ArrowDown => Code::ArrowDown,
+        KbKey::ArrowLeft => Code::ArrowLeft,
+        KbKey::ArrowRight => Code::ArrowRight,
+        KbKey::PrintScreen => Code::PrintScreen,
+        KbKey::Pause => Code::Pause,
+        KbKey::MediaPlayPause => Code::MediaPlayPause,
+        KbKey::MediaStop => Code::MediaStop,
+        KbKey::MediaTrackNext => Code::MediaTrackNext,
+        KbKey::MediaTrackPrevious => Code::MediaTrackPrevious,
+        KbKey::AudioVolumeUp => Code::AudioVolumeUp,
+        KbKey::AudioVolumeDown => Code::AudioVolumeDown,
+        KbKey::AudioVolumeMute => Code::AudioVolumeMute,
+        KbKey::F1 => Code::F1,
+        KbKey::F2 => Code::F2,
+        KbKey::F3 => Code::F3,
+        KbKey::F4 => Code::F4,
+        KbKey::F5 => Code::F5,
+        KbKey::F6 => Code::F6,
+        KbKey::F7 => Code::F7,
+        KbKey::F8 => Code::F8,
+        KbKey::F9 => Code::F9,
+        KbKey::F10 => Code::F10,
+        KbKey::F11 => Code::F11,
+        KbKey::F12 => Code::F12,
+        _ => return None,
+    };
+    Some(code)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::hotkey::{RawMods, SysMods};
+
+    #[test]
+    fn converts_hotkeys() {
+        let hotkey = convert_hotkey(&HotKey::new(SysMods::CmdShift, "P")).unwrap();
+        assert_eq!(hotkey.key, Code::KeyP);
+        assert!(hotkey.mods.contains(NativeModifiers::SHIFT));
+
+        let hotkey = convert_hotkey(&HotKey::new(RawMods::None, KbKey::MediaPlayPause)).unwrap();
+        assert_eq!(hotkey.key, Code::MediaPlayPause);
+        assert!(hotkey.mods.is_empty());
+
+        assert!(convert_hotkey(&HotKey::new(None, "ab")).is_none());
+    }
+}
@@ -13,44 +13,326 @@
 // limitations under the License.
 
 //! Safe wrapper for menus.
+//!
+//! Menus are recorded as a description and only turned into native menus when they are
+//! shown, through `muda`. glutin windows are not GTK windows, so native menus are only
+//! available on macOS for now.
+
+#[cfg(target_os = "macos")]
+use std::collections::HashMap;
 
 use crate::hotkey::HotKey;
+use crate::keyboard::KeyEvent;
+use crate::kurbo::Point;
 
 /// A menu object, which can be either a top-level menubar or a
 /// submenu.
-pub struct Menu;
+#[derive(Default)]
+pub struct Menu {
+    items: Vec<MenuItem>,
+    /// The native menu, kept alive for as long as it is shown.
+    #[cfg(target_os = "macos")]
+    native: Option<NativeMenu>,
+}
 
-impl Drop for Menu {
-    fn drop(&mut self) {
-        // TODO
-    }
+#[cfg(target_os = "macos")]
+struct NativeMenu {
+    _menu: muda::Menu,
+    /// The native items, by id, so they can be updated while the menu is shown.
+    handles: HashMap<u32, NativeItem>,
+    menu_bar: bool,
+}
+
+#[cfg(target_os = "macos")]
+enum NativeItem {
+    Normal(muda::MenuItem),
+    Check(muda::CheckMenuItem),
+}
+
+enum MenuItem {
+    Item {
+        id: u32,
+        text: String,
+        key: Option<HotKey>,
+        enabled: bool,
+        selected: bool,
+    },
+    Dropdown {
+        menu: Menu,
+        text: String,
+        enabled: bool,
+    },
+    Separator,
 }
 
 impl Menu {
     pub fn new() -> Menu {
-        Menu
+        Menu::default()
     }
 
     pub fn new_for_popup() -> Menu {
-        Menu
+        Menu::default()
     }
 
-    pub fn add_dropdown(&mut self, _menu: Menu, _text: &str, _enabled: bool) {
-        log::warn!("unimplemented");
+    pub fn add_dropdown(&mut self, menu: Menu, text: &str, enabled: bool) {
+        self.items.push(MenuItem::Dropdown {
+            menu,
+            text: text.to_owned(),
+            enabled,
+        });
     }
 
     pub fn add_item(
         &mut self,
-        _id: u32,
-        _text: &str,
-        _key: Option<&HotKey>,
-        _enabled: bool,
-        _selected: bool,
+        id: u32,
+        text: &str,
+        key: Option<&HotKey>,
+        enabled: bool,
+        selected: bool,
     ) {
-        log::warn!("unimplemented");
+        self.items.push(MenuItem::Item {
+            id,
+            text: text.to_owned(),
+            key: key.cloned(),
+            enabled,
+            selected,
+        });
     }
 
     pub fn add_separator(&mut self) {
-        log::warn!("unimplemented");
+        self.items.push(MenuItem::Separator);
+    }
+
+    /// Returns `true` if this menu, or one of its submenus, has an item with this id.
+    pub(crate) fn contains(&self, id: u32) -> bool {
+        self.items.iter().any(|item| match item {
+            MenuItem::Item { id: item_id, .. } => *item_id == id,
+            MenuItem::Dropdown { menu, .. } => menu.contains(id),
+            MenuItem::Separator => false,
+        })
+    }
+
+    /// Returns the id of the enabled item whose hotkey matches `event`, if any.
+    ///
+    /// Items in disabled submenus are skipped.
+    pub(crate) fn command_for_key(&self, event: &KeyEvent) -> Option<u32> {
+        self.items.iter().find_map(|item| match item {
+            MenuItem::Item {
+                id,
+                key: Some(key),
+                enabled: true,
+                ..
+            } if key.matches(event) => Some(*id),
+            MenuItem::Dropdown {
+                menu,
+                enabled: true,
+                ..
+            } => menu.command_for_key(event),
+            _ => None,
+        })
+    }
+
+    /// Enable or disable the item `id`, updating the native menu if it is shown.
+    ///
+    /// Returns `false` if this menu has no item with this id.
+    pub(crate) fn set_item_enabled(&mut self, id: u32, enabled: bool) -> bool {
+        let found = self.update_item(id, &mut |item_enabled, _| *item_enabled = enabled);
+        if found {
+            self.sync_native(id);
+        }
+        found
+    }
+
+    /// Check or uncheck the item `id`, updating the native menu if it is shown.
+    ///
+    /// Returns `false` if this menu has no item with this id.
+    pub(crate) fn set_item_checked(&mut self, id: u32, checked: bool) -> bool {
+        let found = self.update_item(id, &mut |_, selected| *selected = checked);
+        if found {
+            self.sync_native(id);
+        }
+        found
+    }
+
+    /// Apply `f` to the enabled and selected state of the item `id`, in this menu or its submenus.
+    fn update_item(&mut self, id: u32, f: &mut dyn FnMut(&mut bool, &mut bool)) -> bool {
+        self.items.iter_mut().any(|item| match item {
+            MenuItem::Item {
+                id: item_id,
+                enabled,
+                selected,
+                ..
+            } if *item_id == id => {
+                f(enabled, selected);
+                true
+            }
+            MenuItem::Dropdown { menu, .. } => menu.update_item(id, f),
+            _ => false,
+        })
+    }
+
+    /// Returns the enabled and selected state of the item `id`.
+    #[cfg(target_os = "macos")]
+    fn item_state(&self, id: u32) -> Option<(bool, bool)> {
+        self.items.iter().find_map(|item| match item {
+            MenuItem::Item {
+                id: item_id,
+                enabled,
+                selected,
+                ..
+            } if *item_id == id => Some((*enabled, *selected)),
+            MenuItem::Dropdown { menu, .. } => menu.item_state(id),
+            _ => None,
+        })
+    }
+}
+
+#[cfg(target_os = "macos")]
+impl Menu {
+    /// Show this menu as the menu bar of `window`.
+    pub(crate) fn show_for_window(&mut self, _window: &glutin::window::Window) {
+        // The menu bar belongs to the application on macOS.
+        self.install_menu_bar();
     }
+
+    fn install_menu_bar(&mut self) {
+        let (menu, handles) = self.to_native();
+        menu.init_for_nsapp();
+        self.native = Some(NativeMenu {
+            _menu: menu,
+            handles,
+            menu_bar: true,
+        });
+    }
+
+    /// Pop this menu up at `pos`, in display points relative to the window.
+    pub(crate) fn show_context_menu(&mut self, window: &glutin::window::Window, pos: Point) {
+        use glutin::platform::macos::WindowExtMacOS;
+        use muda::ContextMenu;
+
+        let (menu, handles) = self.to_native();
+        let pos = muda::LogicalPosition::new(pos.x, pos.y);
+        menu.show_context_menu_for_nsview(window.ns_view() as _, Some(pos.into()));
+        self.native = Some(NativeMenu {
+            _menu: menu,
+            handles,
+            menu_bar: false,
+        });
+    }
+
+    /// Bring the native item `id` in line with its description.
+    fn sync_native(&mut self, id: u32) {
+        let (enabled, selected) = match self.item_state(id) {
+            Some(state) => state,
+            None => return,
+        };
+        let native = match &self.native {
+            Some(native) => native,
+            None => return,
+        };
+        let rebuild = match native.handles.get(&id) {
+            Some(NativeItem::Check(item)) => {
+                item.set_enabled(enabled);
+                item.set_checked(selected);
+                false
+            }
+            Some(NativeItem::Normal(item)) if !selected => {
+                item.set_enabled(enabled);
+                false
+            }
+            // Plain items can't show a check mark, so the menu bar has to be rebuilt.
+            // Context menus are closed by the time they can be updated.
+            Some(NativeItem::Normal(_)) => native.menu_bar,
+            None => false,
+        };
+        if rebuild {
+            self.install_menu_bar();
+        }
+    }
+
+    fn to_native(&self) -> (muda::Menu, HashMap<u32, NativeItem>) {
+        let menu = muda::Menu::new();
+        let mut handles = HashMap::new();
+        let items = self.native_items(&mut handles);
+        let items: Vec<&dyn muda::IsMenuItem> = items.iter().map(|item| item.as_ref()).collect();
+        if let Err(e) = menu.append_items(&items) {
+            log::error!("Failed to build the native menu: {}", e);
+        }
+        (menu, handles)
+    }
+
+    fn native_items(
+        &self,
+        handles: &mut HashMap<u32, NativeItem>,
+    ) -> Vec<Box<dyn muda::IsMenuItem>> {
+        let mut items: Vec<Box<dyn muda::IsMenuItem>> = Vec::with_capacity(self.items.len());
+        for item in &self.items {
+            match item {
+                MenuItem::Item {
+                    id,
+                    text,
+                    enabled,
+                    selected,
+                    ..
+                } => {
+                    let native_id = id.to_string();
+                    if *selected {
+                        let item =
+                            muda::CheckMenuItem::with_id(native_id, text, *enabled, true, None);
+                        handles.insert(*id, NativeItem::Check(item.clone()));
+                        items.push(Box::new(item));
+                    } else {
+                        let item = muda::MenuItem::with_id(native_id, text, *enabled, None);
+                        handles.insert(*id, NativeItem::Normal(item.clone()));
+                        items.push(Box::new(item));
+                    }
+                }
+                MenuItem::Dropdown {
+                    menu,
+                    text,
+                    enabled,
+                } => {
+                    let children = menu.native_items(handles);
+                    let children: Vec<&dyn muda::IsMenuItem> =
+                        children.iter().map(|item| item.as_ref()).collect();
+                    match muda::Submenu::with_items(text, *enabled, &children) {
+                        Ok(submenu) => items.push(Box::new(submenu)),
+                        Err(e) => log::error!("Failed to build submenu {:?}: {}", text, e),
+                    }
+                }
+                MenuItem::Separator => items.push(Box::new(muda::PredefinedMenuItem::separator())),
+            }
+        }
+        items
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+impl Menu {
+    fn sync_native(&mut self, _id: u32) {}
+
+    /// Show this menu as the menu bar of `window`.
+    pub(crate) fn show_for_window(&mut self, _window: &glutin::window::Window) {
+        log::warn!("Menu bars are not supported on this platform");
+    }
+
+    /// Pop this menu up at `pos`, in display points relative to the window.
+    pub(crate) fn show_context_menu(&mut self, _window: &glutin::window::Window, _pos: Point) {
+        log::warn!("Context menus are not supported on this platform");
+    }
+}
+
+/// Returns the ids of the menu items that were activated since the last call.
+#[cfg(target_os = "macos")]
+pub(crate) fn take_activated_items() -> Vec<u32> {
+    muda::MenuEvent::receiver()
+        .try_iter()
+        .filter_map(|event| event.id.0.parse().ok())
+        .collect()
+}
+
+/// Returns the ids of the menu items that were activated since the last call.
+#[cfg(not(target_os = "macos"))]
+pub(crate) fn take_activated_items() -> Vec<u32> {
+    Vec::new()
 }
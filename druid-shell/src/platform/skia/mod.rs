@@ -14,9 +14,11 @@
 
 pub mod application;
 pub mod clipboard;
+mod dialog;
 pub mod error;
+mod global_hotkeys;
 pub mod keycodes;
 pub mod menu;
 pub mod screen;
-pub mod util;
+mod software;
 pub mod window;
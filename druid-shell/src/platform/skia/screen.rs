@@ -12,11 +12,59 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-//! Monitor and Screen information ignored for now. TODO
+//! Monitor information, from the snapshot the application takes while its event loop runs.
 
+use glutin::event_loop::EventLoopWindowTarget;
+use glutin::monitor::MonitorHandle;
+
+use super::application::UserEvent;
+use crate::kurbo::{Point, Rect, Size};
+use crate::scale::Scale;
 use crate::screen::Monitor;
+use crate::Application;
 
 pub(crate) fn get_monitors() -> Vec<Monitor> {
-    log::warn!("Screen::get_monitors() is not implemented for web.");
-    Vec::new()
+    match Application::try_global() {
+        Some(app) => app.platform_app.monitors(),
+        None => Vec::new(),
+    }
+}
+
+/// The monitors the event loop knows about.
+pub(crate) fn available_monitors(target: &EventLoopWindowTarget<UserEvent>) -> Vec<Monitor> {
+    let primary = target.primary_monitor();
+    target
+        .available_monitors()
+        .map(|monitor| {
+            let is_primary = primary.as_ref() == Some(&monitor);
+            convert_monitor(&monitor, is_primary)
+        })
+        .collect()
+}
+
+fn convert_monitor(monitor: &MonitorHandle, primary: bool) -> Monitor {
+    let position = monitor.position();
+    let size = monitor.size();
+    let rect = Rect::from_origin_size(
+        Point::new(position.x as f64, position.y as f64),
+        Size::new(size.width as f64, size.height as f64),
+    );
+    let scale = Scale::new(monitor.scale_factor(), monitor.scale_factor());
+    // winit doesn't know about panels and docks, so the work area is the whole monitor.
+    // It doesn't report the active video mode either, so the refresh rate is unknown.
+    Monitor::new(primary, rect, rect).with_details(monitor.name(), scale, None)
+}
+
+/// The fastest refresh rate in Hz that `monitor` supports at its current resolution.
+///
+/// winit only lists the video modes, not the active one, so this is an upper bound
+/// of the rate the monitor is running at.
+pub(crate) fn max_refresh_rate(monitor: &MonitorHandle) -> Option<u16> {
+    let size = monitor.size();
+    monitor
+        .video_modes()
+        .filter(|mode| mode.size() == size)
+        .map(|mode| mode.refresh_rate())
+        .filter(|&rate| rate > 0)
+        .max()
 }
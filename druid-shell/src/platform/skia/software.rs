@@ -0,0 +1,172 @@
+// Copyright 2020 The Druid Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Presenting CPU rendered frames, for when there is no usable GL.
+//!
+//! This uses `XPutImage`, so it is only available for X11 windows.
+
+use anyhow::{anyhow, Error};
+use skia_safe::{AlphaType, ColorType, ImageInfo, Surface};
+
+/// Copies the pixels of a raster surface into a window.
+pub(crate) struct Presenter {
+    #[cfg(all(target_os = "linux", feature = "winit_x11"))]
+    x11: x11::Presenter,
+    /// The frame in the layout the window expects, reused between frames.
+    pixels: Vec<u8>,
+}
+
+impl Presenter {
+    pub(crate) fn new(window: &glutin::window::Window) -> Result<Presenter, Error> {
+        #[cfg(all(target_os = "linux", feature = "winit_x11"))]
+        {
+            Ok(Presenter {
+                x11: x11::Presenter::new(window)?,
+                pixels: Vec::new(),
+            })
+        }
+        #[cfg(not(all(target_os = "linux", feature = "winit_x11")))]
+        {
+            let _ = window;
+            Err(anyhow!("software rendering is only supported on X11"))
+        }
+    }
+
+    /// Show the contents of `surface` in the window.
+    pub(crate) fn present(&mut self, surface: &mut Surface) -> Result<(), Error> {
+        let (width, height) = (surface.width(), surface.height());
+        // X servers on little endian machines store 24 bit colors as BGRX, and 32 bit
+        // (ARGB) windows as premultiplied BGRA.
+        let info = ImageInfo::new(
+            (width, height),
+            ColorType::BGRA8888,
+            AlphaType::Premul,
+            None,
+        );
+        let row_bytes = width as usize * 4;
+        self.pixels.resize(row_bytes * height as usize, 0);
+        if !surface.read_pixels(&info, &mut self.pixels, row_bytes, (0, 0)) {
+            return Err(anyhow!("failed to read the surface pixels"));
+        }
+        #[cfg(all(target_os = "linux", feature = "winit_x11"))]
+        self.x11.put_image(&mut self.pixels, width, height)?;
+        Ok(())
+    }
+}
+
+#[cfg(all(target_os = "linux", feature = "winit_x11"))]
+mod x11 {
+    use std::mem::MaybeUninit;
+    use std::os::raw::c_char;
+    use std::ptr;
+
+    use anyhow::{anyhow, Error};
+    use glutin::platform::unix::WindowExtUnix;
+    use x11_dl::xlib;
+
+    pub(super) struct Presenter {
+        xlib: xlib::Xlib,
+        display: *mut xlib::Display,
+        window: xlib::Window,
+        gc: xlib::GC,
+        /// The visual of the window, it has an alpha channel when `depth` is 32.
+        visual: *mut xlib::Visual,
+        depth: i32,
+    }
+
+    impl Presenter {
+        pub(super) fn new(window: &glutin::window::Window) -> Result<Presenter, Error> {
+            let (display, window) = match (window.xlib_display(), window.xlib_window()) {
+                (Some(display), Some(window)) => (display as *mut xlib::Display, window),
+                _ => return Err(anyhow!("software rendering is only supported on X11")),
+            };
+            let xlib = xlib::Xlib::open()?;
+            let attributes = unsafe {
+                let mut attributes = MaybeUninit::<xlib::XWindowAttributes>::uninit();
+                if (xlib.XGetWindowAttributes)(display, window, attributes.as_mut_ptr()) == 0 {
+                    return Err(anyhow!("failed to get the window attributes"));
+                }
+                attributes.assume_init()
+            };
+            if attributes.depth != 24 && attributes.depth != 32 {
+                return Err(anyhow!(
+                    "software rendering needs a 24 or 32 bit window, not {} bit",
+                    attributes.depth
+                ));
+            }
+            let gc = unsafe { (xlib.XCreateGC)(display, window, 0, ptr::null_mut()) };
+            if gc.is_null() {
+                return Err(anyhow!("failed to create a graphics context"));
+            }
+            Ok(Presenter {
+                xlib,
+                display,
+                window,
+                gc,
+                visual: attributes.visual,
+                depth: attributes.depth,
+            })
+        }
+
+        /// Copy BGRX, or BGRA for 32 bit windows, `pixels` of a `width` x `height` image
+        /// into the window.
+        pub(super) fn put_image(
+            &self,
+            pixels: &mut [u8],
+            width: i32,
+            height: i32,
+        ) -> Result<(), Error> {
+            unsafe {
+                let image = (self.xlib.XCreateImage)(
+                    self.display,
+                    self.visual,
+                    self.depth as u32,
+                    xlib::ZPixmap,
+                    0,
+                    pixels.as_mut_ptr() as *mut c_char,
+                    width as u32,
+                    height as u32,
+                    32,
+                    width * 4,
+                );
+                if image.is_null() {
+                    return Err(anyhow!("failed to create an image"));
+                }
+                (self.xlib.XPutImage)(
+                    self.display,
+                    self.window,
+                    self.gc,
+                    image,
+                    0,
+                    0,
+                    0,
+                    0,
+                    width as u32,
+                    height as u32,
+                );
+                // The pixels are borrowed, `XDestroyImage` would free them.
+                (*image).data = ptr::null_mut();
+                (self.xlib.XDestroyImage)(image);
+                (self.xlib.XFlush)(self.display);
+            }
+            Ok(())
+        }
+    }
+
+    impl Drop for Presenter {
+        fn drop(&mut self) {
+            unsafe { (self.xlib.XFreeGC)(self.display, self.gc) };
+        }
+    }
+}
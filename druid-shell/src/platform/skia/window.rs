@@ -16,34 +16,44 @@
 
 use std::any::Any;
 use std::cell::{Cell, RefCell};
-use std::collections::BinaryHeap;
+use std::collections::HashSet;
 use std::ffi::OsString;
-use std::panic::Location;
+use std::path::PathBuf;
 use std::rc::{Rc, Weak};
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
 
 use instant::Instant;
 
 use crate::{
+    application::Theme,
     kurbo::{Point, Rect, Size, Vec2},
-    piet::{Piet, PietText},
+    piet::{Color, InterpolationMode, Piet, PietText, RenderContext},
     Code,
 };
 
-use anyhow::Error as AnyError;
-use glutin::dpi::{PhysicalPosition, PhysicalSize};
-use glutin::event::{KeyboardInput, VirtualKeyCode};
+use anyhow::{anyhow, Error as AnyError};
+use glutin::dpi::{LogicalSize, PhysicalPosition, PhysicalSize};
+use glutin::event::{KeyboardInput, ModifiersState, MouseScrollDelta};
+use glutin::window::{CursorIcon, Fullscreen, Icon, UserAttentionType};
+use raw_window_handle::{HasRawWindowHandle, RawWindowHandle};
 
 use super::application::Application;
+use super::dialog;
 use super::error::Error;
 use super::keycodes;
 use super::menu::Menu;
-use super::util::Timer;
-use crate::common_util::IdleCallback;
-use crate::dialog::{FileDialogOptions, FileDialogType};
+use crate::common_util::ClickCounter;
+use crate::dialog::{FileDialogOptions, FileDialogType, FileInfo};
 use crate::error::Error as ShellError;
 use crate::keyboard::Modifiers;
-use crate::scale::{Scalable, Scale, ScaledArea};
+use crate::platform::shared::{
+    convert_modifiers, virtual_keycode_to_code, DamageHistory, IdleKind, Waker, WindowCore,
+    DEFAULT_BUFFER_COUNT,
+};
+use crate::scale::{Scalable, Scale};
+use crate::screen::Monitor;
 
 use crate::keyboard::KeyState;
 
@@ -52,11 +62,97 @@ use crate::region::Region;
 use crate::window;
 use crate::window::{FileDialogToken, IdleToken, TimerToken, WinHandler, WindowLevel};
 
+pub use crate::platform::shared::IdleHandle;
+
 pub struct Window {
-    handler: RefCell<Box<dyn WinHandler>>,
-    window_state: RefCell<WindowState>,
-    idle_queue: Arc<Mutex<Vec<IdleKind>>>,
-    timer_queue: Mutex<BinaryHeap<Timer>>,
+    pub(crate) core: WindowCore<WindowState>,
+    /// Operations that need the glutin window, which only the event loop owns.
+    deferred_queue: RefCell<Vec<DeferredOp>>,
+    /// The menu bar, once it has been shown.
+    menu: RefCell<Option<Menu>>,
+    /// The last context menu that was shown.
+    context_menu: RefCell<Option<Menu>>,
+    /// The handle of the glutin window, once it has been created.
+    raw_handle: Cell<Option<RawWindowHandle>>,
+}
+
+/// The parts of a window's setup that the GL context has to be created for.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct GlOptions {
+    /// The requested number of samples per pixel for multisampling, 0 for none.
+    pub(crate) sample_count: u16,
+    /// Whether the window is see-through where nothing has been painted.
+    pub(crate) transparent: bool,
+    /// Whether buffer swaps wait for the vertical blank of the display.
+    pub(crate) vsync: bool,
+    /// The requested bits per pixel of the stencil buffer, 0 for none.
+    pub(crate) stencil_bits: u8,
+    /// The requested bits per pixel of the depth buffer, 0 for none.
+    pub(crate) depth_bits: u8,
+}
+
+impl Default for GlOptions {
+    fn default() -> GlOptions {
+        GlOptions {
+            sample_count: 0,
+            transparent: false,
+            vsync: false,
+            // skia draws complex clips and paths with the stencil buffer.
+            stencil_bits: 8,
+            depth_bits: 0,
+        }
+    }
+}
+
+/// An operation on the glutin window, applied by the event loop.
+pub(crate) enum DeferredOp {
+    SetTitle(String),
+    SetSize(Size),
+    SetMinSize(Size),
+    SetMaxSize(Size),
+    /// Move the outer top left corner of the window, in pixels.
+    SetPosition(Point),
+    SetResizable(bool),
+    SetAlwaysOnTop(bool),
+    /// Show the cursor with the given icon, or hide it when we draw a custom cursor or
+    /// the cursor is hidden.
+    SetCursor(Option<CursorIcon>),
+    /// Grab the pointer so that its events keep coming while it is outside the window.
+    SetCursorGrab(bool),
+    /// Move the input method's candidate box, in display points relative to the content.
+    SetImePosition(Point),
+    /// Set the opacity of the whole window, from 0 to 1.
+    SetOpacity(f64),
+    SetWindowState(window::WindowState),
+    SetFullscreen(bool),
+    BringToFront,
+    SetMenu(Menu),
+    ShowContextMenu(Menu, Point),
+    SetMenuItemEnabled(u32, bool),
+    SetMenuItemChecked(u32, bool),
+    Open(FileDialogOptions, FileDialogToken),
+    SaveAs(FileDialogOptions, FileDialogToken),
+    Close,
+}
+
+/// The handler call for the `paths` chosen in a file dialog, `None` if it was cancelled.
+///
+/// Every dialog gets exactly one answer, a multi-selection reports all paths at once.
+fn file_dialog_response(
+    ty: FileDialogType,
+    multi_selection: bool,
+    token: FileDialogToken,
+    paths: Option<Vec<PathBuf>>,
+) -> IdleKind {
+    let mut files = paths
+        .unwrap_or_default()
+        .into_iter()
+        .map(|path| FileInfo { path });
+    match ty {
+        FileDialogType::Open if multi_selection => IdleKind::OpenFiles(token, files.collect()),
+        FileDialogType::Open => IdleKind::OpenFile(token, files.next()),
+        FileDialogType::Save => IdleKind::SaveAs(token, files.next()),
+    }
 }
 
 pub fn convert_mouse_button(mouse_button: glutin::event::MouseButton) -> Option<MouseButton> {
@@ -64,158 +160,74 @@ pub fn convert_mouse_button(mouse_button: glutin::event::MouseButton) -> Option<
         glutin::event::MouseButton::Left => Some(MouseButton::Left),
         glutin::event::MouseButton::Right => Some(MouseButton::Right),
         glutin::event::MouseButton::Middle => Some(MouseButton::Middle),
-        _ => None,
+        // The common codes for the back and forward side buttons.
+        glutin::event::MouseButton::Other(1) => Some(MouseButton::X1),
+        glutin::event::MouseButton::Other(2) => Some(MouseButton::X2),
+        glutin::event::MouseButton::Other(_) => None,
     }
 }
 
-fn virtual_keycode_to_code(vkc: VirtualKeyCode) -> Code {
-    use glutin::event::VirtualKeyCode::*;
-
-    // TODO: Many of these keycodes map to shift + another key, for now they are
-    //       left as `Code::Unidentified` - as are any keys that don't have a
-    //       good mapping in the `Code` enum like Fn keys above 12.
-    match vkc {
-        Key1 => Code::Digit1,
-        Key2 => Code::Digit2,
-        Key3 => Code::Digit3,
-        Key4 => Code::Digit4,
-        Key5 => Code::Digit5,
-        Key6 => Code::Digit6,
-        Key7 => Code::Digit7,
-        Key8 => Code::Digit8,
-        Key9 => Code::Digit9,
-        Key0 => Code::Digit0,
-        A => Code::KeyA,
-        B => Code::KeyB,
-        C => Code::KeyC,
-        D => Code::KeyD,
-        E => Code::KeyE,
-        F => Code::KeyF,
-        G => Code::KeyG,
-        H => Code::KeyH,
-        I => Code::KeyI,
-        J => Code::KeyJ,
-        K => Code::KeyK,
-        L => Code::KeyL,
-        M => Code::KeyM,
-        N => Code::KeyN,
-        O => Code::KeyO,
-        P => Code::KeyP,
-        Q => Code::KeyQ,
-        R => Code::KeyR,
-        S => Code::KeyS,
-        T => Code::KeyT,
-        U => Code::KeyU,
-        V => Code::KeyV,
-        W => Code::KeyW,
-        X => Code::KeyX,
-        Y => Code::KeyY,
-        Z => Code::KeyZ,
-        Escape => Code::Escape,
-        F1 => Code::F1,
-        F2 => Code::F2,
-        F3 => Code::F3,
-        F4 => Code::F4,
-        F5 => Code::F5,
-        F6 => Code::F6,
-        F7 => Code::F7,
-        F8 => Code::F8,
-        F9 => Code::F9,
-        F10 => Code::F10,
-        F11 => Code::F11,
-        F12 => Code::F12,
-        // TODO
-        F13 | F14 | F15 | F16 | F17 | F18 | F19 | F20 | F21 | F22 | F23 | F24 => Code::Unidentified,
-        Snapshot => Code::PrintScreen,
-        Scroll => Code::ScrollLock,
-        Pause => Code::Pause,
-        Insert => Code::Insert,
-        Home => Code::Home,
-        Delete => Code::Delete,
-        End => Code::End,
-        PageDown => Code::PageDown,
-        PageUp => Code::PageUp,
-        Left => Code::ArrowLeft,
-        Up => Code::ArrowUp,
-        Right => Code::ArrowRight,
-        Down => Code::ArrowDown,
-        Back => Code::Backspace,
-        Return => Code::Enter,
-        Space => Code::Space,
-        // TODO
-        Compose | Caret => Code::Unidentified,
-        Numlock => Code::NumLock,
-        Numpad0 => Code::Numpad0,
-        Numpad1 => Code::Numpad1,
-        Numpad2 => Code::Numpad2,
-        Numpad3 => Code::Numpad3,
-        Numpad4 => Code::Numpad4,
-        Numpad5 => Code::Numpad5,
-        Numpad6 => Code::Numpad6,
-        Numpad7 => Code::Numpad7,
-        Numpad8 => Code::Numpad8,
-        Numpad9 => Code::Numpad9,
-        NumpadAdd => Code::NumpadAdd,
-        NumpadDivide => Code::NumpadDivide,
-        NumpadDecimal => Code::NumpadDecimal,
-        NumpadComma => Code::NumpadComma,
-        NumpadEnter => Code::NumpadEnter,
-        NumpadEquals => Code::NumpadEqual,
-        NumpadMultiply => Code::NumpadMultiply,
-        NumpadSubtract => Code::NumpadSubtract,
-        // TODO
-        AbntC1 | AbntC2 | Apostrophe | Apps | Asterisk | At | Ax => Code::Unidentified,
-        Backslash => Code::Backslash,
-        // TODO
-        Calculator | Capital | Colon => Code::Unidentified,
-        Comma => Code::Comma,
-        Convert => Code::Convert,
-        Equals => Code::Equal,
-        // TODO
-        Grave | Kana | Kanji | LAlt | LBracket | LControl | LShift | LWin | Mail => {
-            Code::Unidentified
+/// Convert a druid cursor to the closest glutin cursor icon.
+pub fn convert_cursor(cursor: &Cursor) -> CursorIcon {
+    match cursor {
+        Cursor::Arrow => CursorIcon::Default,
+        Cursor::IBeam => CursorIcon::Text,
+        Cursor::Crosshair => CursorIcon::Crosshair,
+        Cursor::OpenHand => CursorIcon::Grab,
+        Cursor::NotAllowed => CursorIcon::NotAllowed,
+        Cursor::ResizeLeftRight => CursorIcon::EwResize,
+        Cursor::ResizeUpDown => CursorIcon::NsResize,
+        // Custom cursors are drawn by the window, see `CustomCursor`.
+        Cursor::Custom(_) => CursorIcon::Default,
+    }
+}
+
+/// Whether windows of `level` are kept above other windows.
+///
+/// glutin only knows normal and always on top windows, so everything but app windows
+/// is kept on top.
+fn is_always_on_top(level: WindowLevel) -> bool {
+    match level {
+        WindowLevel::AppWindow => false,
+        WindowLevel::Tooltip | WindowLevel::DropDown | WindowLevel::Modal => true,
+    }
+}
+
+/// The highest click count reported for repeated clicks.
+const MAX_CLICK_COUNT: u8 = 3;
+
+/// The default wheel delta reported for one line of scrolling.
+///
+/// We use a delta of 120 per line to match the behavior of Windows.
+const SCROLL_LINE_DELTA: f64 = 120.0;
+
+/// Convert a glutin scroll delta to a druid wheel delta in display points.
+///
+/// Mouse wheels scroll by lines, which are multiplied by `line_delta`. Trackpads report
+/// precise pixel deltas on both axes, including the stream of small deltas of inertial
+/// scrolling, these are only converted to display points.
+///
+/// glutin reports positive vertical values for scrolling away from the user,
+/// which is the opposite of druid's polarity.
+pub fn convert_scroll_delta(delta: MouseScrollDelta, scale: Scale, line_delta: f64) -> Vec2 {
+    match delta {
+        MouseScrollDelta::LineDelta(x, y) => {
+            Vec2::new(x as f64 * line_delta, -y as f64 * line_delta)
         }
-        MediaSelect => Code::MediaSelect,
-        MediaStop => Code::MediaStop,
-        Minus => Code::Minus,
-        // TODO
-        Mute | MyComputer | NavigateForward | NavigateBackward | NextTrack => Code::Unidentified,
-        NoConvert => Code::NonConvert,
-        OEM102 => Code::Unidentified,
-        Period => Code::Period,
-        // TODO
-        PlayPause | Plus => Code::Unidentified,
-        Power => Code::Power,
-        // TODO
-        PrevTrack | RAlt | RBracket | RControl | RShift | RWin => Code::Unidentified,
-        Semicolon => Code::Semicolon,
-        Slash => Code::Slash,
-        Sleep => Code::Sleep,
-        // TODO
-        Stop | Sysrq => Code::Unidentified,
-        Tab => Code::Tab,
-        // TODO
-        Underline | Unlabeled | VolumeDown | VolumeUp | Wake | WebBack | WebFavorites
-        | WebForward | WebHome | WebRefresh | WebSearch | WebStop | Yen => Code::Unidentified,
-        Copy => Code::Copy,
-        Paste => Code::Paste,
-        Cut => Code::Cut,
+        MouseScrollDelta::PixelDelta(pos) => Vec2::new(pos.x / scale.x(), -pos.y / scale.y()),
     }
 }
 
 impl Window {
     pub fn render(&self, canvas: &mut skia_safe::Canvas) -> Result<(), AnyError> {
         // important for AnimStart and invalidation of required regions
-        self.with_handler(|h| h.prepare_paint());
-        let invalid =
-            std::mem::replace(&mut borrow_mut!(self.window_state)?.invalid, Region::EMPTY);
-        let prev_invalid = borrow_mut!(self.window_state)?.prev_invalid.clone();
-        let mut buffer_damage = invalid.clone();
-        buffer_damage.union_with(&prev_invalid);
+        self.core.with_handler(|h| h.prepare_paint());
+        let invalid = std::mem::replace(&mut self.core.state_mut()?.invalid, Region::EMPTY);
+        let buffer_damage = self.core.state_mut()?.damage.buffer_damage(invalid);
         canvas.save();
         let mut region = skia_safe::region::Region::new();
         for rect in buffer_damage.rects() {
-            let scale = self.state()?.scale;
+            let scale = self.core.state()?.scale;
             let rect = rect.to_px(scale);
             let rect = skia_safe::IRect {
                 left: rect.x0 as i32,
@@ -226,118 +238,382 @@ impl Window {
             region.op_rect(rect, skia_safe::region::RegionOp::Union);
         }
         canvas.clip_region(&region, None);
-        let mut piet_ctx = Piet::new(canvas);
-        let mut win_handler = borrow_mut!(self.handler).unwrap();
-
-        win_handler.paint(&mut piet_ctx, &buffer_damage);
+        // Without this, whatever was drawn before would show through the damaged region.
+        // The clip keeps the clear to the damaged region.
+        let (background, transparent) = {
+            let state = self.core.state()?;
+            (state.background.clone(), state.gl_options.transparent)
+        };
+        if let Some(color) = background {
+            let (r, g, b, a) = color.as_rgba8();
+            canvas.clear(skia_safe::Color::from_argb(a, r, g, b));
+        } else if transparent {
+            canvas.clear(skia_safe::Color::TRANSPARENT);
+        }
+        self.core
+            .with_handler_and_dont_check_the_other_borrows(|h| {
+                h.pre_paint(canvas);
+                let mut piet_ctx = Piet::new(canvas);
+                h.paint(&mut piet_ctx, &buffer_damage);
+                h.post_paint(canvas);
+            });
+        if let Some((cursor, rect)) = self.custom_cursor() {
+            // The cursor goes on top of everything the handler drew.
+            let mut piet_ctx = Piet::new(canvas);
+            let image = cursor.0.image.to_image(&mut piet_ctx);
+            piet_ctx.draw_image(&image, rect, InterpolationMode::Bilinear);
+        }
         canvas.restore();
+        Ok(())
+    }
 
-        borrow_mut!(self.window_state)?.prev_invalid = invalid;
+    pub fn connect(&self, handle: WindowHandle) -> Result<(), AnyError> {
+        let size = self.size()?;
+        let scale = self.core.state()?.scale;
+        self.core
+            .with_handler_and_dont_check_the_other_borrows(|h| {
+                h.connect(&handle.into());
+                h.scale(scale);
+                h.size(size)
+            });
         Ok(())
     }
 
-    #[track_caller]
-    fn with_handler<T, F: FnOnce(&mut dyn WinHandler) -> T>(&self, f: F) -> Option<T> {
-        if self.handler.try_borrow_mut().is_err() || self.state_mut().is_err() {
-            log::error!("other RefCells were borrowed when calling into the handler");
-            return None;
-        }
+    // TODO this is pub temporary cause we are using it to create window from application.rs
+    /// The size of the window's content area, in display points.
+    pub fn size(&self) -> Result<Size, AnyError> {
+        Ok(self.core.state()?.size)
+    }
 
-        self.with_handler_and_dont_check_the_other_borrows(f)
+    /// Let the handler clean up, and mark the window as closed.
+    ///
+    /// The event loop exits once it sees the window is closed.
+    pub(crate) fn close(&self) {
+        if self.is_closed() {
+            return;
+        }
+        self.core.with_handler(|h| h.destroy());
+        match self.core.state_mut() {
+            Ok(mut state) => state.closed = true,
+            Err(err) => log::error!("Window::close - {}", err),
+        }
     }
 
-    #[track_caller]
-    fn with_handler_and_dont_check_the_other_borrows<T, F: FnOnce(&mut dyn WinHandler) -> T>(
-        &self,
-        f: F,
-    ) -> Option<T> {
-        match self.handler.try_borrow_mut() {
-            Ok(mut h) => Some(f(&mut **h)),
-            Err(_) => {
-                log::error!("failed to borrow WinHandler at {}", Location::caller());
+    pub(crate) fn is_closed(&self) -> bool {
+        self.core.state().map(|state| state.closed).unwrap_or(false)
+    }
+
+    /// Describe the glutin window that should be created for this window.
+    pub(crate) fn window_builder(&self) -> Result<glutin::window::WindowBuilder, AnyError> {
+        let state = self.core.state()?;
+        let mut builder = glutin::window::WindowBuilder::new()
+            .with_title(state.title.clone())
+            .with_inner_size(LogicalSize::new(state.size.width, state.size.height))
+            .with_resizable(state.resizable)
+            .with_transparent(state.gl_options.transparent)
+            .with_always_on_top(state.always_on_top)
+            .with_window_icon(state.icon.clone())
+            .with_maximized(state.window_state == window::WindowState::MAXIMIZED)
+            .with_fullscreen(if state.windowed_size.is_some() {
+                Some(Fullscreen::Borderless(None))
+            } else {
                 None
+            });
+        if let Some(size) = state.min_size {
+            builder = builder.with_min_inner_size(LogicalSize::new(size.width, size.height));
+        }
+        if let Some(size) = state.max_size {
+            builder = builder.with_max_inner_size(LogicalSize::new(size.width, size.height));
+        }
+        Ok(builder)
+    }
+
+    /// Move the newly created glutin window to the requested position.
+    ///
+    /// winit can't create a window at a given position, it is placed by the platform first.
+    pub(crate) fn apply_position(&self, window: &glutin::window::Window) {
+        match self.core.state() {
+            Ok(state) => {
+                if let Some(position) = state.position {
+                    window.set_outer_position(PhysicalPosition::new(position.x, position.y));
+                }
             }
+            Err(err) => log::error!("Window::apply_position - {}", err),
         }
     }
 
-    pub fn connect(&self, handle: WindowHandle) -> Result<(), AnyError> {
-        let size = self.size()?;
-        let scale = self.state()?.scale;
-        self.with_handler_and_dont_check_the_other_borrows(|h| {
-            h.connect(&handle.into());
-            h.scale(scale);
-            h.size(size)
-        });
+    /// Pick the position that centers the window on `primary`, if it was asked to be
+    /// centered. Without a monitor the platform places the window.
+    pub(crate) fn apply_centering(&self, primary: Option<Monitor>) -> Result<(), AnyError> {
+        let mut state = self.core.state_mut()?;
+        if !state.center_on_primary {
+            return Ok(());
+        }
+        state.center_on_primary = false;
+        if let Some(monitor) = primary {
+            state.position = Some(centered_position(state.size, &monitor));
+        }
         Ok(())
     }
 
-    // TODO this is pub temporary cause we are using it to create window from application.rs
-    pub fn size(&self) -> Result<Size, AnyError> {
-        Ok(borrow!(self.window_state)?.size)
+    /// How the GL context of this window should be set up.
+    pub(crate) fn gl_options(&self) -> Result<GlOptions, AnyError> {
+        Ok(self.core.state()?.gl_options)
     }
 
-    pub(crate) fn run_idle(&self) {
-        let mut queue = Vec::new();
-        std::mem::swap(&mut *self.idle_queue.lock().unwrap(), &mut queue);
-
-        let mut needs_redraw = false;
-        self.with_handler(|handler| {
-            for callback in queue {
-                match callback {
-                    IdleKind::Callback(f) => {
-                        f.call(handler.as_any());
+    fn apply_fullscreen(&self, window: &glutin::window::Window, fullscreen: bool) {
+        let mut state = match self.core.state_mut() {
+            Ok(state) => state,
+            Err(err) => {
+                log::error!("Window::apply_fullscreen - {}", err);
+                return;
+            }
+        };
+        if fullscreen == state.windowed_size.is_some() {
+            return;
+        }
+        if fullscreen {
+            state.windowed_size = Some(state.size);
+            window.set_fullscreen(Some(Fullscreen::Borderless(None)));
+        } else if let Some(size) = state.windowed_size.take() {
+            window.set_fullscreen(None);
+            let size = size.to_px(state.scale);
+            window.set_inner_size(PhysicalSize::new(size.width, size.height));
+        }
+    }
+
+    pub(crate) fn set_raw_window_handle(&self, handle: RawWindowHandle) {
+        self.raw_handle.set(Some(handle));
+    }
+
+    fn defer(&self, op: DeferredOp) {
+        self.deferred_queue.borrow_mut().push(op);
+    }
+
+    /// Apply the queued operations to the glutin window.
+    pub(crate) fn run_deferred(&self, window: &glutin::window::Window) {
+        let scale = match self.core.state() {
+            Ok(state) => state.scale,
+            Err(err) => {
+                log::error!("Window::run_deferred - failed to get scale: {}", err);
+                return;
+            }
+        };
+        let ops = std::mem::take(&mut *self.deferred_queue.borrow_mut());
+        for op in ops {
+            match op {
+                DeferredOp::SetTitle(title) => window.set_title(&title),
+                DeferredOp::SetSize(size) => {
+                    let size = size.to_px(scale);
+                    window.set_inner_size(PhysicalSize::new(size.width, size.height));
+                }
+                DeferredOp::SetMinSize(size) => {
+                    let size = size.to_px(scale);
+                    window.set_min_inner_size(Some(PhysicalSize::new(size.width, size.height)));
+                }
+                DeferredOp::SetMaxSize(size) => {
+                    let size = size.to_px(scale);
+                    window.set_max_inner_size(Some(PhysicalSize::new(size.width, size.height)));
+                }
+                DeferredOp::SetPosition(position) => {
+                    window.set_outer_position(PhysicalPosition::new(position.x, position.y));
+                }
+                DeferredOp::SetResizable(resizable) => window.set_resizable(resizable),
+                DeferredOp::SetAlwaysOnTop(on_top) => window.set_always_on_top(on_top),
+                DeferredOp::SetCursor(Some(icon)) => {
+                    window.set_cursor_visible(true);
+                    window.set_cursor_icon(icon);
+                }
+                DeferredOp::SetCursor(None) => window.set_cursor_visible(false),
+                DeferredOp::SetCursorGrab(grab) => {
+                    // Wayland compositors may not support grabs, the capture is then only
+                    // tracked by the window.
+                    if let Err(err) = window.set_cursor_grab(grab) {
+                        log::warn!("Failed to set the cursor grab: {}", err);
                     }
-                    IdleKind::Token(tok) => {
-                        handler.idle(tok);
+                }
+                DeferredOp::SetImePosition(position) => {
+                    let position = position.to_px(scale);
+                    window.set_ime_position(PhysicalPosition::new(position.x, position.y));
+                }
+                DeferredOp::SetOpacity(opacity) => {
+                    if !set_window_opacity(window, opacity) {
+                        log::warn!("Window opacity isn't supported on this platform");
+                    }
+                }
+                DeferredOp::SetWindowState(state) => match state {
+                    window::WindowState::MAXIMIZED => {
+                        window.set_minimized(false);
+                        window.set_maximized(true);
+                    }
+                    window::WindowState::MINIMIZED => window.set_minimized(true),
+                    window::WindowState::RESTORED => {
+                        window.set_minimized(false);
+                        window.set_maximized(false);
                     }
-                    IdleKind::_Redraw => {
-                        needs_redraw = true;
+                },
+                DeferredOp::SetFullscreen(fullscreen) => self.apply_fullscreen(window, fullscreen),
+                DeferredOp::BringToFront => {
+                    window.set_minimized(false);
+                    window.set_visible(true);
+                    // Compositors that don't let us take focus can still flag the window.
+                    if !activate_window(window) {
+                        window.request_user_attention(Some(UserAttentionType::Informational));
                     }
                 }
+                DeferredOp::SetMenu(mut menu) => {
+                    menu.show_for_window(window);
+                    *self.menu.borrow_mut() = Some(menu);
+                }
+                DeferredOp::ShowContextMenu(mut menu, pos) => {
+                    menu.show_context_menu(window, pos);
+                    *self.context_menu.borrow_mut() = Some(menu);
+                }
+                DeferredOp::SetMenuItemEnabled(id, enabled) => {
+                    self.update_menu_item(id, |menu| menu.set_item_enabled(id, enabled));
+                }
+                DeferredOp::SetMenuItemChecked(id, checked) => {
+                    self.update_menu_item(id, |menu| menu.set_item_checked(id, checked));
+                }
+                DeferredOp::Open(options, token) => {
+                    self.show_file_dialog(FileDialogType::Open, options, token)
+                }
+                DeferredOp::SaveAs(options, token) => {
+                    self.show_file_dialog(FileDialogType::Save, options, token)
+                }
+                DeferredOp::Close => self.close(),
             }
-        });
-
-        // TODO
-        //if needs_redraw {
-        //    if let Err(e) = self.redraw_now() {
-        //        log::error!("Error redrawing: {}", e);
-        //    }
-        //}
+        }
     }
 
-    pub(crate) fn next_timeout(&self) -> Option<Instant> {
-        if let Some(timer) = self.timer_queue.lock().unwrap().peek() {
-            Some(timer.deadline())
-        } else {
-            None
+    /// Show a file dialog on its own thread, so that the event loop keeps running while it
+    /// is open, and queue the answer for the handler.
+    fn show_file_dialog(
+        &self,
+        ty: FileDialogType,
+        options: FileDialogOptions,
+        token: FileDialogToken,
+    ) {
+        let multi_selection = options.multi_selection;
+        let idle = self.core.idle_handle();
+        let spawned = thread::Builder::new()
+            .name("druid-shell-file-dialog".into())
+            .spawn(move || {
+                let paths = dialog::get_file_dialog_paths(ty, options);
+                let response = file_dialog_response(ty, multi_selection, token, paths);
+                idle.push(response);
+            });
+        if let Err(err) = spawned {
+            log::error!("Failed to spawn the file dialog thread: {}", err);
+            self.core
+                .push_idle(file_dialog_response(ty, multi_selection, token, None));
         }
     }
 
-    pub(crate) fn run_timers(&self, now: Instant) {
-        while let Some(deadline) = self.next_timeout() {
-            if deadline > now {
-                break;
+    /// Returns `true` if the menu bar or the last context menu has an item with this id.
+    pub(crate) fn has_menu_item(&self, id: u32) -> bool {
+        let in_menu = |menu: &RefCell<Option<Menu>>| {
+            menu.borrow().as_ref().map(|menu| menu.contains(id)) == Some(true)
+        };
+        in_menu(&self.menu) || in_menu(&self.context_menu)
+    }
+
+    /// Apply `update` to the menu bar and the last context menu, warning if neither has
+    /// the item `id`.
+    fn update_menu_item(&self, id: u32, mut update: impl FnMut(&mut Menu) -> bool) {
+        let mut found = false;
+        for menu in &[&self.menu, &self.context_menu] {
+            if let Some(menu) = menu.borrow_mut().as_mut() {
+                found |= update(menu);
             }
-            // Remove the timer and get the token
-            let token = self.timer_queue.lock().unwrap().pop().unwrap().token();
-            self.with_handler(|h| h.timer(token));
         }
+        if !found {
+            log::warn!("No menu item with id {}", id);
+        }
+    }
+
+    /// Queue `token` for the handler's `idle` method.
+    pub(crate) fn handle_idle_token(&self, token: IdleToken) {
+        self.core.push_idle(IdleKind::Token(token));
+    }
+
+    /// Queue the command of the menu item `id` for the handler.
+    pub(crate) fn handle_command(&self, id: u32) {
+        self.core.push_idle(IdleKind::Command(id));
+    }
+
+    /// Returns the id of the enabled menu item whose hotkey matches `event`, if any.
+    fn menu_command_for_key(&self, event: &crate::KeyEvent) -> Option<u32> {
+        let in_menu = |menu: &RefCell<Option<Menu>>| menu.borrow().as_ref()?.command_for_key(event);
+        in_menu(&self.menu).or_else(|| in_menu(&self.context_menu))
+    }
+
+    pub(crate) fn run_idle(&self) {
+        if self.core.run_idle() {
+            self.request_anim_frame();
+        }
+    }
+
+    /// Deliver the relative motion of a mouse, if this window has focus.
+    pub(crate) fn handle_raw_motion(&self, (dx, dy): (f64, f64)) {
+        if self.core.state().map_or(false, |state| state.has_focus) {
+            self.core
+                .with_handler(|h| h.raw_mouse_motion(Vec2::new(dx, dy)));
+        }
+    }
+
+    /// Whether any mouse button is held down.
+    pub(crate) fn has_buttons_down(&self) -> bool {
+        self.core
+            .state()
+            .map_or(false, |state| !state.buttons.is_empty())
     }
 
-    pub(crate) fn state_mut(&self) -> Result<std::cell::RefMut<WindowState>, AnyError> {
-        borrow_mut!(self.window_state)
+    /// The modifiers as of the last keyboard event.
+    pub fn modifiers(&self) -> Modifiers {
+        self.core
+            .state()
+            .map(|state| state.mods)
+            .unwrap_or_default()
     }
 
-    pub(crate) fn state(&self) -> Result<std::cell::Ref<WindowState>, AnyError> {
-        borrow!(self.window_state)
+    /// Whether `code` was held down as of the last keyboard event.
+    pub fn is_key_down(&self, code: Code) -> bool {
+        self.core
+            .state()
+            .map_or(false, |state| state.keys_down.contains(&code))
+    }
+
+    /// Update the scale, for example after the window moved to a monitor with a
+    /// different scale factor.
+    pub(crate) fn set_scale(&self, scale: Scale) -> Result<(), AnyError> {
+        self.core.state_mut()?.scale = scale;
+        self.core.with_handler(|h| h.scale(scale));
+        Ok(())
     }
 
     pub fn screen_size_changed(&self, physical_size: PhysicalSize<u32>) -> Result<(), AnyError> {
-        let scale = self.state()?.scale;
+        let scale = self.core.state()?.scale;
         let size = Size::new(physical_size.width as f64, physical_size.height as f64).to_dp(scale);
 
-        self.state_mut()?.size = Size::new(physical_size.width as f64, physical_size.height as f64);
-        self.with_handler(|h| h.size(size));
+        let restored = {
+            let mut state = self.core.state_mut()?;
+            // glutin has no minimize event, but minimized windows are resized to zero.
+            // Un-minimizing can't tell if the window was maximized before, so assume it wasn't.
+            let was_minimized = state.window_state == window::WindowState::MINIMIZED;
+            if size.is_empty() {
+                state.window_state = window::WindowState::MINIMIZED;
+            } else if was_minimized {
+                state.window_state = window::WindowState::RESTORED;
+            }
+            state.size = size;
+            was_minimized && !size.is_empty()
+        };
+        self.core.with_handler(|h| h.size(size));
+        // Nothing was painted while the window was minimized.
+        if restored {
+            self.invalidate();
+        }
         Ok(())
     }
 
@@ -350,8 +626,25 @@ impl Window {
         let code = virtual_keycode
             .map(virtual_keycode_to_code)
             .unwrap_or(Code::Unidentified);
-        // TODO mods
-        let mods = Modifiers::empty();
+        let (mods, repeat) = match self.core.state_mut() {
+            Ok(mut window_state) => {
+                let repeat = match state {
+                    // Unidentified keys can't be told apart, so they are never repeats.
+                    KeyState::Down => {
+                        code != Code::Unidentified && !window_state.keys_down.insert(code)
+                    }
+                    KeyState::Up => {
+                        window_state.keys_down.remove(&code);
+                        false
+                    }
+                };
+                (window_state.mods, repeat)
+            }
+            Err(err) => {
+                log::error!("Window::handle_key_press - {}", err);
+                return;
+            }
+        };
         // TODO location
         let location = crate::Location::Standard;
         let key = keycodes::code_to_key(code, mods);
@@ -362,31 +655,64 @@ impl Window {
             mods,
             location,
             state,
-            repeat: false,
+            repeat,
             is_composing: false,
         };
         match state {
             KeyState::Down => {
-                self.with_handler(|h| h.key_down(key_event));
+                // Like native accelerators, menu hotkeys take precedence over the handler.
+                match self.menu_command_for_key(&key_event) {
+                    Some(id) => self.handle_command(id),
+                    None => {
+                        self.core.with_handler(|h| h.key_down(key_event));
+                    }
+                }
             }
             KeyState::Up => {
-                self.with_handler(|h| h.key_up(key_event));
+                self.core.with_handler(|h| h.key_up(key_event));
             }
         }
     }
 
+    pub fn handle_modifiers_changed(&self, modifiers: ModifiersState) {
+        match self.core.state_mut() {
+            Ok(mut state) => state.mods = convert_modifiers(modifiers),
+            Err(err) => log::error!("Window::handle_modifiers_changed - {}", err),
+        }
+    }
+
     pub fn handle_motion_notify(&self, physical_position: PhysicalPosition<f64>) {
-        let scale = self.state().unwrap().scale; // TODO unwrap
+        let scale = match self.core.state() {
+            Ok(state) => state.scale,
+            Err(err) => {
+                log::error!("Window::handle_motion_notify - {}", err);
+                return;
+            }
+        };
+        let pos = Point::new(physical_position.x, physical_position.y).to_dp(scale);
+        self.invalidate_custom_cursor();
+        let (buttons, mods) = match self.core.state_mut() {
+            Ok(mut state) => {
+                state.mouse_pos = pos;
+                state.hovered = true;
+                (state.buttons, state.mods)
+            }
+            Err(err) => {
+                log::error!("Window::handle_motion_notify - {}", err);
+                return;
+            }
+        };
+        self.invalidate_custom_cursor();
         let mouse_event = MouseEvent {
-            pos: Point::new(physical_position.x, physical_position.y).to_dp(scale),
-            buttons: MouseButtons::new(),
-            mods: Modifiers::empty(), // TODO
+            pos,
+            buttons,
+            mods,
             count: 0,
             focus: false,
             button: MouseButton::None,
             wheel_delta: Vec2::ZERO,
         };
-        self.with_handler(|h| h.mouse_move(&mouse_event));
+        self.core.with_handler(|h| h.mouse_move(&mouse_event));
     }
 
     pub fn handle_button_press(
@@ -395,39 +721,254 @@ impl Window {
         mouse_button: glutin::event::MouseButton,
     ) {
         if let Some(button) = convert_mouse_button(mouse_button) {
-            let scale = self.state().unwrap().scale; // TODO unwrap
+            let scale = match self.core.state() {
+                Ok(state) => state.scale,
+                Err(err) => {
+                    log::error!("Window::handle_button_press - {}", err);
+                    return;
+                }
+            };
+            let pos = Point::new(physical_position.x, physical_position.y).to_dp(scale);
+            let count = self.click_count(button, pos);
+            let (buttons, mods) = match self.core.state_mut() {
+                Ok(mut state) => {
+                    state.buttons.insert(button);
+                    (state.buttons, state.mods)
+                }
+                Err(err) => {
+                    log::error!("Window::handle_button_press - {}", err);
+                    return;
+                }
+            };
             let mouse_event = MouseEvent {
-                pos: Point::new(physical_position.x, physical_position.y).to_dp(scale),
-                buttons: MouseButtons::new(),
-                mods: Modifiers::empty(), // TODO
-                count: 1,
+                pos,
+                buttons,
+                mods,
+                count,
                 focus: false,
                 button,
                 wheel_delta: Vec2::ZERO,
             };
-            self.with_handler(|h| h.mouse_down(&mouse_event));
+            self.core.with_handler(|h| h.mouse_down(&mouse_event));
         }
     }
 
+    /// Returns the click count for a press of `button` at `pos`.
+    ///
+    /// Pressing a different button than last time starts a new sequence.
+    fn click_count(&self, button: MouseButton, pos: Point) -> u8 {
+        let mut state = match self.core.state_mut() {
+            Ok(state) => state,
+            Err(err) => {
+                log::error!("Window::click_count - {}", err);
+                return 1;
+            }
+        };
+        if state.last_click_button != button {
+            state.click_counter.reset();
+            state.last_click_button = button;
+        }
+        state
+            .click_counter
+            .count_for_click(pos)
+            .min(MAX_CLICK_COUNT)
+    }
+
     pub fn handle_button_release(
         &self,
         physical_position: PhysicalPosition<f64>,
         mouse_button: glutin::event::MouseButton,
     ) {
         if let Some(button) = convert_mouse_button(mouse_button) {
-            let scale = self.state().unwrap().scale; // TODO unwrap
+            let (scale, buttons, mods) = match self.core.state_mut() {
+                Ok(mut state) => {
+                    // druid doesn't want the released button to be included.
+                    state.buttons.remove(button);
+                    (state.scale, state.buttons, state.mods)
+                }
+                Err(err) => {
+                    log::error!("Window::handle_button_release - {}", err);
+                    return;
+                }
+            };
             let mouse_event = MouseEvent {
                 pos: Point::new(physical_position.x, physical_position.y).to_dp(scale),
-                buttons: MouseButtons::new(),
-                mods: Modifiers::empty(), // TODO
+                buttons,
+                mods,
                 count: 0,
                 focus: false,
                 button,
                 wheel_delta: Vec2::ZERO,
             };
-            self.with_handler(|h| h.mouse_up(&mouse_event));
+            self.core.with_handler(|h| h.mouse_up(&mouse_event));
+            if buttons.is_empty() {
+                self.release_pointer();
+            }
         }
     }
+
+    /// Show the last cursor again, glutin may have reset it while the pointer was away.
+    pub fn handle_cursor_entered(&self) {
+        match self.core.state().map(|state| state.shown_cursor_icon()) {
+            Ok(icon) => self.defer(DeferredOp::SetCursor(icon)),
+            Err(err) => log::error!("Window::handle_cursor_entered - {}", err),
+        }
+    }
+
+    /// Remember where the window moved to, for `get_position`, and tell the handler.
+    pub fn handle_moved(&self, position: PhysicalPosition<i32>) {
+        let position = Point::new(position.x.into(), position.y.into());
+        match self.core.state_mut() {
+            Ok(mut state) => state.position = Some(position),
+            Err(err) => log::error!("Window::handle_moved - {}", err),
+        }
+        self.core.with_handler(|h| h.position(position));
+    }
+
+    pub fn handle_cursor_left(&self) {
+        self.invalidate_custom_cursor();
+        let captured = match self.core.state_mut() {
+            Ok(mut state) => {
+                state.hovered = false;
+                state.pointer_captured
+            }
+            Err(err) => {
+                log::error!("Window::handle_cursor_left - {}", err);
+                false
+            }
+        };
+        // The pointer still belongs to the window until the capture is released.
+        if captured {
+            return;
+        }
+        // Held buttons are kept, the release may still arrive if a drag
+        // continues outside of the window.
+        self.core.with_handler(|h| h.mouse_leave());
+    }
+
+    fn set_cursor(&self, cursor: &Cursor) {
+        let custom = match cursor {
+            Cursor::Custom(custom) => Some(custom.clone()),
+            _ => None,
+        };
+        // Widgets set the cursor on every mouse move, only redraw a custom one when it changes.
+        if custom.is_some()
+            && self
+                .core
+                .state()
+                .map_or(false, |state| state.cursor == custom)
+        {
+            return;
+        }
+        self.invalidate_custom_cursor();
+        let icon = match custom {
+            Some(_) => None,
+            None => Some(convert_cursor(cursor)),
+        };
+        let icon = match self.core.state_mut() {
+            Ok(mut state) => {
+                state.cursor = custom;
+                state.cursor_icon = icon;
+                state.shown_cursor_icon()
+            }
+            Err(err) => {
+                log::error!("Window::set_cursor - {}", err);
+                return;
+            }
+        };
+        self.invalidate_custom_cursor();
+        self.defer(DeferredOp::SetCursor(icon));
+    }
+
+    /// Show or hide the cursor, whether it is a system or a custom one.
+    pub fn set_cursor_visible(&self, visible: bool) {
+        self.invalidate_custom_cursor();
+        let icon = match self.core.state_mut() {
+            Ok(mut state) => {
+                state.cursor_visible = visible;
+                state.shown_cursor_icon()
+            }
+            Err(err) => {
+                log::error!("Window::set_cursor_visible - {}", err);
+                return;
+            }
+        };
+        self.invalidate_custom_cursor();
+        self.defer(DeferredOp::SetCursor(icon));
+    }
+
+    /// The custom cursor and where to draw it, if one is shown.
+    fn custom_cursor(&self) -> Option<(CustomCursor, Rect)> {
+        let state = self.core.state().ok()?;
+        if !state.hovered || !state.cursor_visible {
+            return None;
+        }
+        let cursor = state.cursor.clone()?;
+        let rect = cursor.rect(state.mouse_pos);
+        Some((cursor, rect))
+    }
+
+    fn invalidate_custom_cursor(&self) {
+        if let Some((_, rect)) = self.custom_cursor() {
+            self.invalidate_rect(rect);
+        }
+    }
+
+    pub(crate) fn handle_user_event(&self, event: Box<dyn Any + Send>) {
+        self.core.with_handler(|h| h.user_event(event));
+    }
+
+    pub(crate) fn handle_theme_changed(&self, theme: Theme) {
+        self.core.with_handler(|h| h.theme_changed(theme));
+    }
+
+    pub fn handle_focus(&self, focused: bool) {
+        match self.core.state_mut() {
+            Ok(mut state) => state.has_focus = focused,
+            Err(err) => log::error!("Window::handle_focus - {}", err),
+        }
+        if focused {
+            self.core.with_handler(|h| h.got_focus());
+        } else {
+            // Releases that happen while we're in the background never reach us,
+            // so forget about anything that was held down.
+            match self.core.state_mut() {
+                Ok(mut state) => {
+                    state.mods = Modifiers::empty();
+                    state.buttons = MouseButtons::new();
+                    state.keys_down.clear();
+                }
+                Err(err) => log::error!("Window::handle_focus - {}", err),
+            }
+            self.core.with_handler(|h| h.lost_focus());
+        }
+    }
+
+    pub fn handle_mouse_wheel(&self, delta: MouseScrollDelta) {
+        let (wheel_delta, pos, buttons, mods) = match self.core.state() {
+            Ok(state) => (
+                convert_scroll_delta(delta, state.scale, state.scroll_line_delta),
+                state.mouse_pos,
+                state.buttons,
+                state.mods,
+            ),
+            Err(err) => {
+                log::error!("Window::handle_mouse_wheel - failed to get state: {}", err);
+                return;
+            }
+        };
+        let mouse_event = MouseEvent {
+            pos,
+            buttons,
+            mods,
+            count: 0,
+            focus: false,
+            button: MouseButton::None,
+            wheel_delta,
+        };
+        self.core.with_handler(|h| h.wheel(&mouse_event));
+    }
+
     //    pub fn handle_button_release(&self, button_release: &xproto::ButtonReleaseEvent) {
     //        let button = mouse_button(button_release.detail);
     //        let mouse_event = MouseEvent {
@@ -441,30 +982,110 @@ impl Window {
     //            button,
     //            wheel_delta: Vec2::ZERO,
     //        };
-    //        self.with_handler(|h| h.mouse_up(&mouse_event));
+    //        self.core.with_handler(|h| h.mouse_up(&mouse_event));
     //    }
 
-    /// Schedule a redraw on the idle loop, or if we are waiting on present then schedule it for
-    /// when the current present finishes.
-    fn request_anim_frame(&self) {
-        //if let Ok(true) = self.waiting_on_present() {
-        //    if let Err(e) = self.set_needs_present(true) {
-        //        log::error!(
-        //            "Window::request_anim_frame - failed to schedule present: {}",
-        //            e
-        //        );
-        //    }
-        //} else {
-        //    let idle = IdleHandle {
-        //        queue: Arc::clone(&self.idle_queue),
-        //        pipe: self.idle_pipe,
-        //    };
-        //    idle.schedule_redraw();
-        //}
+    /// Keep delivering the pointer events to this window while the pointer is outside of it.
+    ///
+    /// The capture is released by `release_pointer`, or when the last button is released.
+    pub fn capture_pointer(&self) {
+        match self.core.state_mut() {
+            Ok(state) if state.pointer_captured => return,
+            Ok(mut state) => state.pointer_captured = true,
+            Err(err) => {
+                log::error!("Window::capture_pointer - {}", err);
+                return;
+            }
+        }
+        self.defer(DeferredOp::SetCursorGrab(true));
+    }
+
+    pub fn release_pointer(&self) {
+        match self.core.state_mut() {
+            Ok(state) if !state.pointer_captured => return,
+            Ok(mut state) => state.pointer_captured = false,
+            Err(err) => {
+                log::error!("Window::release_pointer - {}", err);
+                return;
+            }
+        }
+        self.defer(DeferredOp::SetCursorGrab(false));
+    }
+
+    /// Ask the event loop to redraw the window on its next frame.
+    pub(crate) fn request_anim_frame(&self) {
+        match self.core.state_mut() {
+            Ok(mut state) => state.wants_anim_frame = true,
+            Err(err) => log::error!("Window::request_anim_frame - {}", err),
+        }
+    }
+
+    /// Returns `true` if there is damage to repaint or an animation frame was requested.
+    ///
+    /// Minimized windows are never redrawn, what they need is painted once they are restored.
+    pub(crate) fn needs_redraw(&self) -> bool {
+        match self.core.state() {
+            Ok(state) if state.window_state == window::WindowState::MINIMIZED => false,
+            Ok(state) => state.wants_anim_frame || !state.invalid.is_empty(),
+            Err(err) => {
+                log::error!("Window::needs_redraw - {}", err);
+                false
+            }
+        }
+    }
+
+    /// Returns `true` if a redraw was requested since the last call.
+    pub(crate) fn take_anim_frame_request(&self) -> bool {
+        match self.core.state_mut() {
+            Ok(mut state) => std::mem::take(&mut state.wants_anim_frame),
+            Err(err) => {
+                log::error!("Window::take_anim_frame_request - {}", err);
+                false
+            }
+        }
+    }
+
+    /// Ask the event loop to read back the pixels of the next frame.
+    pub(crate) fn request_capture(&self) {
+        match self.core.state_mut() {
+            Ok(mut state) => state.capture_requested = true,
+            Err(err) => log::error!("Window::request_capture - {}", err),
+        }
+        self.request_anim_frame();
+    }
+
+    /// Returns `true` if the frame that is being drawn should be captured.
+    pub(crate) fn take_capture_request(&self) -> bool {
+        match self.core.state_mut() {
+            Ok(mut state) => std::mem::take(&mut state.capture_requested),
+            Err(err) => {
+                log::error!("Window::take_capture_request - {}", err);
+                false
+            }
+        }
+    }
+
+    pub(crate) fn set_capture(&self, pixels: Vec<u8>, size: Size) {
+        match self.core.state_mut() {
+            Ok(mut state) => state.capture = Some((pixels, size)),
+            Err(err) => log::error!("Window::set_capture - {}", err),
+        }
+    }
+
+    /// Take the pixels of the frame drawn after the last `request_capture`.
+    ///
+    /// The pixels are RGBA8 with straight (not premultiplied) alpha, in rows from
+    /// top to bottom. The size is in pixels, not display points.
+    pub fn capture(&self) -> Result<(Vec<u8>, Size), AnyError> {
+        self.core
+            .state_mut()?
+            .capture
+            .take()
+            .ok_or_else(|| anyhow!("no frame has been captured"))
     }
 
     pub fn invalidate(&self) {
-        match self.state().map(|state| state.size) {
+        match self.core.state().map(|state| state.size) {
             Ok(size) => self.invalidate_rect(size.to_rect()),
             Err(err) => log::error!("Window::invalidate - failed to get size: {}", err),
         }
@@ -478,9 +1099,18 @@ impl Window {
     }
 
     pub fn add_invalid_rect(&self, rect: Rect) -> Result<(), AnyError> {
-        let scale = self.state()?.scale;
+        let (scale, size) = {
+            let state = self.core.state()?;
+            (state.scale, state.size)
+        };
+        // Damage outside of the window would only grow the clip region. Rects that are fully
+        // outside of it are dropped here, expanding them would give them a pixel of area.
+        let rect = rect.intersect(size.to_rect());
+        if rect.area() <= 0.0 {
+            return Ok(());
+        }
         let rect = rect.to_px(scale).expand().to_dp(scale);
-        self.state_mut()?.invalid.add_rect(rect);
+        self.core.state_mut()?.invalid.add_rect(rect);
         Ok(())
     }
 }
@@ -488,82 +1118,145 @@ impl Window {
 #[derive(Clone, Default)]
 pub struct WindowHandle(Weak<Window>);
 
-/// A handle that can get used to schedule an idle handler. Note that
-/// this handle can be cloned and sent between threads.
-#[derive(Clone)]
-pub struct IdleHandle {
-    queue: Arc<Mutex<Vec<IdleKind>>>,
-    // TODO why there was file descriptor x11, what's the purpose of it
-    // note that it's created in application.rs also
-    //    pipe: RawFd,
+unsafe impl HasRawWindowHandle for WindowHandle {
+    fn raw_window_handle(&self) -> RawWindowHandle {
+        match self.0.upgrade().and_then(|w| w.raw_handle.get()) {
+            Some(handle) => handle,
+            None => {
+                log::error!("Window has no glutin window yet or has been dropped");
+                empty_raw_window_handle()
+            }
+        }
+    }
 }
 
-pub(crate) enum IdleKind {
-    Callback(Box<dyn IdleCallback>),
-    Token(IdleToken),
-    _Redraw,
+#[cfg(target_os = "macos")]
+fn empty_raw_window_handle() -> RawWindowHandle {
+    RawWindowHandle::MacOS(raw_window_handle::macos::MacOSHandle::empty())
 }
 
-impl IdleHandle {
-    fn wake(&self) {
-        //        loop {
-        //            match nix::unistd::write(self.pipe, &[0]) {
-        //                Err(nix::Error::Sys(nix::errno::Errno::EINTR)) => {}
-        //                Err(nix::Error::Sys(nix::errno::Errno::EAGAIN)) => {}
-        //                Err(e) => {
-        //                    log::error!("Failed to write to idle pipe: {}", e);
-        //                    break;
-        //                }
-        //                Ok(_) => {
-        //                    break;
-        //                }
-        //            }
-        //        }
-    }
-
-    pub(crate) fn _schedule_redraw(&self) {
-        self.queue.lock().unwrap().push(IdleKind::_Redraw);
-        self.wake();
-    }
-
-    pub fn add_idle_callback<F>(&self, callback: F)
-    where
-        F: FnOnce(&dyn Any) + Send + 'static,
-    {
-        self.queue
-            .lock()
-            .unwrap()
-            .push(IdleKind::Callback(Box::new(callback)));
-        self.wake();
-    }
-
-    pub fn add_idle_token(&self, token: IdleToken) {
-        self.queue.lock().unwrap().push(IdleKind::Token(token));
-        self.wake();
-    }
+#[cfg(all(target_os = "linux", feature = "winit_x11"))]
+fn empty_raw_window_handle() -> RawWindowHandle {
+    RawWindowHandle::Xlib(raw_window_handle::unix::XlibHandle::empty())
+}
+
+#[cfg(all(target_os = "linux", not(feature = "winit_x11")))]
+fn empty_raw_window_handle() -> RawWindowHandle {
+    RawWindowHandle::Wayland(raw_window_handle::unix::WaylandHandle::empty())
 }
 
 pub(crate) struct WindowState {
     pub(crate) scale: Scale,
-    _area: Cell<ScaledArea>,
-    _idle_queue: Arc<Mutex<Vec<IdleKind>>>,
     size: Size,
+    title: String,
+    resizable: bool,
+    /// The last requested or observed window state.
+    window_state: window::WindowState,
+    /// The size to restore when leaving fullscreen, `None` if the window isn't fullscreen.
+    windowed_size: Option<Size>,
+    gl_options: GlOptions,
+    /// Whether the window is kept above other windows.
+    always_on_top: bool,
+    /// The taskbar and titlebar icon, the platform default if `None`.
+    icon: Option<Icon>,
+    /// The size limits of the drawing area, in display points.
+    min_size: Option<Size>,
+    max_size: Option<Size>,
+    /// The color the damaged region is cleared to before painting.
+    background: Option<Color>,
+    /// The outer position of the window in pixels, kept up to date from moves.
+    /// The platform picks one at creation if `None`.
+    position: Option<Point>,
+    /// Whether `position` should be picked to center the window on the primary monitor.
+    center_on_primary: bool,
+    /// Whether the window has been closed and the handler destroyed.
+    closed: bool,
+    /// The last known cursor position, in display points.
+    mouse_pos: Point,
+    /// Whether the cursor is over the window.
+    hovered: bool,
+    /// The custom cursor drawn at `mouse_pos`, if any.
+    cursor: Option<CustomCursor>,
+    /// The system cursor last asked for, `None` while a custom cursor is drawn instead.
+    cursor_icon: Option<CursorIcon>,
+    /// Whether the cursor is shown, see `set_cursor_visible`.
+    cursor_visible: bool,
+    /// The mouse buttons that are currently held down.
+    buttons: MouseButtons,
+    /// The keys that are currently held down, to tell repeats apart.
+    keys_down: HashSet<Code>,
+    /// Whether this is the focused window.
+    has_focus: bool,
+    /// The modifiers reported by the last `ModifiersChanged` event.
+    mods: Modifiers,
+    click_counter: ClickCounter,
+    /// The button of the last press, used to restart the click count.
+    last_click_button: MouseButton,
+    /// The wheel delta of one line of mouse wheel scrolling.
+    scroll_line_delta: f64,
+    /// Whether the event loop should redraw the window on its next frame.
+    wants_anim_frame: bool,
     invalid: Region,
-    prev_invalid: Region,
+    /// The invalid regions of the frames still shown in the other buffers.
+    damage: DamageHistory,
+    /// Whether the pixels of the next frame should be read back.
+    capture_requested: bool,
+    /// The pixels and pixel size of the last captured frame, until they are taken.
+    capture: Option<(Vec<u8>, Size)>,
+    /// Whether the pointer was captured with `capture_pointer`.
+    pointer_captured: bool,
+}
+
+impl WindowState {
+    /// The system cursor to show, `None` while it is hidden or a custom cursor is drawn.
+    fn shown_cursor_icon(&self) -> Option<CursorIcon> {
+        self.cursor_icon.filter(|_| self.cursor_visible)
+    }
+}
+
+/// A cursor image, drawn by the window on top of its content.
+///
+/// glutin can only show the system cursors, so the system cursor is hidden while
+/// a custom one is set.
+#[derive(Clone)]
+pub struct CustomCursor(Arc<CursorDesc>);
+
+impl PartialEq for CustomCursor {
+    fn eq(&self, other: &CustomCursor) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
 }
 
-// TODO: support custom cursors
-#[derive(Clone, PartialEq)]
-pub struct CustomCursor;
+impl CustomCursor {
+    /// The area covered by the cursor with its hot spot at `pos`.
+    ///
+    /// Each image pixel covers one display point.
+    fn rect(&self, pos: Point) -> Rect {
+        let size = Size::new(self.0.image.width() as f64, self.0.image.height() as f64);
+        Rect::from_origin_size(pos - self.0.hot.to_vec2(), size)
+    }
+}
 
 /// Builder abstraction for creating new windows.
 pub(crate) struct WindowBuilder {
     app: Application,
     handler: Option<Box<dyn WinHandler>>,
-    _title: String,
+    title: String,
+    resizable: bool,
     _cursor: Cursor,
-    _menu: Option<Menu>,
+    menu: Option<Menu>,
     size: Size,
+    min_size: Option<Size>,
+    max_size: Option<Size>,
+    position: Option<Point>,
+    center_on_primary: bool,
+    background: Option<Color>,
+    buffer_count: usize,
+    window_state: window::WindowState,
+    fullscreen: bool,
+    always_on_top: bool,
+    icon: Option<Icon>,
+    gl_options: GlOptions,
 }
 
 impl WindowBuilder {
@@ -571,10 +1264,22 @@ impl WindowBuilder {
         WindowBuilder {
             app,
             handler: None,
-            _title: String::new(),
+            title: String::new(),
+            resizable: true,
             _cursor: Cursor::Arrow,
-            _menu: None,
+            menu: None,
             size: Size::new(800., 600.),
+            min_size: None,
+            max_size: None,
+            position: None,
+            center_on_primary: false,
+            background: None,
+            buffer_count: DEFAULT_BUFFER_COUNT,
+            window_state: window::WindowState::RESTORED,
+            fullscreen: false,
+            always_on_top: false,
+            icon: None,
+            gl_options: GlOptions::default(),
         }
     }
 
@@ -587,55 +1292,162 @@ impl WindowBuilder {
         self.size = size;
     }
 
-    pub fn set_min_size(&mut self, _: Size) {
-        // Ignored
+    pub fn set_min_size(&mut self, size: Size) {
+        self.min_size = Some(size);
     }
 
-    pub fn resizable(&mut self, _resizable: bool) {
-        // Ignored
+    pub fn set_max_size(&mut self, size: Size) {
+        self.max_size = Some(size);
+    }
+
+    pub fn resizable(&mut self, resizable: bool) {
+        self.resizable = resizable;
     }
 
     pub fn show_titlebar(&mut self, _show_titlebar: bool) {
         // Ignored
     }
 
-    pub fn set_position(&mut self, _position: Point) {
-        // Ignored
+    pub fn set_position(&mut self, position: Point) {
+        self.position = Some(position);
+        self.center_on_primary = false;
     }
 
-    pub fn set_window_state(&self, _state: window::WindowState) {
-        // Ignored
+    /// Center the window on the work area of the primary monitor, once it is created.
+    pub fn center_on_primary_monitor(&mut self) {
+        self.center_on_primary = true;
+    }
+
+    pub fn set_window_state(&mut self, state: window::WindowState) {
+        self.window_state = state;
     }
 
-    pub fn set_level(&mut self, _level: WindowLevel) {
-        // ignored
+    pub fn set_fullscreen(&mut self, fullscreen: bool) {
+        self.fullscreen = fullscreen;
+    }
+
+    /// Request multisampling with `samples` samples per pixel, 0 disables it.
+    pub fn set_sample_count(&mut self, samples: u16) {
+        // glutin only accepts powers of two.
+        self.gl_options.sample_count = if samples == 0 || samples.is_power_of_two() {
+            samples
+        } else {
+            log::warn!(
+                "{} is not a valid sample count, using the next power of two",
+                samples
+            );
+            samples.next_power_of_two()
+        };
+    }
+
+    /// Make the background of the window transparent where nothing has been painted.
+    pub fn set_transparent(&mut self, transparent: bool) {
+        self.gl_options.transparent = transparent;
+    }
+
+    /// Clear the damaged region to `color` before the handler paints.
+    pub fn set_background_color(&mut self, color: Color) {
+        self.background = Some(color);
+    }
+
+    /// Set how many buffers the swapchain cycles through, to know how much to redraw.
+    pub fn set_buffer_count(&mut self, count: usize) {
+        self.buffer_count = count;
+    }
+
+    /// Sync buffer swaps to the display, vsync is disabled if the GL driver doesn't support it.
+    pub fn set_vsync(&mut self, vsync: bool) {
+        self.gl_options.vsync = vsync;
+    }
+
+    /// Request a stencil buffer with `bits` bits per pixel, 0 for none.
+    pub fn set_stencil_buffer(&mut self, bits: u8) {
+        self.gl_options.stencil_bits = bits;
+    }
+
+    /// Request a depth buffer with `bits` bits per pixel, 0 for none.
+    pub fn set_depth_buffer(&mut self, bits: u8) {
+        self.gl_options.depth_bits = bits;
+    }
+
+    pub fn set_level(&mut self, level: WindowLevel) {
+        self.always_on_top = is_always_on_top(level);
     }
 
     pub fn set_title<S: Into<String>>(&mut self, title: S) {
-        self._title = title.into();
+        self.title = title.into();
+    }
+
+    /// Set the icon from RGBA8 pixels, in rows from top to bottom.
+    pub fn set_icon(&mut self, rgba: Vec<u8>, width: u32, height: u32) {
+        match Icon::from_rgba(rgba, width, height) {
+            Ok(icon) => self.icon = Some(icon),
+            Err(err) => log::error!("Invalid {}x{} window icon: {}", width, height, err),
+        }
     }
 
     pub fn set_menu(&mut self, menu: Menu) {
-        self._menu = Some(menu);
+        self.menu = Some(menu);
     }
 
     pub fn build(self) -> Result<WindowHandle, Error> {
         let handler = self.handler.unwrap();
+        let size = clamp_size(self.size, self.min_size, self.max_size);
         // TODO
         let state = WindowState {
-            scale: Scale::new(2., 2.),
-            _area: Cell::new(ScaledArea::default()),
-            _idle_queue: Default::default(),
-            size: self.size,
+            // The glutin window is only created once the event loop picks this window up,
+            // which sets the scale of the monitor it opened on.
+            scale: Scale::default(),
+            size,
+            title: self.title,
+            resizable: self.resizable,
+            window_state: self.window_state,
+            windowed_size: if self.fullscreen { Some(size) } else { None },
+            gl_options: self.gl_options,
+            always_on_top: self.always_on_top,
+            icon: self.icon,
+            min_size: self.min_size,
+            max_size: self.max_size,
+            background: self.background,
+            position: self.position,
+            center_on_primary: self.center_on_primary,
+            closed: false,
+            mouse_pos: Point::ZERO,
+            hovered: false,
+            cursor: None,
+            cursor_icon: Some(CursorIcon::Default),
+            cursor_visible: true,
+            buttons: MouseButtons::new(),
+            keys_down: HashSet::new(),
+            has_focus: false,
+            mods: Modifiers::empty(),
+            click_counter: ClickCounter::default(),
+            last_click_button: MouseButton::None,
+            scroll_line_delta: SCROLL_LINE_DELTA,
+            // The first frame is always drawn.
+            wants_anim_frame: true,
             invalid: Region::EMPTY,
-            prev_invalid: Region::EMPTY,
+            damage: DamageHistory::new(self.buffer_count),
+            capture_requested: false,
+            capture: None,
+            pointer_captured: false,
         };
+        let waker = self.app.waker();
+        let waker: Waker = Arc::new(move || waker.wake());
         let window = Rc::new(Window {
-            handler: RefCell::new(handler),
-            window_state: RefCell::new(state),
-            idle_queue: Arc::new(Mutex::new(Vec::new())),
-            timer_queue: Mutex::new(BinaryHeap::new()),
+            core: WindowCore::new(handler, state, Some(waker)),
+            deferred_queue: RefCell::new(Vec::new()),
+            menu: RefCell::new(None),
+            context_menu: RefCell::new(None),
+            raw_handle: Cell::new(None),
         });
+        if let Some(menu) = self.menu {
+            window.defer(DeferredOp::SetMenu(menu));
+        }
+        // Maximized windows are created that way, glutin can't create minimized ones.
+        if self.window_state == window::WindowState::MINIMIZED {
+            window.defer(DeferredOp::SetWindowState(self.window_state));
+        }
 
         let handle = WindowHandle(Rc::downgrade(&window));
         window.connect(handle.clone()).unwrap();
@@ -647,43 +1459,122 @@ impl WindowBuilder {
 impl WindowHandle {
     pub fn show(&self) {}
 
-    pub fn resizable(&self, _resizable: bool) {
-        log::warn!("resizable unimplemented for web");
+    pub fn resizable(&self, resizable: bool) {
+        if let Some(w) = self.0.upgrade() {
+            match w.core.state_mut() {
+                Ok(mut state) => state.resizable = resizable,
+                Err(err) => log::error!("WindowHandle::resizable - {}", err),
+            }
+            w.defer(DeferredOp::SetResizable(resizable));
+        }
     }
 
     pub fn show_titlebar(&self, _show_titlebar: bool) {
         log::warn!("show_titlebar unimplemented for web");
     }
 
-    pub fn set_position(&self, _position: Point) {
-        log::warn!("WindowHandle::set_position unimplemented for web");
+    pub fn set_position(&self, position: Point) {
+        if let Some(w) = self.0.upgrade() {
+            // Not every platform reports moves, so don't wait for one.
+            match w.core.state_mut() {
+                Ok(mut state) => state.position = Some(position),
+                Err(err) => log::error!("WindowHandle::set_position - {}", err),
+            }
+            w.defer(DeferredOp::SetPosition(position));
+        }
+    }
+
+    pub fn set_opacity(&self, opacity: f64) {
+        if let Some(w) = self.0.upgrade() {
+            w.defer(DeferredOp::SetOpacity(opacity.max(0.).min(1.)));
+        }
     }
 
-    pub fn set_level(&self, _level: WindowLevel) {
-        log::warn!("WindowHandle::set_level  is currently unimplemented for web.");
+    pub fn set_ime_position(&self, position: Point) {
+        if let Some(w) = self.0.upgrade() {
+            w.defer(DeferredOp::SetImePosition(position));
+        }
+    }
+
+    pub fn set_level(&self, level: WindowLevel) {
+        if let Some(w) = self.0.upgrade() {
+            let on_top = is_always_on_top(level);
+            match w.core.state_mut() {
+                Ok(mut state) => state.always_on_top = on_top,
+                Err(err) => log::error!("WindowHandle::set_level - {}", err),
+            }
+            w.defer(DeferredOp::SetAlwaysOnTop(on_top));
+        }
     }
 
     pub fn get_position(&self) -> Point {
-        log::warn!("WindowHandle::get_position unimplemented for web.");
-        Point::new(0.0, 0.0)
+        self.0
+            .upgrade()
+            .and_then(|w| w.core.state().ok().and_then(|state| state.position))
+            .unwrap_or(Point::ZERO)
     }
 
-    pub fn set_size(&self, _size: Size) {
-        log::warn!("WindowHandle::set_size unimplemented for web.");
+    pub fn set_size(&self, size: Size) {
+        if let Some(w) = self.0.upgrade() {
+            let size = match w.core.state() {
+                Ok(state) => clamp_size(size, state.min_size, state.max_size),
+                Err(_) => size,
+            };
+            w.defer(DeferredOp::SetSize(size));
+        }
+    }
+
+    pub fn set_min_size(&self, size: Size) {
+        if let Some(w) = self.0.upgrade() {
+            match w.core.state_mut() {
+                Ok(mut state) => state.min_size = Some(size),
+                Err(err) => log::error!("WindowHandle::set_min_size - {}", err),
+            }
+            w.defer(DeferredOp::SetMinSize(size));
+        }
+    }
+
+    pub fn set_max_size(&self, size: Size) {
+        if let Some(w) = self.0.upgrade() {
+            match w.core.state_mut() {
+                Ok(mut state) => state.max_size = Some(size),
+                Err(err) => log::error!("WindowHandle::set_max_size - {}", err),
+            }
+            w.defer(DeferredOp::SetMaxSize(size));
+        }
     }
 
     pub fn get_size(&self) -> Size {
-        log::warn!("WindowHandle::get_size unimplemented for web.");
-        Size::new(0.0, 0.0)
+        if let Some(w) = self.0.upgrade() {
+            match w.size() {
+                Ok(size) => return size,
+                Err(err) => log::error!("WindowHandle::get_size - {}", err),
+            }
+        }
+        Size::ZERO
     }
 
-    pub fn set_window_state(&self, _state: window::WindowState) {
-        log::warn!("WindowHandle::set_window_state unimplemented for web.");
+    pub fn set_window_state(&self, state: window::WindowState) {
+        if let Some(w) = self.0.upgrade() {
+            match w.core.state_mut() {
+                Ok(mut window_state) => window_state.window_state = state,
+                Err(err) => log::error!("WindowHandle::set_window_state - {}", err),
+            }
+            w.defer(DeferredOp::SetWindowState(state));
+        }
+    }
+
+    pub fn set_fullscreen(&self, fullscreen: bool) {
+        if let Some(w) = self.0.upgrade() {
+            w.defer(DeferredOp::SetFullscreen(fullscreen));
+        }
     }
 
     pub fn get_window_state(&self) -> window::WindowState {
-        log::warn!("WindowHandle::get_window_state unimplemented for web.");
-        window::WindowState::RESTORED
+        self.0
+            .upgrade()
+            .and_then(|w| w.core.state().ok().map(|state| state.window_state))
+            .unwrap_or(window::WindowState::RESTORED)
     }
 
     pub fn handle_titlebar(&self, _val: bool) {
@@ -691,14 +1582,54 @@ impl WindowHandle {
     }
 
     pub fn close(&self) {
-        // TODO
+        if let Some(w) = self.0.upgrade() {
+            w.defer(DeferredOp::Close);
+        }
     }
 
     pub fn bring_to_front_and_focus(&self) {
-        log::warn!("bring_to_frontand_focus unimplemented for web");
+        if let Some(w) = self.0.upgrade() {
+            w.defer(DeferredOp::BringToFront);
+        }
     }
 
-    pub fn request_anim_frame(&self) {}
+    pub fn request_anim_frame(&self) {
+        if let Some(w) = self.0.upgrade() {
+            w.request_anim_frame();
+        }
+    }
+
+    pub fn set_scroll_line_delta(&self, delta: f64) {
+        if let Some(w) = self.0.upgrade() {
+            match w.core.state_mut() {
+                Ok(mut state) => state.scroll_line_delta = delta,
+                Err(err) => log::error!("WindowHandle::set_scroll_line_delta - {}", err),
+            }
+        }
+    }
+
+    pub fn request_capture(&self) {
+        if let Some(w) = self.0.upgrade() {
+            w.request_capture();
+        }
+    }
+
+    pub fn capture(&self) -> Result<(Vec<u8>, Size), ShellError> {
+        let window = self.0.upgrade().ok_or(ShellError::WindowDropped)?;
+        Ok(window.capture()?)
+    }
+
+    pub fn capture_pointer(&self) {
+        if let Some(w) = self.0.upgrade() {
+            w.capture_pointer();
+        }
+    }
+
+    pub fn release_pointer(&self) {
+        if let Some(w) = self.0.upgrade() {
+            w.release_pointer();
+        }
+    }
 
     pub fn invalidate_rect(&self, rect: Rect) {
         if let Some(window) = self.0.upgrade() {
@@ -722,78 +1653,132 @@ impl WindowHandle {
     }
 
     pub fn request_timer(&self, deadline: Instant) -> TimerToken {
+        self.0
+            .upgrade()
+            .map_or(TimerToken::INVALID, |w| w.core.request_timer(deadline))
+    }
+
+    pub fn request_interval_timer(&self, deadline: Instant, interval: Duration) -> TimerToken {
+        self.0.upgrade().map_or(TimerToken::INVALID, |w| {
+            w.core.request_interval_timer(deadline, interval)
+        })
+    }
+
+    pub fn cancel_timer(&self, token: TimerToken) {
         if let Some(w) = self.0.upgrade() {
-            let timer = Timer::new(deadline);
-            w.timer_queue.lock().unwrap().push(timer);
-            timer.token()
-        } else {
-            TimerToken::INVALID
+            w.core.cancel_timer(token);
+        }
+    }
+
+    pub fn set_cursor(&mut self, cursor: &Cursor) {
+        if let Some(w) = self.0.upgrade() {
+            w.set_cursor(cursor);
         }
     }
 
-    pub fn set_cursor(&mut self, _cursor: &Cursor) {}
+    pub fn set_cursor_visible(&self, visible: bool) {
+        if let Some(w) = self.0.upgrade() {
+            w.set_cursor_visible(visible);
+        }
+    }
 
-    pub fn make_cursor(&self, _cursor_desc: &CursorDesc) -> Option<Cursor> {
-        log::warn!("Custom cursors are not yet supported in the web backend");
-        None
+    pub fn make_cursor(&self, cursor_desc: &CursorDesc) -> Option<Cursor> {
+        Some(Cursor::Custom(CustomCursor(Arc::new(cursor_desc.clone()))))
     }
 
-    pub fn open_file(&mut self, _options: FileDialogOptions) -> Option<FileDialogToken> {
-        log::warn!("open_file is currently unimplemented for web.");
-        None
+    pub fn open_file(&mut self, options: FileDialogOptions) -> Option<FileDialogToken> {
+        if let Some(w) = self.0.upgrade() {
+            let token = FileDialogToken::next();
+            w.defer(DeferredOp::Open(options, token));
+            Some(token)
+        } else {
+            None
+        }
     }
 
-    pub fn save_as(&mut self, _options: FileDialogOptions) -> Option<FileDialogToken> {
-        log::warn!("save_as is currently unimplemented for web.");
-        None
+    pub fn save_as(&mut self, options: FileDialogOptions) -> Option<FileDialogToken> {
+        if let Some(w) = self.0.upgrade() {
+            let token = FileDialogToken::next();
+            w.defer(DeferredOp::SaveAs(options, token));
+            Some(token)
+        } else {
+            None
+        }
     }
 
     pub fn file_dialog(
         &self,
-        _ty: FileDialogType,
-        _options: FileDialogOptions,
+        ty: FileDialogType,
+        options: FileDialogOptions,
     ) -> Result<OsString, ShellError> {
-        Err(ShellError::Platform(Error::Unimplemented))
+        dialog::get_file_dialog_paths(ty, options)
+            .and_then(|paths| paths.into_iter().next())
+            .map(PathBuf::into_os_string)
+            .ok_or_else(|| anyhow!("the file dialog was cancelled").into())
     }
 
     /// Get a handle that can be used to schedule an idle task.
     pub fn get_idle_handle(&self) -> Option<IdleHandle> {
-        if let Some(w) = self.0.upgrade() {
-            Some(IdleHandle {
-                queue: Arc::clone(&w.idle_queue),
-                //pipe: w.idle_pipe,
-            })
-        } else {
-            None
-        }
+        self.0.upgrade().map(|w| w.core.idle_handle())
     }
 
     /// Get the `Scale` of the window.
     pub fn get_scale(&self) -> Result<Scale, ShellError> {
-        unimplemented!();
-        //Ok(self
-        //    .0
-        //    .upgrade()
-        //    .ok_or(ShellError::WindowDropped)?
-        //    .scale
-        //    .get())
+        let window = self.0.upgrade().ok_or(ShellError::WindowDropped)?;
+        let scale = window.core.state()?.scale;
+        Ok(scale)
     }
 
-    pub fn set_menu(&self, _menu: Menu) {
-        log::warn!("set_menu unimplemented for web");
+    pub fn query_modifiers(&self) -> Modifiers {
+        self.0.upgrade().map(|w| w.modifiers()).unwrap_or_default()
     }
 
-    pub fn show_context_menu(&self, _menu: Menu, _pos: Point) {
-        log::warn!("show_context_menu unimplemented for web");
+    pub fn is_key_down(&self, code: Code) -> bool {
+        self.0.upgrade().map_or(false, |w| w.is_key_down(code))
     }
 
-    pub fn set_title(&self, _title: impl Into<String>) {
-        unimplemented!();
-        //log::warn!("set_title is not implemented");
+    pub fn buffer_count(&self) -> usize {
+        self.0
+            .upgrade()
+            .and_then(|w| w.core.state().ok().map(|state| state.damage.buffer_count()))
+            .unwrap_or(DEFAULT_BUFFER_COUNT)
     }
-}
 
-unsafe impl Send for IdleHandle {}
+    pub fn set_menu(&self, menu: Menu) {
+        if let Some(w) = self.0.upgrade() {
+            w.defer(DeferredOp::SetMenu(menu));
+        }
+    }
+
+    pub fn show_context_menu(&self, menu: Menu, pos: Point) {
+        if let Some(w) = self.0.upgrade() {
+            w.defer(DeferredOp::ShowContextMenu(menu, pos));
+        }
+    }
+
+    pub fn set_menu_item_enabled(&self, id: u32, enabled: bool) {
+        if let Some(w) = self.0.upgrade() {
+            w.defer(DeferredOp::SetMenuItemEnabled(id, enabled));
+        }
+    }
+
+    pub fn set_menu_item_checked(&self, id: u32, checked: bool) {
+        if let Some(w) = self.0.upgrade() {
+            w.defer(DeferredOp::SetMenuItemChecked(id, checked));
+        }
+    }
+
+    pub fn set_title(&self, title: impl Into<String>) {
+        if let Some(w) = self.0.upgrade() {
+            let title = title.into();
+            match w.core.state_mut() {
+                Ok(mut state) => state.title = title.clone(),
+                Err(err) => log::error!("WindowHandle::set_title - {}", err),
+            }
+            w.defer(DeferredOp::SetTitle(title));
+        }
+    }
+}
 
 fn _mouse_button(button: i16) -> Option<MouseButton> {
     match button {
@@ -825,3 +1810,647 @@ fn _mouse_buttons(mask: u16) -> MouseButtons {
     }
     buttons
 }
+
+/// The position, in pixels, of a window of `size` display points centered on the work area
+/// of `monitor`. The top left corner is kept on the monitor.
+fn centered_position(size: Size, monitor: &Monitor) -> Point {
+    let work = monitor.virtual_work_rect();
+    let size = size.to_px(monitor.scale().unwrap_or_default());
+    let origin = work.center() - size.to_vec2() / 2.;
+    Point::new(origin.x.max(work.x0), origin.y.max(work.y0)).round()
+}
+
+/// Limit `size` to the minimum and maximum sizes, the minimum wins if they overlap.
+fn clamp_size(size: Size, min: Option<Size>, max: Option<Size>) -> Size {
+    let size = match max {
+        Some(max) => Size::new(size.width.min(max.width), size.height.min(max.height)),
+        None => size,
+    };
+    match min {
+        Some(min) => Size::new(size.width.max(min.width), size.height.max(min.height)),
+        None => size,
+    }
+}
+
+/// Ask the window manager to raise and focus `window`, returns `false` if we can't.
+#[cfg(all(target_os = "linux", feature = "winit_x11"))]
+fn activate_window(window: &glutin::window::Window) -> bool {
+    use glutin::platform::unix::WindowExtUnix;
+    use x11_dl::xlib;
+
+    let (display, xwindow) = match (window.xlib_display(), window.xlib_window()) {
+        (Some(display), Some(xwindow)) => (display as *mut xlib::Display, xwindow),
+        _ => return false,
+    };
+    let xlib = match xlib::Xlib::open() {
+        Ok(xlib) => xlib,
+        Err(err) => {
+            log::warn!("Failed to load Xlib: {}", err);
+            return false;
+        }
+    };
+    // See _NET_ACTIVE_WINDOW in the Extended Window Manager Hints.
+    unsafe {
+        let mut message: xlib::XClientMessageEvent = std::mem::zeroed();
+        message.type_ = xlib::ClientMessage;
+        message.window = xwindow;
+        message.message_type = (xlib.XInternAtom)(
+            display,
+            b"_NET_ACTIVE_WINDOW\0".as_ptr() as *const _,
+            xlib::False,
+        );
+        message.format = 32;
+        // The request comes from a normal application.
+        message.data.set_long(0, 1);
+        message.data.set_long(1, xlib::CurrentTime as _);
+        let mut event = xlib::XEvent::from(message);
+        (xlib.XSendEvent)(
+            display,
+            (xlib.XDefaultRootWindow)(display),
+            xlib::False,
+            xlib::SubstructureRedirectMask | xlib::SubstructureNotifyMask,
+            &mut event,
+        );
+        (xlib.XFlush)(display);
+    }
+    true
+}
+
+/// Ask the window manager to raise and focus `window`, returns `false` if we can't.
+#[cfg(not(all(target_os = "linux", feature = "winit_x11")))]
+fn activate_window(_window: &glutin::window::Window) -> bool {
+    false
+}
+
+/// Set the opacity of the whole `window`, returns `false` if we can't.
+#[cfg(all(target_os = "linux", feature = "winit_x11"))]
+fn set_window_opacity(window: &glutin::window::Window, opacity: f64) -> bool {
+    use glutin::platform::unix::WindowExtUnix;
+    use x11_dl::xlib;
+
+    let (display, xwindow) = match (window.xlib_display(), window.xlib_window()) {
+        (Some(display), Some(xwindow)) => (display as *mut xlib::Display, xwindow),
+        _ => return false,
+    };
+    let xlib = match xlib::Xlib::open() {
+        Ok(xlib) => xlib,
+        Err(err) => {
+            log::warn!("Failed to load Xlib: {}", err);
+            return false;
+        }
+    };
+    // Compositing managers read _NET_WM_WINDOW_OPACITY, where 0xffffffff is opaque.
+    unsafe {
+        let atom = (xlib.XInternAtom)(
+            display,
+            b"_NET_WM_WINDOW_OPACITY\0".as_ptr() as *const _,
+            xlib::False,
+        );
+        if opacity >= 1. {
+            (xlib.XDeleteProperty)(display, xwindow, atom);
+        } else {
+            let value = (opacity * u32::MAX as f64) as std::os::raw::c_ulong;
+            (xlib.XChangeProperty)(
+                display,
+                xwindow,
+                atom,
+                xlib::XA_CARDINAL,
+                32,
+                xlib::PropModeReplace,
+                &value as *const std::os::raw::c_ulong as *const u8,
+                1,
+            );
+        }
+        (xlib.XFlush)(display);
+    }
+    true
+}
+
+/// Set the opacity of the whole `window`, returns `false` if we can't.
+#[cfg(not(all(target_os = "linux", feature = "winit_x11")))]
+fn set_window_opacity(_window: &glutin::window::Window, _opacity: f64) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use glutin::event::ElementState;
+
+    use crate::hotkey::HotKey;
+    use crate::keyboard::{KbKey, KeyEvent};
+
+    #[derive(Default)]
+    struct TestHandler {
+        keys: Rc<RefCell<Vec<KeyEvent>>>,
+        timers: Rc<RefCell<Vec<TimerToken>>>,
+        commands: Rc<RefCell<Vec<u32>>>,
+        positions: Rc<RefCell<Vec<Point>>>,
+        wheels: Rc<RefCell<Vec<MouseEvent>>>,
+        raw_motions: Rc<RefCell<Vec<Vec2>>>,
+        destroyed: Rc<Cell<bool>>,
+        /// Quit this application when an idle token arrives.
+        quit_on_idle: Option<Application>,
+    }
+
+    impl WinHandler for TestHandler {
+        fn connect(&mut self, _handle: &window::WindowHandle) {}
+
+        fn prepare_paint(&mut self) {}
+
+        fn paint(&mut self, _piet: &mut Piet, _invalid: &Region) {}
+
+        fn key_down(&mut self, event: KeyEvent) -> bool {
+            self.keys.borrow_mut().push(event);
+            true
+        }
+
+        fn timer(&mut self, token: TimerToken) {
+            self.timers.borrow_mut().push(token);
+        }
+
+        fn command(&mut self, id: u32) {
+            self.commands.borrow_mut().push(id);
+        }
+
+        fn position(&mut self, position: Point) {
+            self.positions.borrow_mut().push(position);
+        }
+
+        fn wheel(&mut self, event: &MouseEvent) {
+            self.wheels.borrow_mut().push(event.clone());
+        }
+
+        fn raw_mouse_motion(&mut self, delta: Vec2) {
+            self.raw_motions.borrow_mut().push(delta);
+        }
+
+        fn idle(&mut self, _token: IdleToken) {
+            if let Some(app) = &self.quit_on_idle {
+                app.quit();
+            }
+        }
+
+        fn destroy(&mut self) {
+            self.destroyed.set(true);
+        }
+
+        fn as_any(&mut self) -> &mut dyn Any {
+            self
+        }
+    }
+
+    fn make_window(handler: TestHandler) -> Rc<Window> {
+        let app = Application::new().unwrap();
+        let mut builder = WindowBuilder::new(app.clone());
+        builder.set_handler(Box::new(handler));
+        // `app` keeps the window alive until we have upgraded the handle.
+        let handle = builder.build().unwrap();
+        handle.0.upgrade().unwrap()
+    }
+
+    #[test]
+    fn key_press_without_virtual_keycode() {
+        let handler = TestHandler::default();
+        let keys = handler.keys.clone();
+        let window = make_window(handler);
+
+        #[allow(deprecated)]
+        let input = KeyboardInput {
+            scancode: 0,
+            state: ElementState::Pressed,
+            virtual_keycode: None,
+            modifiers: Default::default(),
+        };
+        window.handle_key_press(input);
+
+        let keys = keys.borrow();
+        assert_eq!(keys.len(), 1);
+        assert_eq!(keys[0].code, Code::Unidentified);
+    }
+
+    #[test]
+    fn held_keys_are_tracked() {
+        let handler = TestHandler::default();
+        let keys = handler.keys.clone();
+        let window = make_window(handler);
+
+        #[allow(deprecated)]
+        let mut input = KeyboardInput {
+            scancode: 0,
+            state: ElementState::Pressed,
+            virtual_keycode: Some(glutin::event::VirtualKeyCode::A),
+            modifiers: Default::default(),
+        };
+        window.handle_key_press(input);
+        window.handle_key_press(input);
+        assert!(window.is_key_down(Code::KeyA));
+        let repeats: Vec<bool> = keys.borrow().iter().map(|key| key.repeat).collect();
+        assert_eq!(repeats, vec![false, true]);
+
+        input.state = ElementState::Released;
+        window.handle_key_press(input);
+        assert!(!window.is_key_down(Code::KeyA));
+
+        input.state = ElementState::Pressed;
+        window.handle_key_press(input);
+        window.handle_focus(false);
+        assert!(!window.is_key_down(Code::KeyA));
+    }
+
+    #[test]
+    fn menu_activation_reaches_handler() {
+        let handler = TestHandler::default();
+        let keys = handler.keys.clone();
+        let commands = handler.commands.clone();
+        let window = make_window(handler);
+
+        let mut file = Menu::new();
+        file.add_item(1, "Open", None, true, false);
+        file.add_item(
+            2,
+            "Quit",
+            Some(&HotKey::new(None, KbKey::Escape)),
+            true,
+            false,
+        );
+        let mut menu = Menu::new();
+        menu.add_dropdown(file, "File", true);
+        *window.menu.borrow_mut() = Some(menu);
+
+        assert!(window.has_menu_item(1));
+        window.handle_command(1);
+        // Commands are delivered from the idle queue.
+        assert!(commands.borrow().is_empty());
+        window.run_idle();
+        assert_eq!(*commands.borrow(), vec![1]);
+
+        #[allow(deprecated)]
+        let input = KeyboardInput {
+            scancode: 0,
+            state: ElementState::Pressed,
+            virtual_keycode: Some(glutin::event::VirtualKeyCode::Escape),
+            modifiers: Default::default(),
+        };
+        window.handle_key_press(input);
+        window.run_idle();
+        assert_eq!(*commands.borrow(), vec![1, 2]);
+        assert!(keys.borrow().is_empty());
+    }
+
+    #[test]
+    fn disabled_menu_item_ignores_hotkey() {
+        let handler = TestHandler::default();
+        let keys = handler.keys.clone();
+        let commands = handler.commands.clone();
+        let window = make_window(handler);
+
+        let mut menu = Menu::new();
+        let hotkey = HotKey::new(None, KbKey::Escape);
+        menu.add_item(1, "Close", Some(&hotkey), true, false);
+        *window.menu.borrow_mut() = Some(menu);
+        window.update_menu_item(1, |menu| menu.set_item_enabled(1, false));
+
+        #[allow(deprecated)]
+        let input = KeyboardInput {
+            scancode: 0,
+            state: ElementState::Pressed,
+            virtual_keycode: Some(glutin::event::VirtualKeyCode::Escape),
+            modifiers: Default::default(),
+        };
+        window.handle_key_press(input);
+        window.run_idle();
+        assert!(commands.borrow().is_empty());
+        assert_eq!(keys.borrow().len(), 1);
+    }
+
+    #[test]
+    fn cursor_is_restored_on_enter() {
+        let window = make_window(TestHandler::default());
+        window.set_cursor(&Cursor::IBeam);
+        window.deferred_queue.borrow_mut().clear();
+
+        window.handle_cursor_entered();
+        let queue = window.deferred_queue.borrow();
+        assert!(matches!(
+            queue.as_slice(),
+            [DeferredOp::SetCursor(Some(CursorIcon::Text))]
+        ));
+    }
+
+    #[test]
+    fn hidden_cursor_stays_hidden() {
+        let window = make_window(TestHandler::default());
+        window.deferred_queue.borrow_mut().clear();
+        window.set_cursor_visible(false);
+        window.set_cursor(&Cursor::IBeam);
+        window.handle_cursor_entered();
+        {
+            let queue = window.deferred_queue.borrow();
+            assert!(queue
+                .iter()
+                .all(|op| matches!(op, DeferredOp::SetCursor(None))));
+        }
+
+        window.set_cursor_visible(true);
+        let queue = window.deferred_queue.borrow();
+        assert!(matches!(
+            queue.last(),
+            Some(DeferredOp::SetCursor(Some(CursorIcon::Text)))
+        ));
+    }
+
+    #[test]
+    fn position_follows_moves() {
+        let handler = TestHandler::default();
+        let positions = handler.positions.clone();
+        let window = make_window(handler);
+        let handle = WindowHandle(Rc::downgrade(&window));
+        assert_eq!(handle.get_position(), Point::ZERO);
+
+        window.handle_moved(PhysicalPosition::new(100, -20));
+        assert_eq!(handle.get_position(), Point::new(100., -20.));
+        assert_eq!(*positions.borrow(), [Point::new(100., -20.)]);
+
+        handle.set_position(Point::new(5., 6.));
+        assert_eq!(handle.get_position(), Point::new(5., 6.));
+        assert!(matches!(
+            window.deferred_queue.borrow().last(),
+            Some(DeferredOp::SetPosition(_))
+        ));
+    }
+
+    #[test]
+    fn centered_on_monitor() {
+        let rect = Rect::new(1920., 0., 3840., 1080.);
+        let monitor = Monitor::new(false, rect, rect).with_details(None, Scale::new(2., 2.), None);
+        assert_eq!(
+            centered_position(Size::new(400., 300.), &monitor),
+            Point::new(2480., 240.)
+        );
+        // Windows larger than the monitor stay on it.
+        assert_eq!(
+            centered_position(Size::new(2000., 300.), &monitor),
+            Point::new(1920., 240.)
+        );
+    }
+
+    #[test]
+    fn size_is_clamped() {
+        let min = Some(Size::new(200., 100.));
+        let max = Some(Size::new(800., 600.));
+        assert_eq!(
+            clamp_size(Size::new(100., 700.), min, max),
+            Size::new(200., 600.)
+        );
+        assert_eq!(
+            clamp_size(Size::new(300., 300.), min, None),
+            Size::new(300., 300.)
+        );
+        assert_eq!(
+            clamp_size(Size::new(100., 50.), min, Some(Size::new(150., 50.))),
+            Size::new(200., 100.)
+        );
+    }
+
+    #[test]
+    fn side_mouse_buttons() {
+        use glutin::event::MouseButton as GlutinButton;
+
+        assert_eq!(
+            convert_mouse_button(GlutinButton::Other(1)),
+            Some(MouseButton::X1)
+        );
+        assert_eq!(
+            convert_mouse_button(GlutinButton::Other(2)),
+            Some(MouseButton::X2)
+        );
+        assert_eq!(convert_mouse_button(GlutinButton::Other(42)), None);
+    }
+
+    #[test]
+    fn scroll_delta_conversion() {
+        let scale = Scale::new(2.0, 2.0);
+
+        let lines = MouseScrollDelta::LineDelta(1.0, -2.0);
+        assert_eq!(
+            convert_scroll_delta(lines, scale, SCROLL_LINE_DELTA),
+            Vec2::new(SCROLL_LINE_DELTA, 2.0 * SCROLL_LINE_DELTA)
+        );
+
+        let pixels = MouseScrollDelta::PixelDelta(PhysicalPosition::new(-8.0, 4.0));
+        assert_eq!(
+            convert_scroll_delta(pixels, scale, SCROLL_LINE_DELTA),
+            Vec2::new(-4.0, -2.0)
+        );
+    }
+
+    #[test]
+    fn wheel_event_has_modifiers_and_position() {
+        let handler = TestHandler::default();
+        let wheels = handler.wheels.clone();
+        let window = make_window(handler);
+
+        window.handle_modifiers_changed(ModifiersState::CTRL);
+        window.handle_motion_notify(PhysicalPosition::new(20., 10.));
+        window.handle_mouse_wheel(MouseScrollDelta::LineDelta(0., 1.));
+
+        let wheels = wheels.borrow();
+        assert_eq!(wheels.len(), 1);
+        assert_eq!(wheels[0].pos, Point::new(20., 10.));
+        assert_eq!(wheels[0].mods, Modifiers::CONTROL);
+        assert_eq!(wheels[0].wheel_delta, Vec2::new(0., -SCROLL_LINE_DELTA));
+    }
+
+    #[test]
+    fn raw_motion_needs_focus() {
+        let handler = TestHandler::default();
+        let raw_motions = handler.raw_motions.clone();
+        let window = make_window(handler);
+
+        window.handle_raw_motion((1., 2.));
+        window.handle_focus(true);
+        window.handle_raw_motion((3., -4.));
+        window.handle_focus(false);
+        window.handle_raw_motion((5., 6.));
+
+        assert_eq!(*raw_motions.borrow(), vec![Vec2::new(3., -4.)]);
+    }
+
+    #[test]
+    fn line_delta_only_scales_line_scrolling() {
+        let scale = Scale::new(2.0, 2.0);
+
+        let lines = MouseScrollDelta::LineDelta(0.5, 1.0);
+        assert_eq!(
+            convert_scroll_delta(lines, scale, 40.0),
+            Vec2::new(20.0, -40.0)
+        );
+
+        // Both axes and the fractions of trackpad deltas are kept.
+        let pixels = MouseScrollDelta::PixelDelta(PhysicalPosition::new(0.5, -0.25));
+        assert_eq!(
+            convert_scroll_delta(pixels, scale, 40.0),
+            Vec2::new(0.25, 0.125)
+        );
+    }
+
+    #[test]
+    fn cancelled_timer_does_not_fire() {
+        let handler = TestHandler::default();
+        let timers = handler.timers.clone();
+        let window = make_window(handler);
+        let handle = WindowHandle(Rc::downgrade(&window));
+
+        let now = Instant::now();
+        let kept = handle.request_timer(now);
+        let cancelled = handle.request_timer(now);
+        handle.cancel_timer(cancelled);
+        window.core.run_timers(now);
+
+        assert_eq!(*timers.borrow(), vec![kept]);
+    }
+
+    #[test]
+    fn quit_from_idle_closes_windows() {
+        let app = Application::new().unwrap();
+        let handler = TestHandler {
+            quit_on_idle: Some(app.clone()),
+            ..TestHandler::default()
+        };
+        let destroyed = handler.destroyed.clone();
+        let mut builder = WindowBuilder::new(app.clone());
+        builder.set_handler(Box::new(handler));
+        let handle = builder.build().unwrap();
+        let window = handle.0.upgrade().unwrap();
+
+        let idle = handle.get_idle_handle().unwrap();
+        idle.add_idle_token(IdleToken::new(0));
+        window.run_idle();
+        assert!(app.quitting());
+
+        // This is what the event loop does before it exits.
+        app.close_all_windows();
+        assert!(window.is_closed());
+        assert!(destroyed.get());
+    }
+
+    #[test]
+    fn interval_timer_repeats_until_cancelled() {
+        let handler = TestHandler::default();
+        let timers = handler.timers.clone();
+        let window = make_window(handler);
+        let handle = WindowHandle(Rc::downgrade(&window));
+
+        let start = Instant::now();
+        let interval = Duration::from_secs(1);
+        let token = handle.request_interval_timer(start + interval, interval);
+        window.core.run_timers(start + interval);
+        window.core.run_timers(start + interval * 2);
+        assert_eq!(*timers.borrow(), vec![token, token]);
+
+        handle.cancel_timer(token);
+        window.core.run_timers(start + interval * 3);
+        assert_eq!(*timers.borrow(), vec![token, token]);
+    }
+
+    #[test]
+    fn interval_timer_skips_missed_ticks() {
+        let handler = TestHandler::default();
+        let timers = handler.timers.clone();
+        let window = make_window(handler);
+        let handle = WindowHandle(Rc::downgrade(&window));
+
+        let start = Instant::now();
+        let interval = Duration::from_secs(1);
+        let token = handle.request_interval_timer(start + interval, interval);
+        window.core.run_timers(start + interval * 5);
+        assert_eq!(*timers.borrow(), vec![token]);
+        assert_eq!(window.core.next_timeout(), Some(start + interval * 6));
+    }
+
+    #[test]
+    fn scheduled_redraw_requests_frame() {
+        let window = make_window(TestHandler::default());
+        let handle = WindowHandle(Rc::downgrade(&window));
+        window.take_anim_frame_request();
+        window.core.state_mut().unwrap().invalid.clear();
+        assert!(!window.needs_redraw());
+
+        handle.get_idle_handle().unwrap()._schedule_redraw();
+        window.run_idle();
+        assert!(window.needs_redraw());
+    }
+
+    #[test]
+    fn minimized_window_is_not_redrawn() {
+        let window = make_window(TestHandler::default());
+        let size = window.size().unwrap();
+        let physical_size = PhysicalSize::new(size.width as u32, size.height as u32);
+        window.take_anim_frame_request();
+        window.core.state_mut().unwrap().invalid.clear();
+
+        window.screen_size_changed(PhysicalSize::new(0, 0)).unwrap();
+        window.request_anim_frame();
+        assert!(!window.needs_redraw());
+
+        window.take_anim_frame_request();
+        window.screen_size_changed(physical_size).unwrap();
+        assert!(window.needs_redraw());
+        let invalid = window.core.state().unwrap().invalid.bounding_box();
+        assert_eq!(invalid, size.to_rect());
+    }
+
+    #[test]
+    fn invalid_rects_are_clamped_to_the_window() {
+        let window = make_window(TestHandler::default());
+        let size = window.size().unwrap();
+        window.core.state_mut().unwrap().invalid.clear();
+
+        window.invalidate_rect(Rect::new(-10., -10., 10., 10.));
+        window.invalidate_rect(size.to_rect() + Vec2::new(100., 100.));
+        window.invalidate_rect(Rect::new(2000., 2000., 2100., 2100.));
+        window.invalidate_rect(Rect::new(
+            size.width + 0.5,
+            size.height + 0.5,
+            size.width + 10.5,
+            size.height + 10.5,
+        ));
+
+        let invalid = window.core.state().unwrap().invalid.clone();
+        let expected = Rect::new(0., 0., size.width, size.height);
+        assert_eq!(invalid.rects().len(), 2);
+        assert_eq!(invalid.bounding_box(), expected);
+    }
+
+    #[test]
+    fn pointer_capture_ends_with_the_last_button() {
+        let window = make_window(TestHandler::default());
+        let position = PhysicalPosition::new(1., 1.);
+        window.handle_button_press(position, glutin::event::MouseButton::Left);
+        window.handle_button_press(position, glutin::event::MouseButton::Right);
+        window.capture_pointer();
+        assert!(window.core.state().unwrap().pointer_captured);
+
+        window.handle_cursor_left();
+        window.handle_button_release(position, glutin::event::MouseButton::Left);
+        assert!(window.core.state().unwrap().pointer_captured);
+        window.handle_button_release(position, glutin::event::MouseButton::Right);
+        assert!(!window.core.state().unwrap().pointer_captured);
+    }
+
+    #[test]
+    fn capture_is_taken_once() {
+        let window = make_window(TestHandler::default());
+        assert!(window.capture().is_err());
+
+        window.request_capture();
+        assert!(window.take_capture_request());
+        assert!(!window.take_capture_request());
+
+        window.set_capture(vec![0; 4], Size::new(1., 1.));
+        assert_eq!(window.capture().unwrap(), (vec![0; 4], Size::new(1., 1.)));
+        assert!(window.capture().is_err());
+    }
+}
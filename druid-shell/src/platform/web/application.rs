@@ -14,7 +14,9 @@
 
 //! Web implementation of features at the application scope.
 
-use crate::application::AppHandler;
+use crate::application::{AppHandler, Theme};
+use crate::hotkey::HotKey;
+use crate::window::IdleToken;
 
 use super::clipboard::Clipboard;
 use super::error::Error;
@@ -35,6 +37,22 @@ impl Application {
         Clipboard
     }
 
+    /// Browsers don't let pages register global hotkeys, so they never fire.
+    pub fn register_global_hotkey(&self, _hotkey: HotKey, _token: IdleToken) -> Result<(), Error> {
+        Ok(())
+    }
+
+    pub fn unregister_global_hotkey(&self, _token: IdleToken) -> Result<(), Error> {
+        Ok(())
+    }
+
+    pub fn system_theme(&self) -> Theme {
+        match dark_light::detect() {
+            dark_light::Mode::Dark => Theme::Dark,
+            dark_light::Mode::Light | dark_light::Mode::Default => Theme::Light,
+        }
+    }
+
     pub fn get_locale() -> String {
         //TODO ahem
         "en-US".into()
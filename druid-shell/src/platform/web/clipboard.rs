@@ -14,47 +14,182 @@
 
 //! Interactions with the browser pasteboard.
 
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+use js_sys::{Function, Promise, Reflect};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
 use crate::clipboard::{ClipboardFormat, FormatId};
 
+thread_local! {
+    /// The text that was last put on or read from the clipboard.
+    static TEXT: RefCell<Option<String>> = RefCell::new(None);
+    /// The callbacks handed to clipboard promises, the browser calls them later.
+    static PENDING: RefCell<Vec<PendingRequest>> = RefCell::new(Vec::new());
+}
+
+/// The callbacks of a clipboard promise, kept alive until it settles.
+struct PendingRequest {
+    settled: Rc<Cell<bool>>,
+    _resolve: Closure<dyn FnMut(JsValue)>,
+    _reject: Closure<dyn FnMut(JsValue)>,
+}
+
 /// The browser clipboard.
+///
+/// The browser only hands out the clipboard asynchronously, so `get_string` returns
+/// the text that was last put on or read from the clipboard. Only `request_string`
+/// reads the clipboard, which may ask the user for permission.
 #[derive(Debug, Clone, Default)]
 pub struct Clipboard;
 
 impl Clipboard {
     /// Put a string onto the system clipboard.
-    pub fn put_string(&mut self, _s: impl AsRef<str>) {
-        log::warn!("unimplemented");
+    pub fn put_string(&mut self, s: impl AsRef<str>) {
+        let text = s.as_ref();
+        TEXT.with(|cache| *cache.borrow_mut() = Some(text.to_owned()));
+        match call_clipboard("writeText", Some(&text.into())) {
+            Ok(promise) => on_settled(promise, |result| {
+                if let Err(err) = result {
+                    log::warn!("failed to write to the clipboard: {:?}", err);
+                }
+            }),
+            Err(err) => log::warn!("failed to write to the clipboard: {:?}", err),
+        }
     }
 
     /// Put multi-format data on the system clipboard.
-    pub fn put_formats(&mut self, _formats: &[ClipboardFormat]) {
-        log::warn!("unimplemented");
+    ///
+    /// Only text is supported, the other formats are ignored.
+    pub fn put_formats(&mut self, formats: &[ClipboardFormat]) {
+        let text = formats
+            .iter()
+            .find(|f| f.identifier == ClipboardFormat::TEXT);
+        match text {
+            Some(format) => self.put_string(String::from_utf8_lossy(&format.data)),
+            None => log::warn!("only text can be put on the browser clipboard"),
+        }
     }
 
-    /// Get a string from the system clipboard, if one is available.
+    /// Get the text that was last put on or read from the clipboard, if any.
     pub fn get_string(&self) -> Option<String> {
-        log::warn!("unimplemented");
-        None
+        TEXT.with(|cache| cache.borrow().clone())
+    }
+
+    /// Whether there is text to return from `get_string`.
+    fn has_text(&self) -> bool {
+        TEXT.with(|cache| cache.borrow().is_some())
+    }
+
+    /// Read the text on the clipboard, and call `callback` with it once the browser
+    /// delivers it.
+    ///
+    /// `callback` gets `None` if the clipboard doesn't hold text or the user denied
+    /// access to it.
+    pub fn request_string(&self, callback: impl FnOnce(Option<String>) + 'static) {
+        let promise = match call_clipboard("readText", None) {
+            Ok(promise) => promise,
+            Err(err) => {
+                log::warn!("failed to read the clipboard: {:?}", err);
+                callback(None);
+                return;
+            }
+        };
+        on_settled(promise, |result| {
+            let text = match result {
+                Ok(text) => text.as_string(),
+                Err(err) => {
+                    log::warn!("failed to read the clipboard: {:?}", err);
+                    None
+                }
+            };
+            if text.is_some() {
+                TEXT.with(|cache| *cache.borrow_mut() = text.clone());
+            }
+            callback(text);
+        });
     }
 
     /// Given a list of supported clipboard types, returns the supported type which has
     /// highest priority on the system clipboard, or `None` if no types are supported.
-    pub fn preferred_format(&self, _formats: &[FormatId]) -> Option<FormatId> {
-        log::warn!("unimplemented");
-        None
+    pub fn preferred_format(&self, formats: &[FormatId]) -> Option<FormatId> {
+        if !self.has_text() {
+            return None;
+        }
+        formats
+            .iter()
+            .find(|f| **f == ClipboardFormat::TEXT)
+            .copied()
     }
 
     /// Return data in a given format, if available.
     ///
     /// It is recommended that the `fmt` argument be a format returned by
     /// [`Clipboard::preferred_format`]
-    pub fn get_format(&self, _format: FormatId) -> Option<Vec<u8>> {
-        log::warn!("unimplemented");
-        None
+    pub fn get_format(&self, format: FormatId) -> Option<Vec<u8>> {
+        if format == ClipboardFormat::TEXT {
+            self.get_string().map(String::into_bytes)
+        } else {
+            None
+        }
     }
 
     pub fn available_type_names(&self) -> Vec<String> {
-        log::warn!("unimplemented");
-        Vec::new()
+        if self.has_text() {
+            vec![ClipboardFormat::TEXT.to_string()]
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+/// Call `method` of `navigator.clipboard`, which returns a promise.
+///
+/// The clipboard API is looked up at runtime, it is missing outside of secure contexts.
+fn call_clipboard(method: &str, arg: Option<&JsValue>) -> Result<Promise, JsValue> {
+    let navigator = web_sys::window().ok_or("no window")?.navigator();
+    let clipboard = Reflect::get(&navigator, &"clipboard".into())?;
+    if clipboard.is_undefined() {
+        return Err("the clipboard API is not available".into());
     }
+    let function: Function = Reflect::get(&clipboard, &method.into())?.dyn_into()?;
+    let result = match arg {
+        Some(arg) => function.call1(&clipboard, arg)?,
+        None => function.call0(&clipboard)?,
+    };
+    result.dyn_into()
+}
+
+/// Call `f` with the value of `promise` once it resolves, or the error if it is rejected.
+fn on_settled(promise: Promise, f: impl FnOnce(Result<JsValue, JsValue>) + 'static) {
+    let settled = Rc::new(Cell::new(false));
+    let resolved = Rc::new(Cell::new(Some(f)));
+    let rejected = resolved.clone();
+    let resolve_settled = settled.clone();
+    let reject_settled = settled.clone();
+    let resolve = Closure::once(move |value: JsValue| {
+        resolve_settled.set(true);
+        if let Some(f) = resolved.take() {
+            f(Ok(value));
+        }
+    });
+    let reject = Closure::once(move |err: JsValue| {
+        reject_settled.set(true);
+        if let Some(f) = rejected.take() {
+            f(Err(err));
+        }
+    });
+    let _ = promise.then2(&resolve, &reject);
+    PENDING.with(|pending| {
+        let mut pending = pending.borrow_mut();
+        // A closure can't be dropped while it runs, so settled requests are dropped here.
+        pending.retain(|request| !request.settled.get());
+        pending.push(PendingRequest {
+            settled,
+            _resolve: resolve,
+            _reject: reject,
+        });
+    });
 }
@@ -35,6 +35,19 @@ pub(crate) fn convert_keyboard_event(
     }
 }
 
+/// A key event for text committed by an input method.
+pub(crate) fn composed_text_event(text: &str, state: KeyState) -> KeyEvent {
+    KeyEvent {
+        state,
+        key: KbKey::Character(text.to_owned()),
+        code: Code::Unidentified,
+        location: Location::Standard,
+        mods: Modifiers::empty(),
+        repeat: false,
+        is_composing: false,
+    }
+}
+
 fn convert_code(code: &str) -> Code {
     match code {
         "Backquote" => Code::Backquote,
@@ -16,12 +16,16 @@
 
 use std::any::Any;
 use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
 use std::ffi::OsString;
 use std::rc::{Rc, Weak};
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use instant::Instant;
 
+use js_sys::Reflect;
+
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
 
@@ -31,7 +35,7 @@ use crate::piet::{PietText, RenderContext};
 
 use super::application::Application;
 use super::error::Error;
-use super::keycodes::convert_keyboard_event;
+use super::keycodes::{composed_text_event, convert_keyboard_event};
 use super::menu::Menu;
 use crate::common_util::{ClickCounter, IdleCallback};
 use crate::dialog::{FileDialogOptions, FileDialogType};
@@ -97,20 +101,109 @@ struct WindowState {
     canvas: web_sys::HtmlCanvasElement,
     context: web_sys::CanvasRenderingContext2d,
     invalid: RefCell<Region>,
+    /// Whether a `requestAnimationFrame` callback is pending.
+    frame_requested: Cell<bool>,
     click_counter: ClickCounter,
+    /// The mouse buttons that are held down, as of the last pointer event.
+    buttons: Cell<MouseButtons>,
+    /// Whether an input method is composing text.
+    composing: Cell<bool>,
+    /// The hidden text area that has the keyboard focus, input methods and on-screen
+    /// keyboards only deliver text to an editable element.
+    text_input: Option<web_sys::HtmlTextAreaElement>,
+    /// Whether the last key press was delivered to the handler as a character, so the
+    /// text the browser inserts for it is not delivered again.
+    key_delivered_text: Cell<bool>,
+    /// The pending timers, for cancelling them.
+    timers: RefCell<HashMap<TimerToken, JsTimer>>,
+}
+
+/// A timer scheduled with `setTimeout` or `setInterval`.
+struct JsTimer {
+    /// The id the browser returned for the timer.
+    handle: i32,
+    /// The callback of an interval timer, which has to live until the interval is cleared.
+    interval: Option<Closure<dyn FnMut()>>,
 }
 
 // TODO: support custom cursors
 #[derive(Clone, PartialEq)]
 pub struct CustomCursor;
 
+/// Convert `duration` to the milliseconds `setTimeout` and `setInterval` take.
+fn timeout_millis(duration: Duration) -> i32 {
+    use std::convert::TryFrom;
+    match i32::try_from(duration.as_millis()) {
+        Ok(millis) => millis,
+        Err(_) => {
+            log::warn!("Timer interval exceeds 32 bit integer max");
+            i32::max_value()
+        }
+    }
+}
+
 impl WindowState {
+    /// Repeat the interval timer `token` every `interval`, after its first tick.
+    fn start_interval(self: &Rc<Self>, token: TimerToken, interval: Duration) {
+        let weak = Rc::downgrade(self);
+        let f = move || {
+            if let Some(state) = weak.upgrade() {
+                if let Ok(mut handler_borrow) = state.handler.try_borrow_mut() {
+                    handler_borrow.timer(token);
+                }
+            }
+        };
+        let f = Closure::wrap(Box::new(f) as Box<dyn FnMut()>);
+        let handle = self
+            .window
+            .set_interval_with_callback_and_timeout_and_arguments_0(
+                f.as_ref().unchecked_ref(),
+                timeout_millis(interval),
+            )
+            .expect("Failed to call setInterval with a callback");
+        let timer = JsTimer {
+            handle,
+            interval: Some(f),
+        };
+        self.timers.borrow_mut().insert(token, timer);
+    }
+
+    /// Deliver text committed by an input method or on-screen keyboard as one key press.
+    fn commit_text(&self, text: &str) {
+        let mut handler = self.handler.borrow_mut();
+        handler.key_down(composed_text_event(text, KeyState::Down));
+        handler.key_up(composed_text_event(text, KeyState::Up));
+    }
+
+    /// Empty the hidden text area, the text in it has been delivered.
+    fn clear_text_input(&self) {
+        if let Some(text_input) = &self.text_input {
+            text_input.set_value("");
+        }
+    }
+
+    /// Run `render` in the next animation frame, unless that is already scheduled.
+    fn schedule_frame(self: &Rc<Self>) {
+        if self.frame_requested.replace(true) {
+            return;
+        }
+        let state = self.clone();
+        self.request_animation_frame(move || state.render())
+            .expect("Failed to request animation frame");
+    }
+
     fn render(&self) {
+        // Anything requested from here on needs another frame.
+        self.frame_requested.set(false);
         self.handler.borrow_mut().prepare_paint();
+        // Whatever is invalidated while painting is left for the next frame.
+        let invalid = std::mem::replace(&mut *self.invalid.borrow_mut(), Region::EMPTY);
+        if invalid.is_empty() {
+            return;
+        }
 
         let mut piet_ctx = piet_common::Piet::new(self.context.clone(), self.window.clone());
         if let Err(e) = piet_ctx.with_save(|mut ctx| {
-            let invalid = self.invalid.borrow();
             ctx.clip(invalid.to_bez_path());
             self.handler.borrow_mut().paint(&mut ctx, &invalid);
             Ok(())
@@ -120,7 +213,6 @@ impl WindowState {
         if let Err(e) = piet_ctx.finish() {
             log::error!("piet error finishing render: {:?}", e);
         }
-        self.invalid.borrow_mut().clear();
     }
 
     fn process_idle_queue(&self) {
@@ -165,12 +257,22 @@ impl WindowState {
 
 fn setup_mouse_down_callback(ws: &Rc<WindowState>) {
     let state = ws.clone();
-    register_canvas_event_listener(ws, "mousedown", move |event: web_sys::MouseEvent| {
+    register_canvas_event_listener(ws, "pointerdown", move |event: web_sys::PointerEvent| {
         if let Some(button) = mouse_button(event.button()) {
+            // Keep the keyboard focus on the text area instead of moving it to the page.
+            event.prevent_default();
+            if let Some(text_input) = &state.text_input {
+                let _ = text_input.focus();
+            }
+            // Keep getting the pointer events of the drag when it leaves the canvas.
+            if let Err(err) = state.canvas.set_pointer_capture(event.pointer_id()) {
+                log::warn!("failed to capture the pointer: {:?}", err);
+            }
             let pos = Point::new(event.offset_x() as f64, event.offset_y() as f64);
             let count = state.click_counter.count_for_click(pos);
 
             let buttons = mouse_buttons(event.buttons());
+            state.buttons.set(buttons);
             let event = MouseEvent {
                 pos,
                 buttons,
@@ -187,9 +289,13 @@ fn setup_mouse_down_callback(ws: &Rc<WindowState>) {
 
 fn setup_mouse_up_callback(ws: &Rc<WindowState>) {
     let state = ws.clone();
-    register_canvas_event_listener(ws, "mouseup", move |event: web_sys::MouseEvent| {
+    register_canvas_event_listener(ws, "pointerup", move |event: web_sys::PointerEvent| {
         if let Some(button) = mouse_button(event.button()) {
             let buttons = mouse_buttons(event.buttons());
+            state.buttons.set(buttons);
+            if buttons.is_empty() {
+                let _ = state.canvas.release_pointer_capture(event.pointer_id());
+            }
             let event = MouseEvent {
                 pos: Point::new(event.offset_x() as f64, event.offset_y() as f64),
                 buttons,
@@ -204,10 +310,44 @@ fn setup_mouse_up_callback(ws: &Rc<WindowState>) {
     });
 }
 
+fn setup_pointer_cancel_callback(ws: &Rc<WindowState>) {
+    let state = ws.clone();
+    register_canvas_event_listener(ws, "pointercancel", move |event: web_sys::PointerEvent| {
+        // The browser took over the gesture, there won't be a `pointerup` for the buttons
+        // that are still down.
+        let _ = state.canvas.release_pointer_capture(event.pointer_id());
+        let pos = Point::new(event.offset_x() as f64, event.offset_y() as f64);
+        let mods = get_modifiers!(event);
+        let down = state.buttons.replace(MouseButtons::new());
+        let mut buttons = down;
+        let all = [
+            MouseButton::Left,
+            MouseButton::Right,
+            MouseButton::Middle,
+            MouseButton::X1,
+            MouseButton::X2,
+        ];
+        for &button in all.iter().filter(|&&button| down.contains(button)) {
+            buttons.remove(button);
+            let event = MouseEvent {
+                pos,
+                buttons,
+                mods,
+                count: 0,
+                focus: false,
+                button,
+                wheel_delta: Vec2::ZERO,
+            };
+            state.handler.borrow_mut().mouse_up(&event);
+        }
+    });
+}
+
 fn setup_mouse_move_callback(ws: &Rc<WindowState>) {
     let state = ws.clone();
-    register_canvas_event_listener(ws, "mousemove", move |event: web_sys::MouseEvent| {
+    register_canvas_event_listener(ws, "pointermove", move |event: web_sys::PointerEvent| {
         let buttons = mouse_buttons(event.buttons());
+        state.buttons.set(buttons);
         let event = MouseEvent {
             pos: Point::new(event.offset_x() as f64, event.offset_y() as f64),
             buttons,
@@ -270,7 +410,8 @@ fn setup_keyup_callback(ws: &Rc<WindowState>) {
     let state = ws.clone();
     register_window_event_listener(ws, "keyup", move |event: web_sys::KeyboardEvent| {
         let modifiers = get_modifiers!(event);
-        let kb_event = convert_keyboard_event(&event, modifiers, KeyState::Up);
+        let mut kb_event = convert_keyboard_event(&event, modifiers, KeyState::Up);
+        kb_event.is_composing |= state.composing.get();
         state.handler.borrow_mut().key_up(kb_event);
     });
 }
@@ -279,15 +420,98 @@ fn setup_keydown_callback(ws: &Rc<WindowState>) {
     let state = ws.clone();
     register_window_event_listener(ws, "keydown", move |event: web_sys::KeyboardEvent| {
         let modifiers = get_modifiers!(event);
-        let kb_event = convert_keyboard_event(&event, modifiers, KeyState::Down);
-        if kb_event.key == KbKey::Backspace {
-            // Prevent the browser from going back a page by default.
+        let mut kb_event = convert_keyboard_event(&event, modifiers, KeyState::Down);
+        kb_event.is_composing |= state.composing.get();
+        let composing = kb_event.is_composing;
+        let is_text = matches!(kb_event.key, KbKey::Character(_));
+        state.key_delivered_text.set(!composing && is_text);
+        // By default backspace makes the browser go back a page.
+        let is_backspace = kb_event.key == KbKey::Backspace;
+        let handled = state.handler.borrow_mut().key_down(kb_event);
+        // Keys that are part of a composition belong to the input method. The browser
+        // shouldn't scroll or navigate on the other keys we used.
+        if !composing && (handled || is_backspace) {
             event.prevent_default();
         }
-        state.handler.borrow_mut().key_down(kb_event);
     });
 }
 
+fn setup_composition_callbacks(ws: &Rc<WindowState>) {
+    let state = ws.clone();
+    register_window_event_listener(
+        ws,
+        "compositionstart",
+        move |_: web_sys::CompositionEvent| {
+            state.composing.set(true);
+        },
+    );
+    let state = ws.clone();
+    register_window_event_listener(
+        ws,
+        "compositionend",
+        move |event: web_sys::CompositionEvent| {
+            state.composing.set(false);
+            // The committed text arrives as one key press.
+            if let Some(text) = event.data().filter(|text| !text.is_empty()) {
+                state.commit_text(&text);
+            }
+            state.clear_text_input();
+        },
+    );
+}
+
+fn setup_text_input_callbacks(ws: &Rc<WindowState>) {
+    let state = ws.clone();
+    register_window_event_listener(ws, "beforeinput", move |event: web_sys::InputEvent| {
+        // Compositions are delivered when they end.
+        if event.is_composing() {
+            return;
+        }
+        // `inputType` and `data` are looked up at runtime, web-sys doesn't bind them.
+        let get = |name: &str| Reflect::get(&event, &name.into()).ok()?.as_string();
+        if get("inputType").as_deref() != Some("insertText") {
+            return;
+        }
+        // The text goes to the handler, not into the text area.
+        event.prevent_default();
+        // Keyboards that send real key presses already delivered the text with them.
+        if state.key_delivered_text.replace(false) {
+            return;
+        }
+        if let Some(text) = get("data").filter(|text| !text.is_empty()) {
+            state.commit_text(&text);
+        }
+    });
+    let state = ws.clone();
+    register_window_event_listener(ws, "input", move |_: web_sys::InputEvent| {
+        if !state.composing.get() {
+            state.clear_text_input();
+        }
+    });
+}
+
+/// Add a hidden text area to the page, for `WindowState::text_input`.
+fn create_text_input(
+    document: &web_sys::Document,
+) -> Result<web_sys::HtmlTextAreaElement, JsValue> {
+    let text_input = document
+        .create_element("textarea")?
+        .dyn_into::<web_sys::HtmlTextAreaElement>()?;
+    text_input.set_attribute("autocapitalize", "off")?;
+    text_input.set_attribute("autocomplete", "off")?;
+    text_input.set_attribute("spellcheck", "false")?;
+    text_input.set_attribute(
+        "style",
+        "position: fixed; left: 0; top: 0; width: 1px; height: 1px; \
+         padding: 0; border: 0; opacity: 0; resize: none;",
+    )?;
+    document
+        .body()
+        .ok_or_else(|| JsValue::from_str("no body"))?
+        .append_child(&text_input)?;
+    Ok(text_input)
+}
+
 /// A helper function to register a window event listener with `addEventListener`.
 fn register_window_event_listener<F, E>(window_state: &Rc<WindowState>, event_type: &str, f: F)
 where
@@ -320,10 +544,13 @@ fn setup_web_callbacks(window_state: &Rc<WindowState>) {
     setup_mouse_down_callback(window_state);
     setup_mouse_move_callback(window_state);
     setup_mouse_up_callback(window_state);
+    setup_pointer_cancel_callback(window_state);
     setup_resize_callback(window_state);
     setup_scroll_callback(window_state);
     setup_keyup_callback(window_state);
     setup_keydown_callback(window_state);
+    setup_composition_callbacks(window_state);
+    setup_text_input_callbacks(window_state);
 }
 
 impl WindowBuilder {
@@ -365,6 +592,10 @@ impl WindowBuilder {
         // Ignored
     }
 
+    pub fn set_fullscreen(&mut self, _fullscreen: bool) {
+        // Ignored
+    }
+
     pub fn set_level(&mut self, _level: WindowLevel) {
         // ignored
     }
@@ -379,9 +610,8 @@ impl WindowBuilder {
 
     pub fn build(self) -> Result<WindowHandle, Error> {
         let window = web_sys::window().ok_or(Error::NoWindow)?;
-        let canvas = window
-            .document()
-            .ok_or(Error::NoDocument)?
+        let document = window.document().ok_or(Error::NoDocument)?;
+        let canvas = document
             .get_element_by_id("canvas")
             .ok_or_else(|| Error::NoElementById("canvas".to_string()))?
             .dyn_into::<web_sys::HtmlCanvasElement>()
@@ -408,6 +638,26 @@ impl WindowBuilder {
         let size_dp = area.size_dp();
 
         set_cursor(&canvas, &self.cursor);
+        // Touch drags go to the app instead of scrolling or zooming the page.
+        canvas
+            .style()
+            .set_property("touch-action", "none")
+            .unwrap_or_else(|_| log::warn!("Failed to set touch-action"));
+        // Keep the title of the page unless the app picked one.
+        if !self.title.is_empty() {
+            set_document_title(&window, &self.title);
+        }
+
+        let text_input = match create_text_input(&document) {
+            Ok(text_input) => {
+                let _ = text_input.focus();
+                Some(text_input)
+            }
+            Err(err) => {
+                log::warn!("failed to add the text input element: {:?}", err);
+                None
+            }
+        };
 
         let handler = self.handler.unwrap();
 
@@ -420,7 +670,13 @@ impl WindowBuilder {
             canvas,
             context,
             invalid: RefCell::new(Region::EMPTY),
+            frame_requested: Cell::new(false),
             click_counter: ClickCounter::default(),
+            buttons: Cell::new(MouseButtons::new()),
+            composing: Cell::new(false),
+            text_input,
+            key_delivered_text: Cell::new(false),
+            timers: Default::default(),
         });
 
         setup_web_callbacks(&window);
@@ -473,14 +729,24 @@ impl WindowHandle {
     }
 
     pub fn get_size(&self) -> Size {
-        log::warn!("WindowHandle::get_size unimplemented for web.");
-        Size::new(0.0, 0.0)
+        match self.0.upgrade() {
+            Some(state) => {
+                // The client size is in CSS pixels, which are display points.
+                let canvas = &state.canvas;
+                Size::new(canvas.client_width() as f64, canvas.client_height() as f64)
+            }
+            None => Size::ZERO,
+        }
     }
 
     pub fn set_window_state(&self, _state: window::WindowState) {
         log::warn!("WindowHandle::set_window_state unimplemented for web.");
     }
 
+    pub fn set_fullscreen(&self, _fullscreen: bool) {
+        log::warn!("WindowHandle::set_fullscreen unimplemented for web.");
+    }
+
     pub fn get_window_state(&self) -> window::WindowState {
         log::warn!("WindowHandle::get_window_state unimplemented for web.");
         window::WindowState::RESTORED
@@ -543,21 +809,79 @@ impl WindowHandle {
         if let Some(state) = self.0.upgrade() {
             let s = state.clone();
             let f = move || {
+                s.timers.borrow_mut().remove(&token);
                 if let Ok(mut handler_borrow) = s.handler.try_borrow_mut() {
                     handler_borrow.timer(token);
                 }
             };
-            state
+            let handle = state
                 .window
                 .set_timeout_with_callback_and_timeout_and_arguments_0(
                     Closure::once_into_js(f).as_ref().unchecked_ref(),
                     interval,
                 )
                 .expect("Failed to call setTimeout with a callback");
+            let timer = JsTimer {
+                handle,
+                interval: None,
+            };
+            state.timers.borrow_mut().insert(token, timer);
         }
         token
     }
 
+    pub fn request_interval_timer(&self, deadline: Instant, interval: Duration) -> TimerToken {
+        let token = TimerToken::next();
+
+        if let Some(state) = self.0.upgrade() {
+            let now = Instant::now();
+            let delay = if deadline > now {
+                deadline.duration_since(now)
+            } else {
+                Duration::from_secs(0)
+            };
+            // The timers are owned by the window state, so they only hold a weak reference
+            // to it. The first tick is a timeout, which then starts the interval.
+            let weak = Rc::downgrade(&state);
+            let first_tick = move || {
+                if let Some(state) = weak.upgrade() {
+                    // Started before calling the handler, so that it can cancel the timer.
+                    state.start_interval(token, interval);
+                    if let Ok(mut handler_borrow) = state.handler.try_borrow_mut() {
+                        handler_borrow.timer(token);
+                    }
+                }
+            };
+            let handle = state
+                .window
+                .set_timeout_with_callback_and_timeout_and_arguments_0(
+                    Closure::once_into_js(first_tick).as_ref().unchecked_ref(),
+                    timeout_millis(delay),
+                )
+                .expect("Failed to call setTimeout with a callback");
+            let timer = JsTimer {
+                handle,
+                interval: None,
+            };
+            state.timers.borrow_mut().insert(token, timer);
+        }
+        token
+    }
+
+    pub fn cancel_timer(&self, token: TimerToken) {
+        if let Some(state) = self.0.upgrade() {
+            let timer = state.timers.borrow_mut().remove(&token);
+            match timer {
+                Some(JsTimer {
+                    handle,
+                    interval: Some(_),
+                }) => state.window.clear_interval_with_handle(handle),
+                Some(JsTimer { handle, .. }) => state.window.clear_timeout_with_handle(handle),
+                None => {}
+            }
+        }
+    }
+
     pub fn set_cursor(&mut self, cursor: &Cursor) {
         if let Some(s) = self.0.upgrade() {
             set_cursor(&s.canvas, cursor);
@@ -581,11 +905,7 @@ impl WindowHandle {
 
     fn render_soon(&self) {
         if let Some(s) = self.0.upgrade() {
-            let state = s.clone();
-            s.request_animation_frame(move || {
-                state.render();
-            })
-            .expect("Failed to request animation frame");
+            s.schedule_frame();
         }
     }
 
@@ -625,7 +945,7 @@ impl WindowHandle {
 
     pub fn set_title(&self, title: impl Into<String>) {
         if let Some(state) = self.0.upgrade() {
-            state.canvas.set_title(&(title.into()))
+            set_document_title(&state.window, &title.into());
         }
     }
 }
@@ -701,6 +1021,14 @@ fn mouse_buttons(mask: u16) -> MouseButtons {
     buttons
 }
 
+/// The page title is the closest thing to a window title.
+fn set_document_title(window: &web_sys::Window, title: &str) {
+    match window.document() {
+        Some(document) => document.set_title(title),
+        None => log::warn!("Failed to set the title, there is no document"),
+    }
+}
+
 fn set_cursor(canvas: &web_sys::HtmlCanvasElement, cursor: &Cursor) {
     canvas
         .style()
@@ -80,6 +80,40 @@ impl Region {
         self.rects.extend_from_slice(&other.rects);
     }
 
+    /// Modifies this region by removing everything in the other region.
+    ///
+    /// Rectangles that partially overlap `other` are split into the pieces outside of it.
+    pub fn subtract(&mut self, other: &Region) {
+        for hole in &other.rects {
+            let mut rects = Vec::with_capacity(self.rects.len());
+            for r in &self.rects {
+                subtract_rect(*r, *hole, &mut rects);
+            }
+            self.rects = rects;
+        }
+    }
+
+    /// Returns the part of this region that is not in the other region.
+    pub fn difference(&self, other: &Region) -> Region {
+        let mut ret = self.clone();
+        ret.subtract(other);
+        ret
+    }
+
+    /// Returns the part of this region that is also in the other region.
+    pub fn intersect(&self, other: &Region) -> Region {
+        let mut rects = Vec::new();
+        for r in &self.rects {
+            for s in &other.rects {
+                let overlap = r.intersect(*s);
+                if overlap.area() > 0.0 {
+                    rects.push(overlap);
+                }
+            }
+        }
+        Region { rects }
+    }
+
     /// Modifies this region by intersecting it with the given rectangle.
     pub fn intersect_with(&mut self, rect: Rect) {
         // TODO: this would be a good use of the nightly drain_filter function, if it stabilizes
@@ -90,6 +124,25 @@ impl Region {
     }
 }
 
+/// Pushes the parts of `rect` that are outside of `hole` to `out`.
+///
+/// These are at most four non-overlapping rectangles: the full-width bands above and
+/// below the hole, and the pieces left and right of it.
+fn subtract_rect(rect: Rect, hole: Rect, out: &mut Vec<Rect>) {
+    let overlap = rect.intersect(hole);
+    if overlap.area() <= 0.0 {
+        out.push(rect);
+        return;
+    }
+    let pieces = [
+        Rect::new(rect.x0, rect.y0, rect.x1, overlap.y0),
+        Rect::new(rect.x0, overlap.y1, rect.x1, rect.y1),
+        Rect::new(rect.x0, overlap.y0, overlap.x0, overlap.y1),
+        Rect::new(overlap.x1, overlap.y0, rect.x1, overlap.y1),
+    ];
+    out.extend(pieces.iter().filter(|r| r.area() > 0.0));
+}
+
 impl std::ops::AddAssign<Vec2> for Region {
     fn add_assign(&mut self, rhs: Vec2) {
         for r in &mut self.rects {
@@ -111,3 +164,91 @@ impl From<Rect> for Region {
         Region { rects: vec![rect] }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn area(region: &Region) -> f64 {
+        region.rects().iter().map(|r| r.area()).sum()
+    }
+
+    #[test]
+    fn subtract_inner_rect() {
+        let mut region = Region::from(Rect::new(0., 0., 10., 10.));
+        let hole = Rect::new(2., 3., 5., 7.);
+        region.subtract(&hole.into());
+
+        assert_eq!(area(&region), 100. - 12.);
+        assert!(!region.intersects(hole));
+        assert_eq!(region.bounding_box(), Rect::new(0., 0., 10., 10.));
+    }
+
+    #[test]
+    fn subtract_partial_overlap() {
+        let region = Region::from(Rect::new(0., 0., 10., 10.));
+        let hole = Rect::new(5., 5., 15., 15.);
+        let difference = region.difference(&hole.into());
+
+        assert_eq!(area(&difference), 75.);
+        assert!(!difference.intersects(hole));
+        // `difference` leaves the original region alone.
+        assert_eq!(region.rects(), &[Rect::new(0., 0., 10., 10.)]);
+    }
+
+    #[test]
+    fn subtract_disjoint_rect() {
+        let mut region = Region::from(Rect::new(0., 0., 10., 10.));
+        region.subtract(&Rect::new(20., 20., 30., 30.).into());
+
+        assert_eq!(region.rects(), &[Rect::new(0., 0., 10., 10.)]);
+    }
+
+    #[test]
+    fn subtract_covering_rect() {
+        let mut region = Region::from(Rect::new(2., 2., 8., 8.));
+        region.subtract(&Rect::new(0., 0., 10., 10.).into());
+
+        assert!(region.is_empty());
+    }
+
+    #[test]
+    fn intersect_disjoint_regions() {
+        let region = Region::from(Rect::new(0., 0., 10., 10.));
+        let other = Region::from(Rect::new(20., 0., 30., 10.));
+
+        assert!(region.intersect(&other).is_empty());
+    }
+
+    #[test]
+    fn intersect_nested_regions() {
+        let rect = Rect::new(2., 3., 5., 7.);
+        let outer = Region::from(Rect::new(0., 0., 10., 10.));
+        let inner = Region::from(rect);
+
+        assert_eq!(outer.intersect(&inner).rects(), &[rect]);
+        assert_eq!(inner.intersect(&outer).rects(), &[rect]);
+    }
+
+    #[test]
+    fn intersect_partial_overlap() {
+        let mut region = Region::from(Rect::new(0., 0., 10., 10.));
+        region.add_rect(Rect::new(20., 0., 30., 10.));
+        let other = Region::from(Rect::new(5., 5., 25., 15.));
+        let intersection = region.intersect(&other);
+
+        assert_eq!(area(&intersection), 50.);
+        assert_eq!(intersection.bounding_box(), Rect::new(5., 5., 25., 10.));
+        assert!(!intersection.intersects(Rect::new(10., 0., 20., 10.)));
+    }
+
+    #[test]
+    fn bounding_box_of_regions() {
+        let mut region = Region::EMPTY;
+        assert_eq!(region.bounding_box(), Rect::ZERO);
+
+        region.add_rect(Rect::new(0., 5., 10., 10.));
+        region.add_rect(Rect::new(20., 0., 30., 8.));
+        assert_eq!(region.bounding_box(), Rect::new(0., 0., 30., 10.));
+    }
+}
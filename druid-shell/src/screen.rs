@@ -16,6 +16,7 @@
 
 use crate::kurbo::Rect;
 use crate::platform;
+use crate::scale::Scale;
 use std::fmt;
 use std::fmt::Display;
 
@@ -32,6 +33,9 @@ pub struct Monitor {
     // https://docs.microsoft.com/en-us/windows/win32/api/winuser/ns-winuser-monitorinfo
     // Unsure about x11
     work_rect: Rect,
+    name: Option<String>,
+    scale: Option<Scale>,
+    refresh_rate: Option<f64>,
 }
 
 impl Monitor {
@@ -41,8 +45,26 @@ impl Monitor {
             primary,
             rect,
             work_rect,
+            name: None,
+            scale: None,
+            refresh_rate: None,
         }
     }
+
+    /// Add the details that not every platform reports.
+    #[allow(dead_code)]
+    pub(crate) fn with_details(
+        mut self,
+        name: Option<String>,
+        scale: Scale,
+        refresh_rate: Option<f64>,
+    ) -> Self {
+        self.name = name;
+        self.scale = Some(scale);
+        self.refresh_rate = refresh_rate;
+        self
+    }
+
     /// Returns true if the monitor is the primary monitor.
     /// The primary monitor has its origin at (0, 0) in virtual screen coordinates.
     pub fn is_primary(&self) -> bool {
@@ -59,6 +81,21 @@ impl Monitor {
     pub fn virtual_work_rect(&self) -> Rect {
         self.work_rect
     }
+
+    /// Returns the name of the monitor, if the platform reports one.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Returns the scale of the monitor, if the platform reports it.
+    pub fn scale(&self) -> Option<Scale> {
+        self.scale
+    }
+
+    /// Returns the refresh rate of the monitor in Hz, if the platform reports it.
+    pub fn refresh_rate(&self) -> Option<f64> {
+        self.refresh_rate
+    }
 }
 
 impl Display for Monitor {
@@ -17,17 +17,22 @@
 use std::any::Any;
 use std::time::Duration;
 
-use crate::application::Application;
+use crate::application::{Application, Theme};
 use crate::common_util::Counter;
 use crate::dialog::{FileDialogOptions, FileInfo};
 use crate::error::Error;
 use crate::keyboard::KeyEvent;
-use crate::kurbo::{Point, Rect, Size};
+#[cfg(any(skia_backend, dri_backend))]
+use crate::keyboard::{Code, Modifiers};
+use crate::kurbo::{Point, Rect, Size, Vec2};
 use crate::menu::Menu;
 use crate::mouse::{Cursor, CursorDesc, MouseEvent};
 use crate::platform::window as platform;
 use crate::region::Region;
 use crate::scale::Scale;
+use crate::screen::{Monitor, Screen};
+#[cfg(any(skia_backend, dri_backend))]
+use piet_common::Color;
 use piet_common::PietText;
 
 /// A token that uniquely identifies a running timer.
@@ -120,6 +125,13 @@ impl FileDialogToken {
 /// Levels in the window system - Z order for display purposes.
 /// Describes the purpose of a window and should be mapped appropriately to match platform
 /// conventions.
+///
+/// # Platform support
+///
+/// - GTK maps each level to the matching window type hint.
+/// - The glutin backends (`winit_x11`, `winit_wayland`) only have normal and always on top
+///   windows. Every level except [`AppWindow`](WindowLevel::AppWindow) is kept on top.
+/// - The other backends ignore the level.
 #[derive(Copy, Clone, Debug)]
 pub enum WindowLevel {
     /// A top level app window.
@@ -140,6 +152,40 @@ pub enum WindowState {
     RESTORED,
 }
 
+/// The position, size and maximized state of a window, to remember it between runs.
+///
+/// See [`WindowHandle::geometry`] and [`WindowHandle::set_geometry`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WindowGeometry {
+    /// The position of the window, as returned by [`WindowHandle::get_position`].
+    pub position: Point,
+    /// The size of the window, as returned by [`WindowHandle::get_size`].
+    pub size: Size,
+    /// Whether the window is maximized.
+    pub maximized: bool,
+}
+
+impl WindowGeometry {
+    /// Move the window to the work area of the primary monitor if it isn't on any of
+    /// `monitors`, for example because the monitor it was on was disconnected.
+    fn clamp_to_monitors(mut self, monitors: &[Monitor]) -> WindowGeometry {
+        if monitors
+            .iter()
+            .any(|monitor| monitor.virtual_rect().contains(self.position))
+        {
+            return self;
+        }
+        let monitor = monitors
+            .iter()
+            .find(|monitor| monitor.is_primary())
+            .or_else(|| monitors.first());
+        if let Some(monitor) = monitor {
+            self.position = monitor.virtual_work_rect().origin();
+        }
+        self
+    }
+}
+
 /// A handle to a platform window object.
 #[derive(Clone, Default)]
 pub struct WindowHandle(platform::WindowHandle);
@@ -173,6 +219,14 @@ impl WindowHandle {
         self.0.get_window_state()
     }
 
+    /// Make the window borderless fullscreen, or return it to a normal window.
+    ///
+    /// Leaving fullscreen restores the size the window had before.
+    #[cfg(any(skia_backend, dri_backend, headless_backend, target_arch = "wasm32"))]
+    pub fn set_fullscreen(&self, fullscreen: bool) {
+        self.0.set_fullscreen(fullscreen)
+    }
+
     /// Informs the system that the current location of the mouse should be treated as part of the
     /// window's titlebar. This can be used to implement a custom titlebar widget. Note that
     /// because this refers to the current location of the mouse, you should probably call this
@@ -212,11 +266,89 @@ impl WindowHandle {
         self.0.set_size(size.into())
     }
 
-    /// Gets the window size, in [pixels](crate::Scale).
+    /// Set the window's minimum drawing area size in [display points](crate::Scale).
+    ///
+    /// Later calls to [`set_size`] are limited to at least this size.
+    ///
+    /// [`set_size`]: WindowHandle::set_size
+    #[cfg(skia_backend)]
+    pub fn set_min_size(&self, size: impl Into<Size>) {
+        self.0.set_min_size(size.into())
+    }
+
+    /// Set the window's maximum drawing area size in [display points](crate::Scale).
+    ///
+    /// Later calls to [`set_size`] are limited to at most this size.
+    ///
+    /// [`set_size`]: WindowHandle::set_size
+    #[cfg(skia_backend)]
+    pub fn set_max_size(&self, size: impl Into<Size>) {
+        self.0.set_max_size(size.into())
+    }
+
+    /// Gets the window's drawing area size in [display points](crate::Scale).
+    ///
+    /// This is in the same units as [`set_size`] on every backend, so the value can be
+    /// passed back to it.
+    ///
+    /// [`set_size`]: WindowHandle::set_size
     pub fn get_size(&self) -> Size {
         self.0.get_size()
     }
 
+    /// Returns the position, size and maximized state of the window.
+    ///
+    /// For a maximized window these are the maximized position and size.
+    pub fn geometry(&self) -> WindowGeometry {
+        WindowGeometry {
+            position: self.get_position(),
+            size: self.get_size(),
+            maximized: self.get_window_state() == WindowState::MAXIMIZED,
+        }
+    }
+
+    /// Restore a geometry returned by [`geometry`], usually saved by an earlier run.
+    ///
+    /// If the position isn't on any of the connected monitors, the window is moved to
+    /// the primary monitor instead of opening off-screen.
+    ///
+    /// [`geometry`]: WindowHandle::geometry
+    pub fn set_geometry(&mut self, geometry: WindowGeometry) {
+        let geometry = geometry.clamp_to_monitors(&Screen::get_monitors());
+        if !geometry.maximized {
+            self.set_window_state(WindowState::RESTORED);
+        }
+        self.set_size(geometry.size);
+        self.set_position(geometry.position);
+        if geometry.maximized {
+            self.set_window_state(WindowState::MAXIMIZED);
+        }
+    }
+
+    /// Set the opacity of the whole window, from 0.0 (invisible) to 1.0 (opaque).
+    ///
+    /// Values outside of that range are clamped. Unlike a [transparent] window, which
+    /// shows through where the handler paints with alpha, this fades everything, title bar
+    /// included; together with a timer it can fade notifications and overlays in and out.
+    /// It is implemented on X11, where it needs a compositing manager. Elsewhere a warning
+    /// is logged and the window stays opaque.
+    ///
+    /// [transparent]: WindowBuilder::set_transparent
+    #[cfg(skia_backend)]
+    pub fn set_opacity(&self, opacity: f64) {
+        self.0.set_opacity(opacity)
+    }
+
+    /// Place the input method's candidate window at `position`, usually the text caret.
+    ///
+    /// The position is in display points, relative to the top left of the window's content
+    /// area. Text fields should call this when they gain focus and whenever the caret moves,
+    /// so that CJK and accented input show their popups next to the text.
+    #[cfg(skia_backend)]
+    pub fn set_ime_position(&self, position: Point) {
+        self.0.set_ime_position(position)
+    }
+
     /// Sets the [`WindowLevel`](crate::WindowLevel), the z-order in the Window system / compositor
     ///
     /// We do not currently have a getter method, mostly because the system's levels aren't a
@@ -226,6 +358,17 @@ impl WindowHandle {
         self.0.set_level(level)
     }
 
+    /// Set the [`MouseEvent::wheel_delta`] of one line of mouse wheel scrolling.
+    ///
+    /// The default is 120, like on Windows. Precise trackpad scrolling is reported in
+    /// display points and isn't affected.
+    ///
+    /// [`MouseEvent::wheel_delta`]: crate::MouseEvent::wheel_delta
+    #[cfg(skia_backend)]
+    pub fn set_scroll_line_delta(&self, delta: f64) {
+        self.0.set_scroll_line_delta(delta)
+    }
+
     /// Bring this window to the front of the window stack and give it focus.
     pub fn bring_to_front_and_focus(&self) {
         self.0.bring_to_front_and_focus()
@@ -259,6 +402,9 @@ impl WindowHandle {
     }
 
     /// Set the top-level menu for this window.
+    ///
+    /// The skia backend only shows native menus on macOS. On Linux the menu isn't shown
+    /// and a warning is logged, but the hotkeys of its items still send their commands.
     pub fn set_menu(&self, menu: Menu) {
         self.0.set_menu(menu.into_inner())
     }
@@ -282,11 +428,53 @@ impl WindowHandle {
         self.0.request_timer(instant::Instant::now() + deadline)
     }
 
+    /// Schedule a timer that fires repeatedly.
+    ///
+    /// This causes a [`WinHandler::timer`] call with the returned token
+    /// every `interval`, until the timer is cancelled with [`cancel_timer`].
+    /// Ticks that were missed because the application was busy are skipped.
+    ///
+    /// [`cancel_timer`]: WindowHandle::cancel_timer
+    #[cfg(any(skia_backend, dri_backend, headless_backend, target_arch = "wasm32"))]
+    pub fn request_interval_timer(&self, interval: Duration) -> TimerToken {
+        self.0
+            .request_interval_timer(instant::Instant::now() + interval, interval)
+    }
+
+    /// Cancel a timer scheduled with [`request_timer`] or [`request_interval_timer`].
+    ///
+    /// The handler will not receive a [`WinHandler::timer`] call for this
+    /// token. Cancelling a timer that has already fired does nothing.
+    ///
+    /// [`request_timer`]: WindowHandle::request_timer
+    /// [`request_interval_timer`]: WindowHandle::request_interval_timer
+    #[cfg(any(skia_backend, dri_backend, headless_backend, target_arch = "wasm32"))]
+    pub fn cancel_timer(&self, token: TimerToken) {
+        self.0.cancel_timer(token)
+    }
+
     /// Set the cursor icon.
+    ///
+    /// The DRI backend shows the cursor on the display's hardware cursor plane if it can,
+    /// and otherwise draws it into each frame. Changing the cursor plane needs DRM master,
+    /// which `dri::kms` keeps for its own device handle, so for now the DRI backend always
+    /// falls back to drawing the cursor.
     pub fn set_cursor(&mut self, cursor: &Cursor) {
         self.0.set_cursor(cursor)
     }
 
+    /// Show or hide the cursor while it is over the window.
+    ///
+    /// This is independent of [`set_cursor`], which changes the cursor that is shown.
+    /// The DRI backend draws the cursor itself, on the hardware cursor plane or into the
+    /// frames, and stops drawing it while it is hidden.
+    ///
+    /// [`set_cursor`]: WindowHandle::set_cursor
+    #[cfg(any(skia_backend, dri_backend))]
+    pub fn set_cursor_visible(&self, visible: bool) {
+        self.0.set_cursor_visible(visible)
+    }
+
     pub fn make_cursor(&self, desc: &CursorDesc) -> Option<Cursor> {
         self.0.make_cursor(desc)
     }
@@ -312,10 +500,28 @@ impl WindowHandle {
     /// Display a pop-up menu at the given position.
     ///
     /// `pos` is in the coordinate space of the window.
+    ///
+    /// The skia backend only shows context menus on macOS. On Linux nothing is shown and
+    /// a warning is logged.
     pub fn show_context_menu(&self, menu: Menu, pos: Point) {
         self.0.show_context_menu(menu.into_inner(), pos)
     }
 
+    /// Enable or disable the menu item `id`, in the window's menu or its last context menu.
+    ///
+    /// This updates the menu while it is shown, for instance to grey out "Paste" when the
+    /// clipboard is empty.
+    #[cfg(skia_backend)]
+    pub fn set_menu_item_enabled(&self, id: u32, enabled: bool) {
+        self.0.set_menu_item_enabled(id, enabled)
+    }
+
+    /// Check or uncheck the menu item `id`, in the window's menu or its last context menu.
+    #[cfg(skia_backend)]
+    pub fn set_menu_item_checked(&self, id: u32, checked: bool) {
+        self.0.set_menu_item_checked(id, checked)
+    }
+
     /// Get a handle that can be used to schedule an idle task.
     pub fn get_idle_handle(&self) -> Option<IdleHandle> {
         self.0.get_idle_handle().map(IdleHandle)
@@ -329,6 +535,86 @@ impl WindowHandle {
     pub fn get_scale(&self) -> Result<Scale, Error> {
         self.0.get_scale().map_err(Into::into)
     }
+
+    /// Returns the modifier keys that are held down.
+    ///
+    /// This is the state as of the last keyboard event the window processed, not a poll of
+    /// the hardware; it is what the next mouse event will carry in its `mods`.
+    #[cfg(any(skia_backend, dri_backend))]
+    pub fn query_modifiers(&self) -> Modifiers {
+        self.0.query_modifiers()
+    }
+
+    /// Returns whether the key with the physical `code` is held down.
+    ///
+    /// Like [`query_modifiers`], this reflects the last keyboard event the window
+    /// processed. Keys are considered released when the window loses focus.
+    ///
+    /// [`query_modifiers`]: WindowHandle::query_modifiers
+    #[cfg(any(skia_backend, dri_backend))]
+    pub fn is_key_down(&self, code: Code) -> bool {
+        self.0.is_key_down(code)
+    }
+
+    /// Returns the number of buffers the swapchain of the window cycles through.
+    ///
+    /// See [`WindowBuilder::set_buffer_count`].
+    #[cfg(any(skia_backend, dri_backend))]
+    pub fn buffer_count(&self) -> usize {
+        self.0.buffer_count()
+    }
+
+    /// Keep delivering pointer events to this window while the pointer is outside of it,
+    /// for example while a scrollbar is dragged.
+    ///
+    /// The capture ends with [`release_pointer`] or when the last mouse button is released.
+    /// The pointer is grabbed through glutin, which keeps it inside the window on X11.
+    /// On Wayland the pointer can only be grabbed if the compositor supports pointer
+    /// constraints; otherwise `mouse_leave` is still held back during the capture, but
+    /// whether moves outside of the window arrive depends on the compositor.
+    ///
+    /// [`release_pointer`]: WindowHandle::release_pointer
+    #[cfg(skia_backend)]
+    pub fn capture_pointer(&self) {
+        self.0.capture_pointer()
+    }
+
+    /// Release the pointer captured by [`capture_pointer`].
+    ///
+    /// [`capture_pointer`]: WindowHandle::capture_pointer
+    #[cfg(skia_backend)]
+    pub fn release_pointer(&self) {
+        self.0.release_pointer()
+    }
+
+    /// Read back the pixels of the next frame, they can be taken with [`capture`] once it
+    /// has been drawn.
+    ///
+    /// [`capture`]: WindowHandle::capture
+    #[cfg(any(skia_backend, headless_backend))]
+    pub fn request_capture(&self) {
+        self.0.request_capture()
+    }
+
+    /// Take the pixels of the frame drawn after the last [`request_capture`], and their
+    /// size in pixels.
+    ///
+    /// The pixels are RGBA8 with straight alpha, in rows from top to bottom. The headless
+    /// backend paints the window right away instead.
+    ///
+    /// [`request_capture`]: WindowHandle::request_capture
+    #[cfg(any(skia_backend, headless_backend))]
+    pub fn capture(&self) -> Result<(Vec<u8>, Size), Error> {
+        self.0.capture()
+    }
+
+    /// Paint the invalid parts of the window and read back all of its pixels.
+    ///
+    /// The pixels are RGBA8 with straight alpha, in rows from top to bottom.
+    #[cfg(headless_backend)]
+    pub fn render_to_image(&self) -> Result<Vec<u8>, Error> {
+        self.0.render_to_image()
+    }
 }
 
 /// A builder type for creating new windows.
@@ -371,6 +657,12 @@ impl WindowBuilder {
         self.0.set_min_size(size)
     }
 
+    /// Set the window's maximum drawing area size in [display points](crate::Scale).
+    #[cfg(skia_backend)]
+    pub fn set_max_size(&mut self, size: Size) {
+        self.0.set_max_size(size)
+    }
+
     /// Set whether the window should be resizable.
     pub fn resizable(&mut self, resizable: bool) {
         self.0.resizable(resizable)
@@ -387,6 +679,91 @@ impl WindowBuilder {
         self.0.set_position(position);
     }
 
+    /// Center the window on the work area of the primary monitor.
+    ///
+    /// The position is picked from the requested size when the window is created.
+    /// If no monitor information is available, the platform places the window.
+    #[cfg(skia_backend)]
+    pub fn center_on_primary_monitor(&mut self) {
+        self.0.center_on_primary_monitor();
+    }
+
+    /// Make the window see-through where nothing has been painted.
+    ///
+    /// Invalid regions are cleared to transparent before they are painted. This needs a
+    /// compositor, and doesn't work with the software renderer.
+    #[cfg(skia_backend)]
+    pub fn set_transparent(&mut self, transparent: bool) {
+        self.0.set_transparent(transparent);
+    }
+
+    /// Request multisampling with `samples` samples per pixel, for smoother edges.
+    ///
+    /// This should be 0 (the default, no multisampling), 2, 4 or 8. Multisampling is
+    /// disabled if the GL driver doesn't support the requested count.
+    #[cfg(skia_backend)]
+    pub fn set_sample_count(&mut self, samples: u16) {
+        self.0.set_sample_count(samples);
+    }
+
+    /// Set the color the window is cleared to before the handler paints.
+    ///
+    /// Only the damaged region is cleared, so partial redraws keep working. Without a
+    /// background color, areas the handler doesn't paint may show old or uninitialized pixels.
+    #[cfg(any(skia_backend, dri_backend))]
+    pub fn set_background_color(&mut self, color: Color) {
+        self.0.set_background_color(color);
+    }
+
+    /// Set how many buffers the swapchain of the window cycles through.
+    ///
+    /// Each frame redraws everything invalidated since the buffer being drawn into was
+    /// last shown, so this has to match the platform for partial redraws to be correct.
+    /// The default is 2, for double buffering. Use 3 on triple buffered compositors.
+    #[cfg(any(skia_backend, dri_backend))]
+    pub fn set_buffer_count(&mut self, count: usize) {
+        self.0.set_buffer_count(count);
+    }
+
+    /// Sync frames to the refresh of the display.
+    ///
+    /// With vsync on, frames are paced by the display instead of the
+    /// [target frame rate](crate::Application::set_target_fps), which gives the smoothest
+    /// output. With vsync off (the default) frames are shown as soon as they are
+    /// rendered, for the lowest latency. Vsync is disabled if the GL driver doesn't support it.
+    #[cfg(skia_backend)]
+    pub fn set_vsync(&mut self, vsync: bool) {
+        self.0.set_vsync(vsync);
+    }
+
+    /// Request a stencil buffer with `bits` bits per pixel for the window's GL context.
+    ///
+    /// The default is 8 bits, which skia uses to draw complex clips and paths; without a
+    /// stencil buffer those may be drawn with rough edges or not clipped correctly.
+    /// 0 requests no stencil buffer.
+    #[cfg(skia_backend)]
+    pub fn set_stencil_buffer(&mut self, bits: u8) {
+        self.0.set_stencil_buffer(bits);
+    }
+
+    /// Request a depth buffer with `bits` bits per pixel for the window's GL context.
+    ///
+    /// skia doesn't need one, so the default is 0, no depth buffer. If the GL driver can't
+    /// provide the requested stencil and depth buffers, the window is created without them.
+    #[cfg(skia_backend)]
+    pub fn set_depth_buffer(&mut self, bits: u8) {
+        self.0.set_depth_buffer(bits);
+    }
+
+    /// Set the taskbar and titlebar icon from RGBA8 pixels, in rows from top to bottom.
+    ///
+    /// The icon is ignored, and an error logged, if `rgba` doesn't hold exactly
+    /// `width * height` pixels. macOS takes the icon from the application bundle instead.
+    #[cfg(skia_backend)]
+    pub fn set_icon(&mut self, rgba: Vec<u8>, width: u32, height: u32) {
+        self.0.set_icon(rgba, width, height);
+    }
+
     /// Sets the initial [`WindowLevel`].
     pub fn set_level(&mut self, level: WindowLevel) {
         self.0.set_level(level);
@@ -398,6 +775,8 @@ impl WindowBuilder {
     }
 
     /// Set the window's menu.
+    ///
+    /// See [`WindowHandle::set_menu`] for the platforms that show it.
     pub fn set_menu(&mut self, menu: Menu) {
         self.0.set_menu(menu.into_inner())
     }
@@ -407,6 +786,20 @@ impl WindowBuilder {
         self.0.set_window_state(state);
     }
 
+    /// Set whether the window starts out borderless fullscreen.
+    #[cfg(any(skia_backend, dri_backend, headless_backend, target_arch = "wasm32"))]
+    pub fn set_fullscreen(&mut self, fullscreen: bool) {
+        self.0.set_fullscreen(fullscreen);
+    }
+
+    /// Set how the window is rotated to fit the display.
+    ///
+    /// By default windows are rotated by 90 degrees on portrait display modes.
+    #[cfg(dri_backend)]
+    pub fn set_rotation(&mut self, rotation: crate::Rotation) {
+        self.0.set_rotation(rotation);
+    }
+
     /// Attempt to construct the platform window.
     ///
     /// If this fails, your application should exit.
@@ -437,10 +830,20 @@ pub trait WinHandler {
 
     /// Called when the [scale](crate::Scale) of the window has changed.
     ///
-    /// This is always called before the accompanying [`size`](WinHandler::size).
+    /// This is always called before the accompanying [`size`](WinHandler::size). Besides
+    /// changes of the system settings, this happens when the window moves to a monitor with
+    /// a different scale; a window spanning two monitors follows the scale the platform
+    /// picks. The whole window is invalidated afterwards.
     #[allow(unused_variables)]
     fn scale(&mut self, scale: Scale) {}
 
+    /// Called when the window has been moved.
+    ///
+    /// The `position` parameter is the new position of the window in [pixels](crate::Scale),
+    /// relative to the origin of the virtual screen. Not every platform reports moves.
+    #[allow(unused_variables)]
+    fn position(&mut self, position: Point) {}
+
     /// Request the handler to prepare to paint the window contents.  In particular, if there are
     /// any regions that need to be repainted on the next call to `paint`, the handler should
     /// invalidate those regions by calling [`WindowHandle::invalidate_rect`] or
@@ -452,6 +855,23 @@ pub trait WinHandler {
     /// have no effect.
     fn paint(&mut self, piet: &mut piet_common::Piet, invalid: &Region);
 
+    /// Called right before [`paint`](WinHandler::paint) with the skia canvas of the window,
+    /// to draw behind the contents.
+    ///
+    /// The canvas is scaled to [display points](crate::Scale) and clipped to the invalid
+    /// region, like the `Piet` context passed to `paint`.
+    #[cfg(any(skia_backend, dri_backend))]
+    #[allow(unused_variables)]
+    fn pre_paint(&mut self, canvas: &mut skia_safe::Canvas) {}
+
+    /// Called right after [`paint`](WinHandler::paint) with the skia canvas of the window,
+    /// to draw on top of the contents.
+    ///
+    /// The canvas is set up the same way as for [`pre_paint`](WinHandler::pre_paint).
+    #[cfg(any(skia_backend, dri_backend))]
+    #[allow(unused_variables)]
+    fn post_paint(&mut self, canvas: &mut skia_safe::Canvas) {}
+
     /// Called when the resources need to be rebuilt.
     ///
     /// Discussion: this function is mostly motivated by using
@@ -478,6 +898,20 @@ pub trait WinHandler {
     #[allow(unused_variables)]
     fn open_file(&mut self, token: FileDialogToken, file: Option<FileInfo>) {}
 
+    /// Called when an "Open" dialog with [`multi_selection`] is closed.
+    ///
+    /// `token` is the value returned by [`WindowHandle::open_file`]. `files` contains the
+    /// information of every chosen path, it is empty if the dialog was cancelled. Backends
+    /// that can't select several files call [`open_file`] instead.
+    ///
+    /// The default implementation passes the first file to [`open_file`].
+    ///
+    /// [`multi_selection`]: crate::FileDialogOptions::multi_selection
+    /// [`open_file`]: WinHandler::open_file
+    fn open_files(&mut self, token: FileDialogToken, files: Vec<FileInfo>) {
+        self.open_file(token, files.into_iter().next())
+    }
+
     /// Called on a key down event.
     ///
     /// Return `true` if the event is handled.
@@ -502,6 +936,15 @@ pub trait WinHandler {
     #[allow(unused_variables)]
     fn wheel(&mut self, event: &MouseEvent) {}
 
+    /// Called with the relative motion of the mouse, while this is the focused window.
+    ///
+    /// Unlike [`mouse_move`](WinHandler::mouse_move), this reports the raw movement of the
+    /// device, in device specific units, and keeps coming when the cursor can't move any
+    /// further, for example at the edge of the screen or while the pointer is captured.
+    /// This suits camera controls in games and 3D viewers. Only the skia backend reports it.
+    #[allow(unused_variables)]
+    fn raw_mouse_motion(&mut self, delta: Vec2) {}
+
     /// Called when a platform-defined zoom gesture occurs (such as pinching
     /// on the trackpad).
     #[allow(unused_variables)]
@@ -538,6 +981,20 @@ pub trait WinHandler {
     #[allow(unused_variables)]
     fn lost_focus(&mut self) {}
 
+    /// Called with a value sent through a [`UserEventSender`], when this is the focused
+    /// window.
+    ///
+    /// [`UserEventSender`]: crate::UserEventSender
+    #[allow(unused_variables)]
+    fn user_event(&mut self, event: Box<dyn Any + Send>) {}
+
+    /// Called when the system switches between light and dark mode.
+    ///
+    /// Not every platform reports this; on the skia backend the theme is checked
+    /// again when a window gains focus.
+    #[allow(unused_variables)]
+    fn theme_changed(&mut self, theme: Theme) {}
+
     /// Called when the shell requests to close the window, for example because the user clicked
     /// the little "X" in the titlebar.
     ///
@@ -560,6 +1017,13 @@ pub trait WinHandler {
     fn as_any(&mut self) -> &mut dyn Any;
 }
 
+#[cfg(skia_backend)]
+unsafe impl raw_window_handle::HasRawWindowHandle for WindowHandle {
+    fn raw_window_handle(&self) -> raw_window_handle::RawWindowHandle {
+        self.0.raw_window_handle()
+    }
+}
+
 impl From<platform::WindowHandle> for WindowHandle {
     fn from(src: platform::WindowHandle) -> WindowHandle {
         WindowHandle(src)
@@ -574,4 +1038,31 @@ mod test {
 
     sa::assert_not_impl_any!(WindowHandle: Send, Sync);
     sa::assert_impl_all!(IdleHandle: Send);
+
+    #[test]
+    fn geometry_is_clamped_to_the_monitors() {
+        let primary = Rect::new(0., 0., 1920., 1080.);
+        let work_rect = Rect::new(0., 30., 1920., 1080.);
+        let secondary = Rect::new(1920., 0., 3840., 1080.);
+        let monitors = vec![
+            Monitor::new(true, primary, work_rect),
+            Monitor::new(false, secondary, secondary),
+        ];
+        let geometry = WindowGeometry {
+            position: Point::new(2000., 100.),
+            size: Size::new(800., 600.),
+            maximized: false,
+        };
+        assert_eq!(geometry.clamp_to_monitors(&monitors), geometry);
+
+        let disconnected = WindowGeometry {
+            position: Point::new(-1500., 100.),
+            ..geometry
+        };
+        let clamped = disconnected.clamp_to_monitors(&monitors);
+        assert_eq!(clamped.position, Point::new(0., 30.));
+        assert_eq!(clamped.size, geometry.size);
+
+        assert_eq!(disconnected.clamp_to_monitors(&[]), disconnected);
+    }
 }
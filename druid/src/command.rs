@@ -262,6 +262,13 @@ pub mod sys {
     /// [`FileInfo`]: ../struct.FileInfo.html
     pub const OPEN_FILE: Selector<FileInfo> = Selector::new("druid-builtin.open-file-path");
 
+    /// Open several paths, sent instead of [`OPEN_FILE`] by an open panel with
+    /// [`multi_selection`]. Must be handled by the application.
+    ///
+    /// [`OPEN_FILE`]: constant.OPEN_FILE.html
+    /// [`multi_selection`]: ../struct.FileDialogOptions.html#method.multi_selection
+    pub const OPEN_FILES: Selector<Vec<FileInfo>> = Selector::new("druid-builtin.open-files-path");
+
     /// When submitted by the application, the system will show the 'save as' panel,
     /// and if a path is selected the system will issue a [`SAVE_FILE`] command
     /// with the selected path as the payload.
@@ -115,6 +115,7 @@ use crate::{FileInfo, FileSpec, Selector};
 pub struct FileDialogOptions {
     pub(crate) opt: ShellOptions,
     pub(crate) accept_cmd: Option<Selector<FileInfo>>,
+    pub(crate) accept_multiple_cmd: Option<Selector<Vec<FileInfo>>>,
     pub(crate) cancel_cmd: Option<Selector<()>>,
 }
 
@@ -152,7 +153,11 @@ impl FileDialogOptions {
 
     /// Set multiple items to be selectable.
     ///
-    /// This is only relevant for open dialogs.
+    /// This is only relevant for open dialogs. The chosen files are sent with the
+    /// [`OPEN_FILES`] command, or with [`OPEN_FILE`] on platforms that can only select one.
+    ///
+    /// [`OPEN_FILES`]: crate::commands::OPEN_FILES
+    /// [`OPEN_FILE`]: crate::commands::OPEN_FILE
     pub fn multi_selection(mut self) -> Self {
         self.opt = self.opt.multi_selection();
         self
@@ -238,6 +243,17 @@ impl FileDialogOptions {
         self
     }
 
+    /// Sets a custom command to use when a file dialog with [`multi_selection`] succeeds.
+    ///
+    /// By default, the [`OPEN_FILES`] command is sent.
+    ///
+    /// [`multi_selection`]: #method.multi_selection
+    /// [`OPEN_FILES`]: crate::commands::OPEN_FILES
+    pub fn accept_multiple_command(mut self, cmd: Selector<Vec<FileInfo>>) -> Self {
+        self.accept_multiple_cmd = Some(cmd);
+        self
+    }
+
     /// Sets a custom command to use when the file dialog is cancelled.
     ///
     /// By default, an "open" dialog sends the [`OPEN_PANEL_CANCELLED`] command when it is cancelled, and a "save"
@@ -80,6 +80,8 @@ struct DialogInfo {
     id: WindowId,
     /// The command to send if the dialog is accepted.
     accept_cmd: Selector<FileInfo>,
+    /// The command to send if a dialog with multiple selection is accepted.
+    accept_multiple_cmd: Selector<Vec<FileInfo>>,
     /// The command to send if the dialog is cancelled.
     cancel_cmd: Selector<()>,
 }
@@ -658,6 +660,9 @@ impl<T: Data> AppState<T> {
             .map(|w| w.handle.clone());
 
         let accept_cmd = options.accept_cmd.unwrap_or(crate::commands::OPEN_FILE);
+        let accept_multiple_cmd = options
+            .accept_multiple_cmd
+            .unwrap_or(crate::commands::OPEN_FILES);
         let cancel_cmd = options
             .cancel_cmd
             .unwrap_or(crate::commands::OPEN_PANEL_CANCELLED);
@@ -668,6 +673,7 @@ impl<T: Data> AppState<T> {
                 DialogInfo {
                     id: window_id,
                     accept_cmd,
+                    accept_multiple_cmd,
                     cancel_cmd,
                 },
             );
@@ -683,6 +689,9 @@ impl<T: Data> AppState<T> {
             .get_mut(window_id)
             .map(|w| w.handle.clone());
         let accept_cmd = options.accept_cmd.unwrap_or(crate::commands::SAVE_FILE_AS);
+        let accept_multiple_cmd = options
+            .accept_multiple_cmd
+            .unwrap_or(crate::commands::OPEN_FILES);
         let cancel_cmd = options
             .cancel_cmd
             .unwrap_or(crate::commands::SAVE_PANEL_CANCELLED);
@@ -693,6 +702,7 @@ impl<T: Data> AppState<T> {
                 DialogInfo {
                     id: window_id,
                     accept_cmd,
+                    accept_multiple_cmd,
                     cancel_cmd,
                 },
             );
@@ -700,13 +710,34 @@ impl<T: Data> AppState<T> {
     }
 
     fn handle_dialog_response(&mut self, token: FileDialogToken, file_info: Option<FileInfo>) {
+        self.dispatch_dialog_response(token, |dialog_info| match file_info {
+            Some(info) => dialog_info.accept_cmd.with(info).to(dialog_info.id),
+            None => dialog_info.cancel_cmd.to(dialog_info.id),
+        });
+    }
+
+    fn handle_multiple_dialog_response(&mut self, token: FileDialogToken, files: Vec<FileInfo>) {
+        self.dispatch_dialog_response(token, |dialog_info| {
+            if files.is_empty() {
+                dialog_info.cancel_cmd.to(dialog_info.id)
+            } else {
+                dialog_info
+                    .accept_multiple_cmd
+                    .with(files)
+                    .to(dialog_info.id)
+            }
+        });
+    }
+
+    /// Send the command `make_cmd` builds for the dialog `token` belongs to.
+    fn dispatch_dialog_response(
+        &mut self,
+        token: FileDialogToken,
+        make_cmd: impl FnOnce(DialogInfo) -> Command,
+    ) {
         let mut inner = self.inner.borrow_mut();
         if let Some(dialog_info) = inner.file_dialogs.remove(&token) {
-            let cmd = if let Some(info) = file_info {
-                dialog_info.accept_cmd.with(info).to(dialog_info.id)
-            } else {
-                dialog_info.cancel_cmd.to(dialog_info.id)
-            };
+            let cmd = make_cmd(dialog_info);
             inner.append_command(cmd);
         } else {
             log::error!("unknown dialog token");
@@ -859,6 +890,10 @@ impl<T: Data> WinHandler for DruidHandler<T> {
         self.app_state.handle_dialog_response(token, file_info);
     }
 
+    fn open_files(&mut self, token: FileDialogToken, files: Vec<FileInfo>) {
+        self.app_state.handle_multiple_dialog_response(token, files);
+    }
+
     fn mouse_down(&mut self, event: &MouseEvent) {
         // TODO: double-click detection (or is this done in druid-shell?)
         let event = Event::MouseDown(event.clone().into());